@@ -1,7 +1,13 @@
-use crate::{CodePtr, CodeEntry, Balloon, cbdif, STEntry, Type, SidetableMeta, Idk, CtlType, Opcode};
+use crate::{CodePtr, CodeEntry, Balloon, cbdif, STEntry, Type, SidetableMeta, Idk, CtlType, Opcode, Trap, TrapKind, BlockSig, table_index, unwind};
+use std::ops::Range;
+use crate::num::{I32Binop, I32Unop, I32Relop, I64Binop, I64Unop, I64Relop, FBinop, FUnop, FRelop, Cvtop, Slot};
+use crate::mem::access_width;
 
 pub trait CPSCBD {
     type I32Val: Clone + From<i32>;
+    type I64Val: Clone + From<i64>;
+    type F32Val: Clone + From<f32>;
+    type F64Val: Clone + From<f64>;
     type StackVal: Clone + Into<Self::LocalVal>;
     type LocalVal: Clone + Into<Self::StackVal>;
     type CondVal: Balloon;
@@ -10,17 +16,56 @@ pub trait CPSCBD {
     fn pushi_imm(&mut self, x: i32);
     fn pushi(&mut self, x: Self::I32Val);
 
+    fn popl(&mut self) -> Self::I64Val;
+    fn pushl(&mut self, x: Self::I64Val);
+
+    fn popf(&mut self) -> Self::F32Val;
+    fn pushf(&mut self, x: Self::F32Val);
+
+    fn popd(&mut self) -> Self::F64Val;
+    fn pushd(&mut self, x: Self::F64Val);
+
     fn push(&mut self, x: Self::StackVal);
     fn pop(&mut self) -> Self::StackVal;
 
     fn set_local(&mut self, idx: i32, val: Self::LocalVal);
     fn get_local(&mut self, idx: i32) -> Self::LocalVal;
 
-    fn xfer_state(&mut self, stp: usize) -> usize;
-    fn cond_xfer_state(&mut self, cond: Self::CondVal, left_stp: usize, right_stp: usize) -> usize;
+    fn set_global(&mut self, idx: i32, val: Self::StackVal);
+    fn get_global(&mut self, idx: i32) -> Self::StackVal;
+
+    // each moves the values the target takes and returns its stp
+    fn xfer_state(&mut self, tgt: BrTgt) -> usize;
+    fn cond_xfer_state(&mut self, cond: Self::CondVal, left: BrTgt, right: BrTgt) -> usize;
+    fn table_xfer_state(&mut self, idx: Self::I32Val, tgts: &[BrTgt]) -> usize;
+
+    // only evaluators can trap, see crate::Trap
+    fn take_trap(&mut self) -> Option<TrapKind> {
+        None
+    }
 
     fn i32_add(&mut self, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
     fn i32_eqz(&mut self, x: Self::I32Val) -> Self::CondVal;
+    fn i32_binop(&mut self, op: I32Binop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
+    fn i32_unop(&mut self, op: I32Unop, x: Self::I32Val) -> Self::I32Val;
+    fn i32_relop(&mut self, op: I32Relop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
+    fn i64_add(&mut self, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val;
+    fn i64_binop(&mut self, op: I64Binop, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val;
+    fn i64_unop(&mut self, op: I64Unop, x: Self::I64Val) -> Self::I64Val;
+    fn i64_relop(&mut self, op: I64Relop, x: Self::I64Val, y: Self::I64Val) -> Self::I32Val;
+    fn i64_eqz(&mut self, x: Self::I64Val) -> Self::I32Val;
+    fn f32_binop(&mut self, op: FBinop, x: Self::F32Val, y: Self::F32Val) -> Self::F32Val;
+    fn f32_unop(&mut self, op: FUnop, x: Self::F32Val) -> Self::F32Val;
+    fn f32_relop(&mut self, op: FRelop, x: Self::F32Val, y: Self::F32Val) -> Self::I32Val;
+    fn f64_binop(&mut self, op: FBinop, x: Self::F64Val, y: Self::F64Val) -> Self::F64Val;
+    fn f64_unop(&mut self, op: FUnop, x: Self::F64Val) -> Self::F64Val;
+    fn f64_relop(&mut self, op: FRelop, x: Self::F64Val, y: Self::F64Val) -> Self::I32Val;
+    fn cvtop(&mut self, op: Cvtop, x: Self::StackVal) -> Self::StackVal;
+    fn select(&mut self, ty: Option<Type>, cond: Self::I32Val, x: Self::StackVal, y: Self::StackVal) -> Self::StackVal;
+    fn unreachable(&mut self);
+    fn ref_null(&mut self, t: Type) -> Self::StackVal;
+    fn ref_is_null(&mut self, x: Self::StackVal) -> Self::I32Val;
+    fn ref_func(&mut self, func_idx: usize) -> Self::StackVal;
 
     fn cbd_i32_const(&mut self, x: i32) {
         self.pushi(x.into());
@@ -33,6 +78,151 @@ pub trait CPSCBD {
         self.pushi(z);
     }
 
+    fn cbd_i32_binop(&mut self, op: I32Binop) {
+        let y = self.popi();
+        let x = self.popi();
+        let z = self.i32_binop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i32_unop(&mut self, op: I32Unop) {
+        let x = self.popi();
+        let z = self.i32_unop(op, x);
+        self.pushi(z);
+    }
+
+    fn cbd_i32_relop(&mut self, op: I32Relop) {
+        let y = self.popi();
+        let x = self.popi();
+        let z = self.i32_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i64_const(&mut self, x: i64) {
+        self.pushl(x.into());
+    }
+
+    fn cbd_i64_add(&mut self) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_add(x, y);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_binop(&mut self, op: I64Binop) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_binop(op, x, y);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_unop(&mut self, op: I64Unop) {
+        let x = self.popl();
+        let z = self.i64_unop(op, x);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_relop(&mut self, op: I64Relop) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i64_eqz(&mut self) {
+        let x = self.popl();
+        let z = self.i64_eqz(x);
+        self.pushi(z);
+    }
+
+    fn cbd_f32_const(&mut self, x: f32) {
+        self.pushf(x.into());
+    }
+
+    fn cbd_f32_binop(&mut self, op: FBinop) {
+        let y = self.popf();
+        let x = self.popf();
+        let z = self.f32_binop(op, x, y);
+        self.pushf(z);
+    }
+
+    fn cbd_f32_unop(&mut self, op: FUnop) {
+        let x = self.popf();
+        let z = self.f32_unop(op, x);
+        self.pushf(z);
+    }
+
+    fn cbd_f32_relop(&mut self, op: FRelop) {
+        let y = self.popf();
+        let x = self.popf();
+        let z = self.f32_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_f64_const(&mut self, x: f64) {
+        self.pushd(x.into());
+    }
+
+    fn cbd_f64_binop(&mut self, op: FBinop) {
+        let y = self.popd();
+        let x = self.popd();
+        let z = self.f64_binop(op, x, y);
+        self.pushd(z);
+    }
+
+    fn cbd_f64_unop(&mut self, op: FUnop) {
+        let x = self.popd();
+        let z = self.f64_unop(op, x);
+        self.pushd(z);
+    }
+
+    fn cbd_f64_relop(&mut self, op: FRelop) {
+        let y = self.popd();
+        let x = self.popd();
+        let z = self.f64_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_cvtop(&mut self, op: Cvtop) {
+        let x = self.pop();
+        let z = self.cvtop(op, x);
+        self.push(z);
+    }
+
+    fn cbd_nop(&mut self) { }
+
+    fn cbd_unreachable(&mut self) {
+        self.unreachable();
+    }
+
+    fn cbd_drop(&mut self) {
+        self.pop();
+    }
+
+    fn cbd_select(&mut self, ty: Option<Type>) {
+        let cond = self.popi();
+        let y = self.pop();
+        let x = self.pop();
+        let z = self.select(ty, cond, x, y);
+        self.push(z);
+    }
+
+    fn cbd_ref_null(&mut self, t: Type) {
+        let x = self.ref_null(t);
+        self.push(x);
+    }
+
+    fn cbd_ref_is_null(&mut self) {
+        let x = self.pop();
+        let z = self.ref_is_null(x);
+        self.pushi(z);
+    }
+
+    fn cbd_ref_func(&mut self, func_idx: usize) {
+        let x = self.ref_func(func_idx);
+        self.push(x);
+    }
+
     fn cbd_local_set(&mut self, idx: i32) {
         let val = self.pop();
         self.set_local(idx, val.into());
@@ -49,20 +239,51 @@ pub trait CPSCBD {
         self.set_local(idx, val.into());
     }
 
-    fn cbd_block(&mut self, typ_idx: usize) {
+    fn cbd_global_get(&mut self, idx: i32) {
+        let val = self.get_global(idx);
+        self.push(val);
+    }
+
+    fn cbd_global_set(&mut self, idx: i32) {
+        let val = self.pop();
+        self.set_global(idx, val);
+    }
+
+    fn cbd_block(&mut self, _ty: BlockSig) {
+    }
+
+    fn cbd_loop(&mut self, _ty: BlockSig) {
+    }
+
+    fn cbd_if(&mut self, _ty: BlockSig, els: BrTgt, then_stp: usize) -> usize {
+        let condv = self.popi();
+        let condb = self.i32_eqz(condv);
+        self.cond_xfer_state(condb, els, BrTgt::fallthru(then_stp))
+    }
+
+    // only reached by falling out of the then arm
+    fn cbd_else(&mut self, end: BrTgt) -> usize {
+        self.xfer_state(end)
     }
 
-    fn cbd_loop(&mut self, typ_idx: usize) {
+    fn cbd_br(&mut self, target: BrTgt) -> usize {
+        self.xfer_state(target)
     }
 
-    fn cbd_br(&mut self, target_stp: usize) -> usize {
-        self.xfer_state(target_stp)
+    // a br to the function's label
+    fn cbd_return(&mut self, end: BrTgt) -> usize {
+        self.xfer_state(end)
     }
 
-    fn cbd_br_if(&mut self, target_stp: usize, fallthru_stp: usize) -> usize {
+    fn cbd_br_if(&mut self, target: BrTgt, fallthru_stp: usize) -> usize {
         let condv = self.popi();
         let condb = self.i32_eqz(condv);
-        self.cond_xfer_state(condb, fallthru_stp, target_stp)
+        self.cond_xfer_state(condb, BrTgt::fallthru(fallthru_stp), target)
+    }
+
+    fn cbd_br_table(&mut self, targets: &[BrTgt]) -> usize {
+        let idx = self.popi();
+        self.table_xfer_state(idx, targets)
     }
     
     fn cbd_end(&mut self) {
@@ -86,18 +307,47 @@ pub struct WASMFun {
 #[derive(Debug)]
 pub struct Cont {
     pub ip: usize,
+    // the branches ending the previous cont, empty for a fallthru and
+    // several for a br_table
+    pub from_branches: Range<usize>,
 }
 
+// val_count and pop_count as in the branch's STEntry
 #[derive(Debug)]
 pub struct Branch {
     pub tgt_idx: usize,
+    pub val_count: usize,
+    pub pop_count: usize,
+}
+
+impl Branch {
+    fn new(tgt_idx: usize) -> Self {
+        Branch { tgt_idx, val_count: 0, pop_count: 0 }
+    }
+}
+
+// a branch's target ContBlock and the stack adjustment getting there: the
+// top val_count values move over and the pop_count under them are dropped
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BrTgt {
+    pub stp: usize,
+    pub val_count: usize,
+    pub pop_count: usize,
+}
+
+impl BrTgt {
+    // falling through leaves the stack as it is
+    pub fn fallthru(stp: usize) -> Self {
+        BrTgt { stp, val_count: 0, pop_count: 0 }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct ContBlock {
     pub ip: usize,
     // pub fallthru_cont: usize, // easily found at runtime, just the next ContBlock
-    pub br_tgt: usize, // only for blocks ended by a Br
+    pub br_tgt: BrTgt, // only for blocks ended by a Br
+    pub br_tgts: Vec<BrTgt>, // every target of the ending branch, for a BrTable the default last
     // pub data: T
 }
 
@@ -110,7 +360,9 @@ pub struct CtlEntry {
 }
 
 impl WASMFun {
-    pub fn new(code: Vec<CodeEntry>) -> Self {
+    // the sidetable is the validator's for the same code, which says what
+    // each branch carries. An empty one carries nothing.
+    pub fn new(code: Vec<CodeEntry>, sidetable: &[STEntry]) -> Self {
         let mut ctls = vec![
             CtlEntry {
                 ty: CtlType::Func,
@@ -122,14 +374,36 @@ impl WASMFun {
         let mut ctl_stack = vec![0];
         let mut codeptr = CodePtr { code, ip: 0 };
 
-        let mut conts = vec![Cont { ip: 0 }];
+        let mut conts = vec![Cont { ip: 0, from_branches: 0..0 }];
         let mut branches = vec![];
 
         while let Some(op) = codeptr.next() {
             use {Opcode::*, CodeEntry::*};
             match op {
-                Op(I32Const | LocalSet | LocalGet) => {
+                Op(I32Const | LocalSet | LocalGet | LocalTee | GlobalGet | GlobalSet | Call | RefFunc) => {
+                    codeptr.read_imm_i32();
+                }
+                Op(TableGet | TableSet | TableSize | TableGrow | TableFill | ElemDrop | MemoryInit | DataDrop) => {
+                    codeptr.read_imm_i32();
+                }
+                Op(SelectTyped | RefNull) => {
+                    codeptr.read_val_type();
+                }
+                Op(I64Const) => {
+                    codeptr.read_imm_i64();
+                }
+                Op(F32Const) => {
+                    codeptr.read_imm_f32();
+                }
+                Op(F64Const) => {
+                    codeptr.read_imm_f64();
+                }
+                Op(CallIndirect | TableCopy | TableInit) => {
                     codeptr.read_imm_i32();
+                    codeptr.read_imm_i32();
+                }
+                &Op(op) if access_width(op).is_some() => {
+                    codeptr.read_memarg();
                 }
                 Op(Block) => {
                     let _bt = codeptr.read_block_type();
@@ -150,59 +424,105 @@ impl WASMFun {
                         cont_idx: conts.len(),
                         fallthru_ip: 0,
                     });
-                    conts.push(Cont { ip: codeptr.ip });
+                    conts.push(Cont { ip: codeptr.ip, from_branches: 0..0 });
+                }
+                // the false edge targets a ctl of its own, one past the if's,
+                // which lands after the else or at the end if there is none
+                Op(If) => {
+                    let _bt = codeptr.read_block_type();
+                    ctl_stack.push(ctls.len());
+                    ctls.push(CtlEntry {
+                        ty: CtlType::If,
+                        entry_ip: codeptr.ip,
+                        cont_idx: 0,
+                        fallthru_ip: 0,
+                    });
+                    ctls.push(CtlEntry {
+                        ty: CtlType::IfFalse,
+                        entry_ip: codeptr.ip,
+                        cont_idx: 0,
+                        fallthru_ip: 0,
+                    });
+
+                    branches.push(Branch::new(ctls.len() - 1));
+                    conts.push(Cont { ip: codeptr.ip, from_branches: branches.len() - 1..branches.len() });
+                }
+                Op(Else) => {
+                    let ctl_idx = ctl_stack[ctl_stack.len() - 1];
+                    ctls[ctl_idx].ty = CtlType::Else;
+
+                    branches.push(Branch::new(ctl_idx));
+                    conts.push(Cont { ip: codeptr.ip, from_branches: branches.len() - 1..branches.len() });
+                    ctls[ctl_idx + 1].cont_idx = conts.len() - 1;
                 }
                 Op(End) => {
                     let ctl_idx = ctl_stack.pop().unwrap();
                     let ctl = &mut ctls[ctl_idx];
                     ctl.fallthru_ip = codeptr.ip;
-                    if ctl.ty == CtlType::Block {
+                    if ctl.ty != CtlType::Loop {
                         ctl.cont_idx = conts.len();
                     }
-                    conts.push(Cont { ip: codeptr.ip });
+                    if ctl.ty == CtlType::If {
+                        ctls[ctl_idx + 1].cont_idx = conts.len();
+                    }
+                    conts.push(Cont { ip: codeptr.ip, from_branches: 0..0 });
+                }
+                Op(BrTable) => {
+                    let labels = codeptr.read_labels();
+
+                    let first = branches.len();
+                    for depth in labels {
+                        let ctl_idx = ctl_stack[ctl_stack.len() - 1 - depth];
+                        branches.push(Branch::new(ctl_idx));
+                    }
+                    conts.push(Cont { ip: codeptr.ip, from_branches: first..branches.len() });
+                }
+                // a branch to the function's label
+                Op(Return) => {
+                    branches.push(Branch::new(0));
+                    conts.push(Cont { ip: codeptr.ip, from_branches: branches.len() - 1..branches.len() });
                 }
                 Op(BrIf | Br) => {
                     let depth = codeptr.read_imm_i32() as usize;
 
-                    conts.push(Cont { ip: codeptr.ip });
                     let ctl_idx = ctl_stack[ctl_stack.len() - 1 - depth];
-                    branches.push(Branch { tgt_idx: ctl_idx });
+                    branches.push(Branch::new(ctl_idx));
+                    conts.push(Cont { ip: codeptr.ip, from_branches: branches.len() - 1..branches.len() });
                 }
                 Op(_) => {},
-                I32Imm(_) | BlockType(_) => panic!(),
+                I32Imm(_) | I64Imm(_) | F32Imm(_) | F64Imm(_) | BlockType(_) | Labels(_) | MemArg(_) | ValType(_) => panic!(),
             }
         }
 
+        // an entry per branch, in the same order after the unused first
+        for (br, ste) in branches.iter_mut().zip(sidetable.iter().skip(1)) {
+            br.val_count = ste.val_count;
+            br.pop_count = ste.pop_count;
+        }
+
         dbg!(&conts);
         dbg!(&branches);
 
         let code = codeptr.code;
 
         let mut cont_blocks = vec![];
-        let mut br_idx = 0;
         for i in 0..(conts.len() - 1) {
             let current_cont = &conts[i];
             let next_cont = &conts[i + 1];
 
             let ip = current_cont.ip;
-            let br_tgt = {
-                match code[next_cont.ip - 2] {
-                    CodeEntry::Op(Opcode::Br | Opcode::BrIf) => {
-                        let ctl_idx = branches[br_idx].tgt_idx;
-                        let cont_idx = ctls[ctl_idx].cont_idx;
-                        br_idx += 1;
-                        cont_idx
-                    }
-                    _ => 0,
-                }
-            };
-            cont_blocks.push(ContBlock { ip, br_tgt });
+            let br_tgts: Vec<BrTgt> = branches[next_cont.from_branches.clone()].iter()
+                .map(|br| BrTgt { stp: ctls[br.tgt_idx].cont_idx, val_count: br.val_count, pop_count: br.pop_count })
+                .collect();
+            let br_tgt = br_tgts.first().copied().unwrap_or_default();
+            cont_blocks.push(ContBlock { ip, br_tgt, br_tgts });
         }
 
         let last_cont = &conts[conts.len() - 1];
         cont_blocks.push(ContBlock {
             ip: last_cont.ip,
-            br_tgt: 0,
+            br_tgt: BrTgt::default(),
+            br_tgts: vec![],
         });
 
         Self {
@@ -214,63 +534,91 @@ impl WASMFun {
     }
 
     // this is bad because it can't handle multiple simultaneous out-branches
-    pub fn run<I: CPSCBD>(&mut self, mut interpreter: I) -> I {
+    pub fn run<I: CPSCBD>(&mut self, mut interpreter: I) -> Result<I, Trap> {
         let mut codeptr = CodePtr { code: std::mem::take(&mut self.code), ip: 0 };
         let mut current_block = 0;
+        let mut trap = None;
 
-        while let Some(op) = codeptr.next() {
+        loop {
+            let ip = codeptr.ip;
+            let Some(op) = codeptr.next() else { break };
             use {Opcode::*, CodeEntry::*};
             match op {
-                Op(I32Const) => {
-                    let imm = codeptr.read_imm_i32();
-                    interpreter.cbd_i32_const(imm);
-                }
-                Op(I32Add) => interpreter.cbd_i32_add(),
-                Op(LocalSet) => {
-                    let local_idx = codeptr.read_imm_i32();
-                    interpreter.cbd_local_set(local_idx);
-                }
-                Op(LocalGet) => {
-                    let local_idx = codeptr.read_imm_i32();
-                    interpreter.cbd_local_get(local_idx);
-                }
-                Op(Block) => {
-                    let typ_idx = codeptr.read_block_type(); 
-                    interpreter.cbd_block(typ_idx);
-                }
                 Op(Loop) => {
-                    let typ_idx = codeptr.read_block_type(); 
-                    interpreter.cbd_loop(typ_idx);
+                    let ty = codeptr.read_block_type();
+                    interpreter.cbd_loop(ty);
                     current_block += 1;
                 }
                 Op(Br) => {
                     let _depth = codeptr.read_imm_i32();
-                    let cur_block = self.cont_blocks[current_block];
+                    let cur_block = &self.cont_blocks[current_block];
                     let tgt_block = cur_block.br_tgt;
                     let end_block = interpreter.cbd_br(tgt_block);
 
                     current_block = end_block;
                     codeptr.ip = self.cont_blocks[current_block].ip;
                 }
+                Op(Return) => {
+                    let end_block = interpreter.cbd_return(self.cont_blocks[current_block].br_tgt);
+
+                    current_block = end_block;
+                    codeptr.ip = self.cont_blocks[current_block].ip;
+                }
                 Op(BrIf) => {
                     let _depth = codeptr.read_imm_i32();
-                    let cur_block = self.cont_blocks[current_block];
+                    let cur_block = &self.cont_blocks[current_block];
                     let tgt_block = cur_block.br_tgt;
                     let end_block = interpreter.cbd_br_if(tgt_block, current_block + 1);
 
                     current_block = end_block;
                     codeptr.ip = self.cont_blocks[current_block].ip;
                 }
+                Op(If) => {
+                    let ty = codeptr.read_block_type();
+                    let cur_block = &self.cont_blocks[current_block];
+                    let end_block = interpreter.cbd_if(ty, cur_block.br_tgt, current_block + 1);
+
+                    current_block = end_block;
+                    codeptr.ip = self.cont_blocks[current_block].ip;
+                }
+                Op(Else) => {
+                    let cur_block = &self.cont_blocks[current_block];
+                    let end_block = interpreter.cbd_else(cur_block.br_tgt);
+
+                    current_block = end_block;
+                    codeptr.ip = self.cont_blocks[current_block].ip;
+                }
+                Op(BrTable) => {
+                    let _labels = codeptr.read_labels();
+                    let cur_block = &self.cont_blocks[current_block];
+                    let end_block = interpreter.cbd_br_table(&cur_block.br_tgts);
+
+                    current_block = end_block;
+                    codeptr.ip = self.cont_blocks[current_block].ip;
+                }
                 Op(End) => {
                     interpreter.cbd_end();
                     current_block += 1;
                 }
-                _ => panic!(),
+                &Op(op) => {
+                    if let Err(kind) = step(&mut interpreter, op, &mut codeptr) {
+                        trap = Some(Trap { kind, ip });
+                        break;
+                    }
+                }
+                I32Imm(_) | I64Imm(_) | F32Imm(_) | F64Imm(_) | BlockType(_) | Labels(_) | MemArg(_) | ValType(_) => panic!(),
+            }
+            if let Some(kind) = interpreter.take_trap() {
+                trap = Some(Trap { kind, ip });
+                break;
             }
         }
 
         self.code = codeptr.code;
-        interpreter
+        match trap {
+            Some(trap) => Err(trap),
+            None => Ok(interpreter),
+        }
     }
 
     // TODO:
@@ -282,131 +630,415 @@ impl WASMFun {
         for current_block in 0..self.cont_blocks.len() {
             let start_ip = self.cont_blocks[current_block].ip;
             let tgt_block = self.cont_blocks[current_block].br_tgt;
+            let tgt_blocks = self.cont_blocks[current_block].br_tgts.clone();
 
             let fallthru_block = current_block + 1;
 
             res.conts.push(Box::new(move |compiled: *const CompiledFun<I>, mut interpreter: I, codeptr: &mut CodePtr| unsafe {
                 codeptr.ip = start_ip;
                 // TODO: xfer state into this cont
-                while let Some(op) = codeptr.next() {
+                loop {
+                    let ip = codeptr.ip;
+                    let Some(op) = codeptr.next() else { break };
                     use {Opcode::*, CodeEntry::*};
                     match op {
-                        Op(I32Const) => {
-                            let imm = codeptr.read_imm_i32();
-                            interpreter.cbd_i32_const(imm);
-                        }
-                        Op(I32Add) => interpreter.cbd_i32_add(),
-                        Op(LocalSet) => {
-                            let local_idx = codeptr.read_imm_i32();
-                            interpreter.cbd_local_set(local_idx);
-                        }
-                        Op(LocalGet) => {
-                            let local_idx = codeptr.read_imm_i32();
-                            interpreter.cbd_local_get(local_idx);
-                        }
-                        Op(Block) => {
-                            let typ_idx = codeptr.read_block_type(); 
-                            interpreter.cbd_block(typ_idx);
-                        }
                         Op(Loop) => {
-                            let typ_idx = codeptr.read_block_type(); 
-                            interpreter.cbd_loop(typ_idx);
+                            let ty = codeptr.read_block_type();
+                            interpreter.cbd_loop(ty);
 
-                            let cont = &(*compiled).conts[fallthru_block];
+                            let cont = &(&(*compiled).conts)[fallthru_block];
                             return cont(compiled, interpreter, codeptr);
                         }
                         Op(Br) => {
                             let _depth = codeptr.read_imm_i32();
+                            let end_block = interpreter.cbd_br(tgt_block);
+
+                            let cont = &(&(*compiled).conts)[end_block];
+                            return cont(compiled, interpreter, codeptr);
+                        }
+                        Op(Return) => {
+                            let end_block = interpreter.cbd_return(tgt_block);
 
-                            let cont = &(*compiled).conts[tgt_block];
+                            let cont = &(&(*compiled).conts)[end_block];
                             return cont(compiled, interpreter, codeptr);
                         }
                         Op(BrIf) => {
                             let _depth = codeptr.read_imm_i32();
                             let end_block = interpreter.cbd_br_if(tgt_block, fallthru_block);
 
-                            let cont = &(*compiled).conts[end_block];
+                            let cont = &(&(*compiled).conts)[end_block];
+                            return cont(compiled, interpreter, codeptr);
+                        }
+                        Op(If) => {
+                            let ty = codeptr.read_block_type();
+                            let end_block = interpreter.cbd_if(ty, tgt_block, fallthru_block);
+
+                            let cont = &(&(*compiled).conts)[end_block];
+                            return cont(compiled, interpreter, codeptr);
+                        }
+                        Op(Else) => {
+                            let end_block = interpreter.cbd_else(tgt_block);
+
+                            let cont = &(&(*compiled).conts)[end_block];
+                            return cont(compiled, interpreter, codeptr);
+                        }
+                        Op(BrTable) => {
+                            let _labels = codeptr.read_labels();
+                            let end_block = interpreter.cbd_br_table(&tgt_blocks);
+
+                            let cont = &(&(*compiled).conts)[end_block];
                             return cont(compiled, interpreter, codeptr);
                         }
                         Op(End) => {
                             interpreter.cbd_end();
 
-                            let cont = &(*compiled).conts[fallthru_block];
+                            let cont = &(&(*compiled).conts)[fallthru_block];
                             return cont(compiled, interpreter, codeptr);
                         }
+                        &Op(op) => step(&mut interpreter, op, codeptr).map_err(|kind| Trap { kind, ip })?,
                         _ => {
                             dbg!(op);
                             panic!();
                         }
                     }
+                    if let Some(kind) = interpreter.take_trap() {
+                        return Err(Trap { kind, ip });
+                    }
                 }
-                return interpreter
+                return Ok(interpreter)
             }));
         }
-        res.conts.push(Box::new(|_, i, _| i));
+        res.conts.push(Box::new(|_, i, _| Ok(i)));
 
         res
     }
 }
 
+// straight-line ops, i.e. everything that can't end a ContBlock. A WASMFun
+// is a single function with no memory or tables, so the ops that need them
+// are Unsupported.
+fn step<I: CPSCBD>(interpreter: &mut I, op: Opcode, codeptr: &mut CodePtr) -> Result<(), TrapKind> {
+    use Opcode::*;
+    match op {
+        I32Const => {
+            let imm = codeptr.read_imm_i32();
+            interpreter.cbd_i32_const(imm);
+        }
+        I32Add => interpreter.cbd_i32_add(),
+        I64Const => {
+            let imm = codeptr.read_imm_i64();
+            interpreter.cbd_i64_const(imm);
+        }
+        I64Add => interpreter.cbd_i64_add(),
+        I64Eqz => interpreter.cbd_i64_eqz(),
+        F32Const => {
+            let imm = codeptr.read_imm_f32();
+            interpreter.cbd_f32_const(imm);
+        }
+        F64Const => {
+            let imm = codeptr.read_imm_f64();
+            interpreter.cbd_f64_const(imm);
+        }
+        LocalSet => {
+            let local_idx = codeptr.read_imm_i32();
+            interpreter.cbd_local_set(local_idx);
+        }
+        LocalGet => {
+            let local_idx = codeptr.read_imm_i32();
+            interpreter.cbd_local_get(local_idx);
+        }
+        LocalTee => {
+            let local_idx = codeptr.read_imm_i32();
+            interpreter.cbd_local_tee(local_idx);
+        }
+        Unreachable => interpreter.cbd_unreachable(),
+        Nop => interpreter.cbd_nop(),
+        Drop => interpreter.cbd_drop(),
+        Select => interpreter.cbd_select(None),
+        SelectTyped => {
+            let ty = codeptr.read_val_type();
+            interpreter.cbd_select(Some(ty));
+        }
+        RefNull => {
+            let ty = codeptr.read_val_type();
+            interpreter.cbd_ref_null(ty);
+        }
+        RefIsNull => interpreter.cbd_ref_is_null(),
+        RefFunc => {
+            let func_idx = codeptr.read_imm_i32();
+            interpreter.cbd_ref_func(func_idx as usize);
+        }
+        GlobalGet => {
+            let global_idx = codeptr.read_imm_i32();
+            interpreter.cbd_global_get(global_idx);
+        }
+        GlobalSet => {
+            let global_idx = codeptr.read_imm_i32();
+            interpreter.cbd_global_set(global_idx);
+        }
+        Block => {
+            let ty = codeptr.read_block_type();
+            interpreter.cbd_block(ty);
+        }
+        Call | CallIndirect => return Err(TrapKind::Unsupported),
+        _ if access_width(op).is_some() || matches!(op, MemorySize | MemoryGrow | MemoryInit | DataDrop | MemoryCopy | MemoryFill) => return Err(TrapKind::Unsupported),
+        TableGet | TableSet | TableSize | TableGrow | TableFill | TableCopy | TableInit | ElemDrop => return Err(TrapKind::Unsupported),
+        _ => {
+            if let Some(op) = I32Binop::from_opcode(op) {
+                interpreter.cbd_i32_binop(op);
+            } else if let Some(op) = I32Unop::from_opcode(op) {
+                interpreter.cbd_i32_unop(op);
+            } else if let Some(op) = I32Relop::from_opcode(op) {
+                interpreter.cbd_i32_relop(op);
+            } else if let Some(op) = I64Binop::from_opcode(op) {
+                interpreter.cbd_i64_binop(op);
+            } else if let Some(op) = I64Unop::from_opcode(op) {
+                interpreter.cbd_i64_unop(op);
+            } else if let Some(op) = I64Relop::from_opcode(op) {
+                interpreter.cbd_i64_relop(op);
+            } else if let Some(op) = FBinop::from_f32_opcode(op) {
+                interpreter.cbd_f32_binop(op);
+            } else if let Some(op) = FUnop::from_f32_opcode(op) {
+                interpreter.cbd_f32_unop(op);
+            } else if let Some(op) = FRelop::from_f32_opcode(op) {
+                interpreter.cbd_f32_relop(op);
+            } else if let Some(op) = FBinop::from_f64_opcode(op) {
+                interpreter.cbd_f64_binop(op);
+            } else if let Some(op) = FUnop::from_f64_opcode(op) {
+                interpreter.cbd_f64_unop(op);
+            } else if let Some(op) = FRelop::from_f64_opcode(op) {
+                interpreter.cbd_f64_relop(op);
+            } else if let Some(op) = Cvtop::from_opcode(op) {
+                interpreter.cbd_cvtop(op);
+            } else {
+                return Err(TrapKind::Unsupported);
+            }
+        }
+    }
+    Ok(())
+}
+
 pub struct CompiledFun<I: CPSCBD> {
-    pub conts: Vec<Box<dyn Fn(*const CompiledFun<I>, I, &mut CodePtr) -> I>>,
+    pub conts: Vec<Box<dyn Fn(*const CompiledFun<I>, I, &mut CodePtr) -> Result<I, Trap>>>,
 }
 
 #[derive(Debug)]
 pub struct CPSEval {
-    pub stack: Vec<i32>,
-    pub locals: Vec<i32>,
+    pub stack: Vec<Slot>,
+    pub locals: Vec<Slot>,
+    pub globals: Vec<Slot>,
+    pub trap: Option<TrapKind>,
+}
+
+impl CPSEval {
+    fn set_trap(&mut self, kind: TrapKind) {
+        self.trap.get_or_insert(kind);
+    }
 }
 
 impl CPSCBD for CPSEval {
     type I32Val = i32;
-    type StackVal = i32;
-    type LocalVal = i32;
+    type I64Val = i64;
+    type F32Val = f32;
+    type F64Val = f64;
+    type StackVal = Slot;
+    type LocalVal = Slot;
     type CondVal = bool;
 
     fn popi(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+        self.pop().i32()
     }
 
     fn pushi_imm(&mut self, x: i32) {
         self.pushi(x)
     }
     fn pushi(&mut self, x: i32) {
-        self.stack.push(x)
+        self.stack.push(x.into())
+    }
+
+    fn popl(&mut self) -> i64 {
+        self.pop().i64()
     }
 
-    fn push(&mut self, x: i32) {
+    fn pushl(&mut self, x: i64) {
+        self.stack.push(x.into())
+    }
+
+    fn popf(&mut self) -> f32 {
+        self.pop().f32()
+    }
+
+    fn pushf(&mut self, x: f32) {
+        self.stack.push(x.into())
+    }
+
+    fn popd(&mut self) -> f64 {
+        self.pop().f64()
+    }
+
+    fn pushd(&mut self, x: f64) {
+        self.stack.push(x.into())
+    }
+
+    fn push(&mut self, x: Slot) {
         self.stack.push(x)
     }
-    fn pop(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+    fn pop(&mut self) -> Slot {
+        self.stack.pop().unwrap_or_else(|| {
+            self.set_trap(TrapKind::StackUnderflow);
+            Slot::default()
+        })
+    }
+
+    fn set_local(&mut self, idx: i32, val: Slot) {
+        match self.locals.get_mut(idx as usize) {
+            Some(local) => *local = val,
+            None => self.set_trap(TrapKind::LocalOutOfRange),
+        }
+    }
+
+    fn get_local(&mut self, idx: i32) -> Slot {
+        match self.locals.get(idx as usize) {
+            Some(&local) => local,
+            None => {
+                self.set_trap(TrapKind::LocalOutOfRange);
+                Slot::default()
+            }
+        }
     }
 
-    fn set_local(&mut self, idx: i32, val: i32) {
-        self.locals[idx as usize] = val;
+    fn set_global(&mut self, idx: i32, val: Slot) {
+        match self.globals.get_mut(idx as u32 as usize) {
+            Some(global) => *global = val,
+            None => self.set_trap(TrapKind::GlobalOutOfRange),
+        }
     }
 
-    fn get_local(&mut self, idx: i32) -> i32 {
-        self.locals[idx as usize]
+    fn get_global(&mut self, idx: i32) -> Slot {
+        match self.globals.get(idx as u32 as usize) {
+            Some(&global) => global,
+            None => {
+                self.set_trap(TrapKind::GlobalOutOfRange);
+                Slot::default()
+            }
+        }
     }
 
     fn i32_add(&mut self, x: i32, y: i32) -> i32 {
-        x + y
+        x.wrapping_add(y)
     }
 
     fn i32_eqz(&mut self, x: i32) -> bool {
         x == 0
     }
 
-    fn xfer_state(&mut self, stp: usize) -> usize { stp }
-    fn cond_xfer_state(&mut self, cond: bool, left_stp: usize, right_stp: usize) -> usize { 
-        if cond { left_stp } else { right_stp }
+    fn i32_binop(&mut self, op: I32Binop, x: i32, y: i32) -> i32 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
+
+    fn i32_unop(&mut self, op: I32Unop, x: i32) -> i32 {
+        op.eval(x)
+    }
+
+    fn i32_relop(&mut self, op: I32Relop, x: i32, y: i32) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn i64_add(&mut self, x: i64, y: i64) -> i64 {
+        x.wrapping_add(y)
+    }
+
+    fn i64_binop(&mut self, op: I64Binop, x: i64, y: i64) -> i64 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
+
+    fn i64_unop(&mut self, op: I64Unop, x: i64) -> i64 {
+        op.eval(x)
+    }
+
+    fn i64_relop(&mut self, op: I64Relop, x: i64, y: i64) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn i64_eqz(&mut self, x: i64) -> i32 {
+        (x == 0) as i32
+    }
+
+    fn f32_binop(&mut self, op: FBinop, x: f32, y: f32) -> f32 {
+        op.eval(x, y)
+    }
+
+    fn f32_unop(&mut self, op: FUnop, x: f32) -> f32 {
+        op.eval(x)
+    }
+
+    fn f32_relop(&mut self, op: FRelop, x: f32, y: f32) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn f64_binop(&mut self, op: FBinop, x: f64, y: f64) -> f64 {
+        op.eval(x, y)
+    }
+
+    fn f64_unop(&mut self, op: FUnop, x: f64) -> f64 {
+        op.eval(x)
+    }
+
+    fn f64_relop(&mut self, op: FRelop, x: f64, y: f64) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn cvtop(&mut self, op: Cvtop, x: Slot) -> Slot {
+        op.eval(x).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            Slot::default()
+        })
+    }
+
+    fn select(&mut self, _ty: Option<Type>, cond: i32, x: Slot, y: Slot) -> Slot {
+        if cond != 0 { x } else { y }
+    }
+
+    fn unreachable(&mut self) {
+        self.set_trap(TrapKind::Unreachable);
+    }
+
+    fn ref_null(&mut self, _t: Type) -> Slot {
+        Slot::from(None)
+    }
+
+    fn ref_is_null(&mut self, x: Slot) -> i32 {
+        x.reference().is_none() as i32
+    }
+
+    fn ref_func(&mut self, func_idx: usize) -> Slot {
+        Slot::from(Some(func_idx))
+    }
+
+    fn xfer_state(&mut self, tgt: BrTgt) -> usize {
+        unwind(&mut self.stack, tgt.val_count, tgt.pop_count);
+        tgt.stp
+    }
+    fn cond_xfer_state(&mut self, cond: bool, left: BrTgt, right: BrTgt) -> usize { 
+        self.xfer_state(if cond { left } else { right })
+    }
+    fn table_xfer_state(&mut self, idx: i32, tgts: &[BrTgt]) -> usize {
+        self.xfer_state(tgts[table_index(idx, tgts.len())])
+    }
+
+    fn take_trap(&mut self) -> Option<TrapKind> {
+        self.trap.take()
     }
 }
 
 impl CPSCBDDebug for CPSEval {
-    fn stack(&self) -> &[i32] {
+    fn stack(&self) -> &[Slot] {
         &self.stack
     }
 }
@@ -0,0 +1,569 @@
+use crate::{BlockSig, CodeEntry, Opcode, Type};
+use crate::module::{ConstExpr, Data, DataMode, Elem, ElemMode, Export, ExportDesc, Func, FuncType, Global, GlobalType, Import, ImportDesc, Memory, Module, Table};
+use crate::mem::{access_width, MemArg};
+
+// Binary decoder. Custom sections are skipped over by size, every other
+// section is interpreted.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEof(usize),
+    BadMagic,
+    BadVersion(u32),
+    LebTooLong(usize),
+    LebUnusedBits(usize), // set in the last byte beyond the integer's width
+    BadSectionSize { id: u8, offset: usize },
+    BadFuncTypeForm(usize),
+    UnknownValType { byte: u8, offset: usize },
+    UnknownOpcode { byte: u8, offset: usize },
+    UnknownPrefixedOpcode { prefix: u8, sub: u32, offset: usize },
+    UnsupportedBlockType { byte: u8, offset: usize },
+    MissingEnd(usize),
+    FuncCountMismatch { funcs: usize, bodies: usize },
+    UnknownRefType { byte: u8, offset: usize },
+    BadLimits { byte: u8, offset: usize },
+    UnsupportedElemSegment { flags: u32, offset: usize },
+    UnsupportedConstExpr(usize),
+    BadMemoryIndex { byte: u8, offset: usize },
+    BadMutability { byte: u8, offset: usize },
+    BadSelectTypes(usize),
+    BadName(usize), // not UTF-8
+    BadExternKind { byte: u8, offset: usize },
+    UnsupportedDataSegment { flags: u32, offset: usize },
+    DataCountMismatch { count: usize, datas: usize },
+    TooManyLocals(usize),
+    UnknownSection { id: u8, offset: usize },
+    DuplicateSection { id: u8, offset: usize },
+    SectionOutOfOrder { id: u8, offset: usize },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use DecodeError::*;
+        match self {
+            UnexpectedEof(offset) => write!(f, "unexpected end of input at {offset}"),
+            BadMagic => write!(f, "bad magic number"),
+            BadVersion(version) => write!(f, "unsupported version {version}"),
+            LebTooLong(offset) => write!(f, "LEB128 too long at {offset}"),
+            LebUnusedBits(offset) => write!(f, "LEB128 has unused bits set at {offset}"),
+            BadSectionSize { id, offset } => write!(f, "section {id} at {offset} has the wrong size"),
+            BadFuncTypeForm(offset) => write!(f, "bad function type form at {offset}"),
+            UnknownValType { byte, offset } => write!(f, "unknown value type {byte:#04x} at {offset}"),
+            UnknownOpcode { byte, offset } => write!(f, "unknown opcode {byte:#04x} at {offset}"),
+            UnknownPrefixedOpcode { prefix, sub, offset } => write!(f, "unknown opcode {prefix:#04x} {sub} at {offset}"),
+            UnsupportedBlockType { byte, offset } => write!(f, "unsupported block type {byte:#04x} at {offset}"),
+            MissingEnd(offset) => write!(f, "missing end at {offset}"),
+            FuncCountMismatch { funcs, bodies } => write!(f, "{funcs} functions but {bodies} bodies"),
+            UnknownRefType { byte, offset } => write!(f, "unknown reference type {byte:#04x} at {offset}"),
+            BadLimits { byte, offset } => write!(f, "bad limits flag {byte:#04x} at {offset}"),
+            UnsupportedElemSegment { flags, offset } => write!(f, "unsupported element segment flags {flags} at {offset}"),
+            UnsupportedConstExpr(offset) => write!(f, "unsupported constant expression at {offset}"),
+            BadMemoryIndex { byte, offset } => write!(f, "bad memory index {byte} at {offset}"),
+            BadMutability { byte, offset } => write!(f, "bad mutability flag {byte:#04x} at {offset}"),
+            BadSelectTypes(offset) => write!(f, "typed select needs exactly one type at {offset}"),
+            BadName(offset) => write!(f, "name at {offset} isn't UTF-8"),
+            BadExternKind { byte, offset } => write!(f, "bad import kind {byte:#04x} at {offset}"),
+            UnsupportedDataSegment { flags, offset } => write!(f, "unsupported data segment flags {flags} at {offset}"),
+            DataCountMismatch { count, datas } => write!(f, "data count {count} but {datas} data segments"),
+            TooManyLocals(offset) => write!(f, "too many locals at {offset}"),
+            UnknownSection { id, offset } => write!(f, "unknown section {id} at {offset}"),
+            DuplicateSection { id, offset } => write!(f, "duplicate section {id} at {offset}"),
+            SectionOutOfOrder { id, offset } => write!(f, "section {id} at {offset} is out of order"),
+        }
+    }
+}
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: u32 = 1;
+
+// an implementation limit on a function's declared locals, which every call
+// allocates
+pub const MAX_LOCALS: u32 = 50_000;
+
+const SEC_CUSTOM: u8 = 0;
+const SEC_TYPE: u8 = 1;
+const SEC_IMPORT: u8 = 2;
+const SEC_FUNC: u8 = 3;
+const SEC_TABLE: u8 = 4;
+const SEC_MEMORY: u8 = 5;
+const SEC_GLOBAL: u8 = 6;
+const SEC_EXPORT: u8 = 7;
+const SEC_START: u8 = 8;
+const SEC_ELEM: u8 = 9;
+const SEC_CODE: u8 = 10;
+const SEC_DATA: u8 = 11;
+const SEC_DATA_COUNT: u8 = 12;
+
+// the order the other sections have to come in, at most once each. data
+// count was added later with the next free id, but goes before the code
+// that refers to it
+const SECTION_ORDER: [u8; 12] = [
+    SEC_TYPE, SEC_IMPORT, SEC_FUNC, SEC_TABLE, SEC_MEMORY, SEC_GLOBAL, SEC_EXPORT, SEC_START, SEC_ELEM,
+    SEC_DATA_COUNT, SEC_CODE, SEC_DATA,
+];
+
+// positions are always offsets into the whole file, `end` limits reads to
+// the current section or body
+pub struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub pos: usize,
+    pub end: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0, end: bytes.len() }
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.pos >= self.end
+    }
+
+    pub fn byte(&mut self) -> Result<u8, DecodeError> {
+        if self.at_end() {
+            return Err(DecodeError::UnexpectedEof(self.pos));
+        }
+        self.pos += 1;
+        Ok(self.bytes[self.pos - 1])
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        let start = self.pos;
+        self.skip(n)?;
+        Ok(&self.bytes[start..self.pos])
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<(), DecodeError> {
+        match self.pos.checked_add(n) {
+            Some(end) if end <= self.end => {
+                self.pos = end;
+                Ok(())
+            }
+            _ => Err(DecodeError::UnexpectedEof(self.end)),
+        }
+    }
+
+    // reader over the next `n` bytes, which are skipped in self
+    pub fn sub(&mut self, n: usize) -> Result<Reader<'a>, DecodeError> {
+        let start = self.pos;
+        self.skip(n)?;
+        Ok(Reader { bytes: self.bytes, pos: start, end: self.pos })
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(self.leb(32, false)? as u32)
+    }
+
+    pub fn s32(&mut self) -> Result<i32, DecodeError> {
+        Ok(self.leb(32, true)? as i32)
+    }
+
+    pub fn s64(&mut self) -> Result<i64, DecodeError> {
+        self.leb(64, true)
+    }
+
+    // floats are stored as their little endian bits, not LEB128
+    pub fn f32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn f64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    // LEB128 of at most `bits` bits, sign extended if `signed`
+    fn leb(&mut self, bits: u32, signed: bool) -> Result<i64, DecodeError> {
+        let start = self.pos;
+        let max_bytes = bits.div_ceil(7);
+        let mut result: i64 = 0;
+        let mut shift = 0;
+        for i in 0..max_bytes {
+            let b = self.byte()?;
+            // the last byte only has room for what's left of the integer,
+            // the rest must be zero or, if signed, copies of its sign
+            if i == max_bytes - 1 {
+                let used = bits - shift;
+                let unused = (b & 0x7F) >> (used - signed as u32);
+                if unused != 0 && !(signed && unused == 0x7F >> (used - 1)) {
+                    return Err(DecodeError::LebUnusedBits(start));
+                }
+            }
+            result |= ((b & 0x7F) as i64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                if signed && shift < 64 && b & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+        }
+        Err(DecodeError::LebTooLong(start))
+    }
+
+    pub fn val_type(&mut self) -> Result<Type, DecodeError> {
+        let offset = self.pos;
+        match self.byte()? {
+            0x7F => Ok(Type::I32),
+            0x7E => Ok(Type::I64),
+            0x7D => Ok(Type::F32),
+            0x7C => Ok(Type::F64),
+            0x70 => Ok(Type::FuncRef),
+            0x6F => Ok(Type::ExternRef),
+            byte => Err(DecodeError::UnknownValType { byte, offset }),
+        }
+    }
+
+    // also the heap type of ref.null, which shares the encoding
+    pub fn ref_type(&mut self) -> Result<Type, DecodeError> {
+        let offset = self.pos;
+        match self.byte()? {
+            0x70 => Ok(Type::FuncRef),
+            0x6F => Ok(Type::ExternRef),
+            byte => Err(DecodeError::UnknownRefType { byte, offset }),
+        }
+    }
+
+    // 0x40, a value type, or a non-negative s33 type index
+    pub fn block_type(&mut self) -> Result<BlockSig, DecodeError> {
+        let offset = self.pos;
+        let byte = self.byte()?;
+        if byte == 0x40 {
+            return Ok(BlockSig::Empty);
+        }
+        self.pos = offset;
+        if let Ok(t) = self.val_type() {
+            return Ok(BlockSig::Value(t));
+        }
+        self.pos = offset;
+        match self.leb(33, true)? {
+            idx if idx >= 0 => Ok(BlockSig::Index(idx as usize)),
+            _ => Err(DecodeError::UnsupportedBlockType { byte, offset }),
+        }
+    }
+
+    // min, and max if flagged
+    pub fn limits(&mut self) -> Result<(u32, Option<u32>), DecodeError> {
+        let offset = self.pos;
+        match self.byte()? {
+            0x00 => Ok((self.u32()?, None)),
+            0x01 => Ok((self.u32()?, Some(self.u32()?))),
+            byte => Err(DecodeError::BadLimits { byte, offset }),
+        }
+    }
+
+    pub fn name(&mut self) -> Result<String, DecodeError> {
+        let offset = self.pos;
+        let n = self.u32()? as usize;
+        let bytes = self.bytes(n)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::BadName(offset))
+    }
+
+    pub fn memarg(&mut self) -> Result<MemArg, DecodeError> {
+        let align = self.u32()?;
+        let offset = self.u32()?;
+        Ok(MemArg { align, offset })
+    }
+
+    fn vec<T>(&mut self, mut f: impl FnMut(&mut Self) -> Result<T, DecodeError>) -> Result<Vec<T>, DecodeError> {
+        let n = self.u32()?;
+        (0..n).map(|_| f(self)).collect()
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Module, DecodeError> {
+    let mut r = Reader::new(bytes);
+    if r.bytes(4).map_err(|_| DecodeError::BadMagic)? != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let version = u32::from_le_bytes(r.bytes(4)?.try_into().unwrap());
+    if version != VERSION {
+        return Err(DecodeError::BadVersion(version));
+    }
+
+    let mut module = Module::default();
+    let mut func_types = vec![];
+    let mut data_count = None;
+    let mut last_section = None;
+
+    while !r.at_end() {
+        let offset = r.pos;
+        let id = r.byte()?;
+        if id != SEC_CUSTOM {
+            let rank = SECTION_ORDER.iter().position(|&sec| sec == id).ok_or(DecodeError::UnknownSection { id, offset })?;
+            match last_section {
+                Some(last) if rank == last => return Err(DecodeError::DuplicateSection { id, offset }),
+                Some(last) if rank < last => return Err(DecodeError::SectionOutOfOrder { id, offset }),
+                _ => last_section = Some(rank),
+            }
+        }
+        let size = r.u32()? as usize;
+        let start = r.pos;
+        let mut sec = r.sub(size)?;
+        match id {
+            SEC_TYPE => module.types = sec.vec(read_func_type)?,
+            SEC_IMPORT => module.imports = sec.vec(read_import)?,
+            SEC_FUNC => func_types = sec.vec(|r| Ok(r.u32()? as usize))?,
+            SEC_TABLE => module.tables = sec.vec(read_table)?,
+            SEC_MEMORY => module.memories = sec.vec(read_memory)?,
+            SEC_GLOBAL => module.globals = sec.vec(read_global)?,
+            SEC_EXPORT => module.exports = sec.vec(read_export)?,
+            SEC_START => module.start = Some(sec.u32()? as usize),
+            SEC_ELEM => module.elems = sec.vec(read_elem)?,
+            SEC_DATA_COUNT => data_count = Some(sec.u32()? as usize),
+            SEC_DATA => module.datas = sec.vec(read_data)?,
+            SEC_CODE => {
+                let bodies = sec.vec(read_body)?;
+                if bodies.len() != func_types.len() {
+                    return Err(DecodeError::FuncCountMismatch { funcs: func_types.len(), bodies: bodies.len() });
+                }
+                module.funcs = func_types.iter().zip(bodies).map(|(&ty, (locals, code))| {
+                    Func { ty, locals, code }
+                }).collect();
+            }
+            _ => continue, // custom
+        }
+        if !sec.at_end() {
+            return Err(DecodeError::BadSectionSize { id, offset: start });
+        }
+    }
+
+    if module.funcs.len() != func_types.len() {
+        return Err(DecodeError::FuncCountMismatch { funcs: func_types.len(), bodies: module.funcs.len() });
+    }
+    match data_count {
+        Some(count) if count != module.datas.len() => Err(DecodeError::DataCountMismatch { count, datas: module.datas.len() }),
+        _ => Ok(module),
+    }
+}
+
+fn read_func_type(r: &mut Reader) -> Result<FuncType, DecodeError> {
+    if r.byte()? != 0x60 {
+        return Err(DecodeError::BadFuncTypeForm(r.pos - 1));
+    }
+    let params = r.vec(Reader::val_type)?;
+    let results = r.vec(Reader::val_type)?;
+    Ok(FuncType { params, results })
+}
+
+fn read_table(r: &mut Reader) -> Result<Table, DecodeError> {
+    let elem_type = r.ref_type()?;
+    let (min, max) = r.limits()?;
+    Ok(Table { elem_type, min, max })
+}
+
+fn read_memory(r: &mut Reader) -> Result<Memory, DecodeError> {
+    let (min, max) = r.limits()?;
+    Ok(Memory { min, max })
+}
+
+// the kind byte is shared with exports: func, table, memory, global
+fn read_import(r: &mut Reader) -> Result<Import, DecodeError> {
+    let module = r.name()?;
+    let name = r.name()?;
+    let offset = r.pos;
+    let desc = match r.byte()? {
+        0x00 => ImportDesc::Func(r.u32()? as usize),
+        0x01 => ImportDesc::Table(read_table(r)?),
+        0x02 => ImportDesc::Memory(read_memory(r)?),
+        0x03 => ImportDesc::Global(read_global_type(r)?),
+        byte => return Err(DecodeError::BadExternKind { byte, offset }),
+    };
+    Ok(Import { module, name, desc })
+}
+
+fn read_export(r: &mut Reader) -> Result<Export, DecodeError> {
+    let name = r.name()?;
+    let offset = r.pos;
+    let kind = r.byte()?;
+    let idx = r.u32()? as usize;
+    let desc = match kind {
+        0x00 => ExportDesc::Func(idx),
+        0x01 => ExportDesc::Table(idx),
+        0x02 => ExportDesc::Memory(idx),
+        0x03 => ExportDesc::Global(idx),
+        byte => return Err(DecodeError::BadExternKind { byte, offset }),
+    };
+    Ok(Export { name, desc })
+}
+
+// bit 0 set is passive, or declarative with bit 1. Otherwise bit 1 means an
+// explicit table index. Bit 2 means expressions rather than function
+// indices, and forms without a table index or with bit 2 say which type.
+fn read_elem(r: &mut Reader) -> Result<Elem, DecodeError> {
+    let offset = r.pos;
+    let flags = r.u32()?;
+    if flags > 7 {
+        return Err(DecodeError::UnsupportedElemSegment { flags, offset });
+    }
+    let (passive, explicit, exprs) = (flags & 1 != 0, flags & 2 != 0, flags & 4 != 0);
+    let mode = match (passive, explicit) {
+        (true, false) => ElemMode::Passive,
+        (true, true) => ElemMode::Declarative,
+        (false, false) => ElemMode::Active { table: 0, offset: read_const_expr(r)? },
+        (false, true) => {
+            let table = r.u32()? as usize;
+            ElemMode::Active { table, offset: read_const_expr(r)? }
+        }
+    };
+    // flags 0 and 4 are funcref implicitly
+    let ty = match (passive || explicit, exprs) {
+        (false, _) => Type::FuncRef,
+        (true, true) => r.ref_type()?,
+        (true, false) => {
+            let offset = r.pos;
+            match r.byte()? {
+                0x00 => Type::FuncRef, // the only elemkind
+                byte => return Err(DecodeError::UnknownRefType { byte, offset }),
+            }
+        }
+    };
+    let init = if exprs {
+        r.vec(read_const_expr)?
+    } else {
+        r.vec(|r| Ok(ConstExpr::RefFunc(r.u32()? as usize)))?
+    };
+    Ok(Elem { ty, mode, init })
+}
+
+// 0 is active in memory 0, 1 passive, and 2 active with a memory index
+fn read_data(r: &mut Reader) -> Result<Data, DecodeError> {
+    let offset = r.pos;
+    let mode = match r.u32()? {
+        0 => DataMode::Active { memory: 0, offset: read_const_expr(r)? },
+        1 => DataMode::Passive,
+        2 => {
+            let memory = r.u32()? as usize;
+            DataMode::Active { memory, offset: read_const_expr(r)? }
+        }
+        flags => return Err(DecodeError::UnsupportedDataSegment { flags, offset }),
+    };
+    let n = r.u32()? as usize;
+    let init = r.bytes(n)?.to_vec();
+    Ok(Data { mode, init })
+}
+
+fn read_global_type(r: &mut Reader) -> Result<GlobalType, DecodeError> {
+    let ty = r.val_type()?;
+    let offset = r.pos;
+    let mutable = match r.byte()? {
+        0x00 => false,
+        0x01 => true,
+        byte => return Err(DecodeError::BadMutability { byte, offset }),
+    };
+    Ok(GlobalType { ty, mutable })
+}
+
+fn read_global(r: &mut Reader) -> Result<Global, DecodeError> {
+    let ty = read_global_type(r)?;
+    let init = read_const_expr(r)?;
+    Ok(Global { ty, init })
+}
+
+// a single constant, reference or global.get, then end
+fn read_const_expr(r: &mut Reader) -> Result<ConstExpr, DecodeError> {
+    let start = r.pos;
+    let expr = match Opcode::from_byte(r.byte()?) {
+        Some(Opcode::I32Const) => ConstExpr::I32Const(r.s32()?),
+        Some(Opcode::I64Const) => ConstExpr::I64Const(r.s64()?),
+        Some(Opcode::F32Const) => ConstExpr::F32Const(r.f32()?),
+        Some(Opcode::F64Const) => ConstExpr::F64Const(r.f64()?),
+        Some(Opcode::GlobalGet) => ConstExpr::GlobalGet(r.u32()? as usize),
+        Some(Opcode::RefNull) => ConstExpr::RefNull(r.ref_type()?),
+        Some(Opcode::RefFunc) => ConstExpr::RefFunc(r.u32()? as usize),
+        _ => return Err(DecodeError::UnsupportedConstExpr(start)),
+    };
+    if r.byte()? != Opcode::End.byte() {
+        return Err(DecodeError::UnsupportedConstExpr(start));
+    }
+    Ok(expr)
+}
+
+fn read_body(r: &mut Reader) -> Result<(Vec<Type>, Vec<CodeEntry>), DecodeError> {
+    let size = r.u32()? as usize;
+    let body_start = r.pos;
+    let mut body = r.sub(size)?;
+
+    let decls = body.vec(|r| Ok((r.u32()?, r.val_type()?)))?;
+    let count = decls.iter().try_fold(0u32, |sum, &(n, _)| sum.checked_add(n).filter(|&sum| sum <= MAX_LOCALS));
+    if count.is_none() {
+        return Err(DecodeError::TooManyLocals(body_start));
+    }
+    let mut locals = vec![];
+    for (n, t) in decls {
+        locals.extend(std::iter::repeat_n(t, n as usize));
+    }
+
+    let mut code = vec![];
+    while !body.at_end() {
+        read_instr(&mut body, &mut code)?;
+    }
+    if code.last() != Some(&CodeEntry::Op(Opcode::End)) {
+        return Err(DecodeError::MissingEnd(body_start));
+    }
+    Ok((locals, code))
+}
+
+fn read_instr(r: &mut Reader, code: &mut Vec<CodeEntry>) -> Result<(), DecodeError> {
+    use CodeEntry::*;
+    use Opcode::*;
+
+    let offset = r.pos;
+    let byte = r.byte()?;
+    let op = if Opcode::is_prefix(byte) {
+        let sub = r.u32()?;
+        Opcode::from_prefixed(byte, sub).ok_or(DecodeError::UnknownPrefixedOpcode { prefix: byte, sub, offset })?
+    } else {
+        Opcode::from_byte(byte).ok_or(DecodeError::UnknownOpcode { byte, offset })?
+    };
+    code.push(Op(op));
+    match op {
+        I32Const => code.push(I32Imm(r.s32()?)),
+        I64Const => code.push(I64Imm(r.s64()?)),
+        F32Const => code.push(F32Imm(r.f32()?)),
+        F64Const => code.push(F64Imm(r.f64()?)),
+        LocalGet | LocalSet | LocalTee | GlobalGet | GlobalSet | Br | BrIf | Call => code.push(I32Imm(r.u32()? as i32)),
+        RefFunc | TableGet | TableSet | TableGrow | TableSize | TableFill => code.push(I32Imm(r.u32()? as i32)),
+        DataDrop | ElemDrop => code.push(I32Imm(r.u32()? as i32)),
+        RefNull => code.push(ValType(r.ref_type()?)),
+        // elem then table for init, dst then src for copy
+        TableInit | TableCopy => {
+            code.push(I32Imm(r.u32()? as i32));
+            code.push(I32Imm(r.u32()? as i32));
+        }
+        Block | Loop | If => code.push(BlockType(r.block_type()?)),
+        // a vec in the encoding, but it has to hold exactly one type
+        SelectTyped => {
+            let offset = r.pos;
+            match r.vec(Reader::val_type)?[..] {
+                [t] => code.push(ValType(t)),
+                _ => return Err(DecodeError::BadSelectTypes(offset)),
+            }
+        }
+        CallIndirect => {
+            code.push(I32Imm(r.u32()? as i32)); // type index
+            code.push(I32Imm(r.u32()? as i32)); // table index
+        }
+        _ if access_width(op).is_some() => code.push(MemArg(r.memarg()?)),
+        // the only memory, 0, is a reserved byte, copy's dst and src both
+        MemorySize | MemoryGrow | MemoryInit | MemoryCopy | MemoryFill => {
+            if op == MemoryInit {
+                code.push(I32Imm(r.u32()? as i32)); // data index
+            }
+            let reserved = if op == MemoryCopy { 2 } else { 1 };
+            for _ in 0..reserved {
+                let offset = r.pos;
+                match r.byte()? {
+                    0 => {}
+                    byte => return Err(DecodeError::BadMemoryIndex { byte, offset }),
+                }
+            }
+        }
+        BrTable => {
+            let mut labels = r.vec(|r| Ok(r.u32()? as usize))?;
+            labels.push(r.u32()? as usize); // the default
+            code.push(Labels(labels));
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use crate::{BlockSig, CodeEntry, CodePtr, Opcode, STEntry};
+use crate::cps::ContBlock;
+use crate::mem::access_width;
+use crate::num::Float;
+
+use std::fmt::{Debug, Write};
+
+// Renders code as indented WAT-like text, one instruction per line prefixed
+// by its ip. Branches are annotated with the sidetable entry they use and
+// where it sends them, and ContBlock starts are marked so the CPS view of
+// the same function can be checked against the sidetable view.
+pub fn disassemble(codeptr: &CodePtr, sidetable: &[STEntry], cont_blocks: &[ContBlock]) -> String {
+    let code = &codeptr.code;
+    let mut buf = String::new();
+    let mut depth = 1;
+    let mut stp = 0;
+    let mut ip = 0;
+
+    while ip < code.len() {
+        mark_cont_blocks(&mut buf, cont_blocks, ip);
+
+        let start = ip;
+        let op = match &code[ip] {
+            &CodeEntry::Op(op) => op,
+            entry => {
+                writeln!(buf, "{start:>5}: ;; stray {entry:?}").unwrap();
+                ip += 1;
+                continue;
+            }
+        };
+        ip += 1;
+
+        // immediates are the non-Op entries following the op
+        let mut imms = vec![];
+        // an if's entry is its false edge, an else's the skip over the else
+        // arm, a return's the branch to the function's end, and a br_table
+        // has one per target
+        let mut entries = match op {
+            Opcode::Br | Opcode::BrIf | Opcode::If | Opcode::Else | Opcode::Return => 1,
+            _ => 0,
+        };
+        while let Some(entry) = code.get(ip).filter(|e| !matches!(e, CodeEntry::Op(_))) {
+            match entry {
+                CodeEntry::I32Imm(i) => imms.push(i.to_string()),
+                CodeEntry::I64Imm(i) => imms.push(i.to_string()),
+                CodeEntry::F32Imm(bits) => imms.push(float_text(f32::from_bits(*bits), *bits as u64 & 0x7f_ffff, 1 << 22)),
+                CodeEntry::F64Imm(bits) => imms.push(float_text(f64::from_bits(*bits), bits & 0xf_ffff_ffff_ffff, 1 << 51)),
+                CodeEntry::BlockType(BlockSig::Empty) => {}
+                CodeEntry::BlockType(BlockSig::Value(t)) => imms.push(format!("(result {})", t.name())),
+                CodeEntry::BlockType(BlockSig::Index(idx)) => imms.push(format!("(type {idx})")),
+                CodeEntry::ValType(t) if op == Opcode::RefNull => imms.push(t.heap_type().to_string()),
+                CodeEntry::ValType(t) => imms.push(format!("(result {})", t.name())),
+                CodeEntry::Labels(labels) => {
+                    imms.extend(labels.iter().map(usize::to_string));
+                    entries = labels.len();
+                }
+                // like the text format, defaults are left out
+                CodeEntry::MemArg(memarg) => {
+                    if memarg.offset != 0 {
+                        imms.push(format!("offset={}", memarg.offset));
+                    }
+                    if Some(memarg.align) != access_width(op).map(usize::trailing_zeros) {
+                        // the binary format allows exponents too large to shift
+                        imms.push(match 1u64.checked_shl(memarg.align) {
+                            Some(align) => format!("align={align}"),
+                            None => format!("align=2^{}", memarg.align),
+                        });
+                    }
+                }
+                CodeEntry::Op(_) => unreachable!(),
+            }
+            ip += 1;
+        }
+
+        if op == Opcode::End {
+            depth = usize::max(depth, 1) - 1;
+        }
+        // else lines up with its if
+        let indent = "  ".repeat(if op == Opcode::Else { usize::max(depth, 1) - 1 } else { depth });
+        let mut line = format!("{start:>5}: {indent}{}", op.name());
+        for imm in &imms {
+            write!(line, " {imm}").unwrap();
+        }
+
+        for k in 0..entries {
+            if k > 0 {
+                writeln!(buf, "{line}").unwrap();
+                line.clear();
+            }
+            stp += 1;
+            write!(line, "{:w$};; ", "", w = 32usize.saturating_sub(line.len())).unwrap();
+            match sidetable.get(stp) {
+                Some(ste) => {
+                    let tgt_ip = ip as isize + ste.ip_delta;
+                    let tgt_stp = stp as isize + ste.stp_delta;
+                    write!(line, "st[{stp}] ip_delta={:+} stp_delta={:+} -> ip {tgt_ip}, stp {tgt_stp}",
+                        ste.ip_delta, ste.stp_delta).unwrap();
+                    if ste.val_count + ste.pop_count > 0 {
+                        write!(line, ", keep {} pop {}", ste.val_count, ste.pop_count).unwrap();
+                    }
+                }
+                None => write!(line, "st[{stp}] missing").unwrap(),
+            }
+        }
+        writeln!(buf, "{line}").unwrap();
+
+        if matches!(op, Opcode::Block | Opcode::Loop | Opcode::If) {
+            depth += 1;
+        }
+    }
+    mark_cont_blocks(&mut buf, cont_blocks, code.len());
+
+    buf
+}
+
+// as the text format writes it. Debug already gives inf and -inf, but NaNs
+// need their sign, and their payload unless it's the canonical one.
+fn float_text<F: Float + Debug>(x: F, payload: u64, canonical: u64) -> String {
+    if !x.is_nan() {
+        return format!("{x:?}");
+    }
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    if payload == canonical {
+        format!("{sign}nan")
+    } else {
+        format!("{sign}nan:{payload:#x}")
+    }
+}
+
+fn mark_cont_blocks(buf: &mut String, cont_blocks: &[ContBlock], ip: usize) {
+    for (i, cb) in cont_blocks.iter().enumerate().filter(|(_, cb)| cb.ip == ip) {
+        writeln!(buf, "       ;; ---- cont_block {i} (br_tgt {}) ----", cb.br_tgt.stp).unwrap();
+    }
+}
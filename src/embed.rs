@@ -0,0 +1,213 @@
+use crate::{Trap, Type};
+use crate::instance::{Evaluator, Extern, Instance, Store};
+use crate::module::FuncType;
+use crate::num::Slot;
+
+use std::marker::PhantomData;
+
+// Calling exported functions from Rust. Slots don't know their types, so
+// args and results are checked against the function's type up front, once
+// per TypedFunc or on each dynamic call, and converted at the boundary.
+// Either way the call runs on the evaluator the caller picks.
+
+// a value that knows its type, for dynamic calls
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    FuncRef(Option<usize>), // a store function address, None for null
+    ExternRef(Option<usize>),
+}
+
+impl Value {
+    pub fn ty(self) -> Type {
+        match self {
+            Value::I32(_) => Type::I32,
+            Value::I64(_) => Type::I64,
+            Value::F32(_) => Type::F32,
+            Value::F64(_) => Type::F64,
+            Value::FuncRef(_) => Type::FuncRef,
+            Value::ExternRef(_) => Type::ExternRef,
+        }
+    }
+
+    // a slot read as `ty`, which a function's type says
+    pub fn from_slot(slot: Slot, ty: Type) -> Value {
+        match ty {
+            Type::I32 => Value::I32(slot.i32()),
+            Type::I64 => Value::I64(slot.i64()),
+            Type::F32 => Value::F32(slot.f32()),
+            Type::F64 => Value::F64(slot.f64()),
+            Type::FuncRef => Value::FuncRef(slot.reference()),
+            Type::ExternRef => Value::ExternRef(slot.reference()),
+            Type::Unknown => unreachable!("no function has an unknown type"),
+        }
+    }
+}
+
+impl From<Value> for Slot {
+    fn from(val: Value) -> Self {
+        match val {
+            Value::I32(x) => x.into(),
+            Value::I64(x) => x.into(),
+            Value::F32(x) => x.into(),
+            Value::F64(x) => x.into(),
+            Value::FuncRef(r) | Value::ExternRef(r) => r.into(),
+        }
+    }
+}
+
+// a Rust type standing for a wasm value type
+pub trait WasmTy: Into<Slot> + From<Slot> {
+    const TYPE: Type;
+}
+
+impl WasmTy for i32 {
+    const TYPE: Type = Type::I32;
+}
+
+impl WasmTy for i64 {
+    const TYPE: Type = Type::I64;
+}
+
+impl WasmTy for f32 {
+    const TYPE: Type = Type::F32;
+}
+
+impl WasmTy for f64 {
+    const TYPE: Type = Type::F64;
+}
+
+// params or results: (), one value, or a tuple of them
+pub trait WasmTypes: Sized {
+    fn types() -> Vec<Type>;
+    fn into_slots(self) -> Vec<Slot>;
+    fn from_slots(slots: &[Slot]) -> Self;
+}
+
+impl WasmTypes for () {
+    fn types() -> Vec<Type> {
+        vec![]
+    }
+
+    fn into_slots(self) -> Vec<Slot> {
+        vec![]
+    }
+
+    fn from_slots(_: &[Slot]) -> Self {}
+}
+
+impl<T: WasmTy> WasmTypes for T {
+    fn types() -> Vec<Type> {
+        vec![T::TYPE]
+    }
+
+    fn into_slots(self) -> Vec<Slot> {
+        vec![self.into()]
+    }
+
+    fn from_slots(slots: &[Slot]) -> Self {
+        T::from(slots[0])
+    }
+}
+
+macro_rules! tuple_types {
+    ($($t:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($t: WasmTy),*> WasmTypes for ($($t,)*) {
+            fn types() -> Vec<Type> {
+                vec![$($t::TYPE),*]
+            }
+
+            fn into_slots(self) -> Vec<Slot> {
+                let ($($t,)*) = self;
+                vec![$($t.into()),*]
+            }
+
+            fn from_slots(slots: &[Slot]) -> Self {
+                let mut slots = slots.iter().copied();
+                ($($t::from(slots.next().unwrap()),)*)
+            }
+        }
+    };
+}
+
+tuple_types!(A);
+tuple_types!(A, B);
+tuple_types!(A, B, C);
+tuple_types!(A, B, C, D);
+tuple_types!(A, B, C, D, E);
+tuple_types!(A, B, C, D, E, F);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallError {
+    UnknownExport(String),
+    NotAFunc(String),
+    ParamTypes { expected: Vec<Type>, found: Vec<Type> },
+    ResultTypes { expected: Vec<Type>, found: Vec<Type> },
+    Trap(Trap),
+}
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CallError::UnknownExport(name) => write!(f, "unknown export {name}"),
+            CallError::NotAFunc(name) => write!(f, "export {name} is not a function"),
+            CallError::ParamTypes { expected, found } => write!(f, "param types: expected {expected:?}, found {found:?}"),
+            CallError::ResultTypes { expected, found } => write!(f, "result types: expected {expected:?}, found {found:?}"),
+            CallError::Trap(trap) => write!(f, "{trap}"),
+        }
+    }
+}
+
+// an exported function whose type has been checked against P and R
+#[derive(Debug, Copy, Clone)]
+pub struct TypedFunc<P, R> {
+    pub func_addr: usize,
+    types: PhantomData<fn(P) -> R>,
+}
+
+impl<P: WasmTypes, R: WasmTypes> TypedFunc<P, R> {
+    pub fn call<E: Evaluator>(&self, store: &mut Store, params: P) -> Result<R, Trap> {
+        let results = store.invoke::<E>(self.func_addr, &params.into_slots())?;
+        Ok(R::from_slots(&results))
+    }
+}
+
+impl Instance {
+    fn exported_func(&self, name: &str) -> Result<(usize, &FuncType), CallError> {
+        match self.export(name) {
+            Some(Extern::Func(addr)) => {
+                let idx = self.funcs.iter().position(|&a| a == addr).unwrap();
+                Ok((addr, &self.func_types[idx]))
+            }
+            Some(_) => Err(CallError::NotAFunc(name.to_string())),
+            None => Err(CallError::UnknownExport(name.to_string())),
+        }
+    }
+
+    pub fn get_typed_func<P: WasmTypes, R: WasmTypes>(&self, name: &str) -> Result<TypedFunc<P, R>, CallError> {
+        let (func_addr, ty) = self.exported_func(name)?;
+        if P::types() != ty.params {
+            return Err(CallError::ParamTypes { expected: ty.params.clone(), found: P::types() });
+        }
+        if R::types() != ty.results {
+            return Err(CallError::ResultTypes { expected: ty.results.clone(), found: R::types() });
+        }
+        Ok(TypedFunc { func_addr, types: PhantomData })
+    }
+
+    // a dynamic call, the args checked against the function's type
+    pub fn call<E: Evaluator>(&self, store: &mut Store, name: &str, args: &[Value]) -> Result<Vec<Value>, CallError> {
+        let (func_addr, ty) = self.exported_func(name)?;
+        let found: Vec<Type> = args.iter().map(|val| val.ty()).collect();
+        if found != ty.params {
+            return Err(CallError::ParamTypes { expected: ty.params.clone(), found });
+        }
+        let args: Vec<Slot> = args.iter().map(|&val| val.into()).collect();
+        let results = store.invoke::<E>(func_addr, &args).map_err(CallError::Trap)?;
+        Ok(results.into_iter().zip(&ty.results).map(|(slot, &t)| Value::from_slot(slot, t)).collect())
+    }
+}
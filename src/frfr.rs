@@ -1,4 +1,8 @@
-use crate::{CodePtr, CodeEntry, Balloon, STEntry, Idk};
+use crate::{CodePtr, CodeEntry, Balloon, STEntry, Idk, TrapKind, Type, BlockSig, table_index, Frame, FuncEntry, MAX_CALL_DEPTH, resolve_indirect};
+use crate::num::{I32Binop, I32Unop, I32Relop, I64Binop, I64Unop, I64Relop, FBinop, FUnop, FRelop, Cvtop, Slot};
+use crate::mem::{I32LoadOp, I32StoreOp, LinearMemory, MemArg};
+use crate::table::{self, RefTable};
+use crate::instance::{call_host, HostFunc};
 use std::marker::PhantomData;
 use crate::Run;
 use std::collections::VecDeque;
@@ -39,6 +43,9 @@ impl Merge for usize {
 //    on the interpreter to make a compiler
 pub trait CBD_FR {
     type I32Val;
+    type I64Val;
+    type F32Val;
+    type F64Val;
     type StackVal: Clone + Into<Self::LocalVal>;
     type LocalVal: Clone + Into<Self::StackVal>;
     type CondVal: Balloon;
@@ -50,25 +57,86 @@ pub trait CBD_FR {
     fn pushi_imm(&mut self, x: i32);
     fn pushi(&mut self, x: Self::I32Val);
 
+    fn popl(&mut self) -> Self::I64Val;
+    fn pushl_imm(&mut self, x: i64);
+    fn pushl(&mut self, x: Self::I64Val);
+
+    fn popf(&mut self) -> Self::F32Val;
+    fn pushf_imm(&mut self, x: f32);
+    fn pushf(&mut self, x: Self::F32Val);
+
+    fn popd(&mut self) -> Self::F64Val;
+    fn pushd_imm(&mut self, x: f64);
+    fn pushd(&mut self, x: Self::F64Val);
+
     fn push(&mut self, x: Self::StackVal);
     fn pop(&mut self) -> Self::StackVal;
 
     fn set_local(&mut self, idx: i32, val: Self::LocalVal);
     fn get_local(&mut self, idx: i32) -> Self::LocalVal;
 
-    fn start_block(&mut self, ty_index: usize);
-    fn start_loop(&mut self, ty_index: usize);
+    fn set_global(&mut self, idx: i32, val: Self::StackVal);
+    fn get_global(&mut self, idx: i32) -> Self::StackVal;
+
+    fn start_block(&mut self, ty: BlockSig);
+    fn start_loop(&mut self, ty: BlockSig);
+    fn start_if(&mut self, ty: BlockSig);
+    fn start_else(&mut self);
     fn end(&mut self);
 
     fn i32_add(&mut self, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
     fn i32_eqz(&mut self, x: Self::I32Val) -> Self::CondVal;
+    fn i32_binop(&mut self, op: I32Binop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
+    fn i32_unop(&mut self, op: I32Unop, x: Self::I32Val) -> Self::I32Val;
+    fn i32_relop(&mut self, op: I32Relop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
+    fn i64_add(&mut self, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val;
+    fn i64_binop(&mut self, op: I64Binop, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val;
+    fn i64_unop(&mut self, op: I64Unop, x: Self::I64Val) -> Self::I64Val;
+    fn i64_relop(&mut self, op: I64Relop, x: Self::I64Val, y: Self::I64Val) -> Self::I32Val;
+    fn i64_eqz(&mut self, x: Self::I64Val) -> Self::I32Val;
+    fn f32_binop(&mut self, op: FBinop, x: Self::F32Val, y: Self::F32Val) -> Self::F32Val;
+    fn f32_unop(&mut self, op: FUnop, x: Self::F32Val) -> Self::F32Val;
+    fn f32_relop(&mut self, op: FRelop, x: Self::F32Val, y: Self::F32Val) -> Self::I32Val;
+    fn f64_binop(&mut self, op: FBinop, x: Self::F64Val, y: Self::F64Val) -> Self::F64Val;
+    fn f64_unop(&mut self, op: FUnop, x: Self::F64Val) -> Self::F64Val;
+    fn f64_relop(&mut self, op: FRelop, x: Self::F64Val, y: Self::F64Val) -> Self::I32Val;
+    fn cvtop(&mut self, op: Cvtop, x: Self::StackVal) -> Self::StackVal;
+    fn select(&mut self, ty: Option<Type>, cond: Self::I32Val, x: Self::StackVal, y: Self::StackVal) -> Self::StackVal;
+    fn unreachable(&mut self);
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, addr: Self::I32Val) -> Self::I32Val;
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, addr: Self::I32Val, val: Self::I32Val);
+    fn memory_size(&mut self) -> Self::I32Val;
+    fn memory_grow(&mut self, delta: Self::I32Val) -> Self::I32Val;
+    fn memory_init(&mut self, data_idx: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn data_drop(&mut self, data_idx: usize);
+    fn memory_copy(&mut self, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn memory_fill(&mut self, dst: Self::I32Val, val: Self::I32Val, len: Self::I32Val);
+    fn ref_null(&mut self, t: Type) -> Self::StackVal;
+    fn ref_is_null(&mut self, x: Self::StackVal) -> Self::I32Val;
+    fn ref_func(&mut self, func_idx: usize) -> Self::StackVal;
+    fn table_get(&mut self, table_idx: usize, idx: Self::I32Val) -> Self::StackVal;
+    fn table_set(&mut self, table_idx: usize, idx: Self::I32Val, val: Self::StackVal);
+    fn table_size(&mut self, table_idx: usize) -> Self::I32Val;
+    fn table_grow(&mut self, table_idx: usize, init: Self::StackVal, delta: Self::I32Val) -> Self::I32Val;
+    fn table_fill(&mut self, table_idx: usize, dst: Self::I32Val, val: Self::StackVal, len: Self::I32Val);
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn elem_drop(&mut self, elem_idx: usize);
 
     // gotta make all control xfer return some mergeable state
     fn branch(&mut self, label_idx: usize) -> Self::MergeState;
+    fn branch_table(&mut self, labels: &[usize], idx: Self::I32Val) -> Self::MergeState;
+    fn call(&mut self, func_idx: usize);
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, idx: Self::I32Val);
     fn fallthru(&mut self) -> Self::MergeState;
 
     fn merge(&mut self, other: Self::MergeState);
 
+    // only evaluators can trap, see crate::Trap
+    fn take_trap(&mut self) -> Option<TrapKind> {
+        None
+    }
+
     fn cbd_i32_const(&mut self) {
         let x = self.codeptr_mut().read_imm_i32();
         self.pushi_imm(x);
@@ -81,6 +149,269 @@ pub trait CBD_FR {
         self.pushi(z);
     }
 
+    fn cbd_i32_binop(&mut self, op: I32Binop) {
+        let y = self.popi();
+        let x = self.popi();
+        let z = self.i32_binop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i32_unop(&mut self, op: I32Unop) {
+        let x = self.popi();
+        let z = self.i32_unop(op, x);
+        self.pushi(z);
+    }
+
+    fn cbd_i32_relop(&mut self, op: I32Relop) {
+        let y = self.popi();
+        let x = self.popi();
+        let z = self.i32_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i64_const(&mut self) {
+        let x = self.codeptr_mut().read_imm_i64();
+        self.pushl_imm(x);
+    }
+
+    fn cbd_i64_add(&mut self) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_add(x, y);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_binop(&mut self, op: I64Binop) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_binop(op, x, y);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_unop(&mut self, op: I64Unop) {
+        let x = self.popl();
+        let z = self.i64_unop(op, x);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_relop(&mut self, op: I64Relop) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i64_eqz(&mut self) {
+        let x = self.popl();
+        let z = self.i64_eqz(x);
+        self.pushi(z);
+    }
+
+    fn cbd_f32_const(&mut self) {
+        let x = self.codeptr_mut().read_imm_f32();
+        self.pushf_imm(x);
+    }
+
+    fn cbd_f32_binop(&mut self, op: FBinop) {
+        let y = self.popf();
+        let x = self.popf();
+        let z = self.f32_binop(op, x, y);
+        self.pushf(z);
+    }
+
+    fn cbd_f32_unop(&mut self, op: FUnop) {
+        let x = self.popf();
+        let z = self.f32_unop(op, x);
+        self.pushf(z);
+    }
+
+    fn cbd_f32_relop(&mut self, op: FRelop) {
+        let y = self.popf();
+        let x = self.popf();
+        let z = self.f32_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_f64_const(&mut self) {
+        let x = self.codeptr_mut().read_imm_f64();
+        self.pushd_imm(x);
+    }
+
+    fn cbd_f64_binop(&mut self, op: FBinop) {
+        let y = self.popd();
+        let x = self.popd();
+        let z = self.f64_binop(op, x, y);
+        self.pushd(z);
+    }
+
+    fn cbd_f64_unop(&mut self, op: FUnop) {
+        let x = self.popd();
+        let z = self.f64_unop(op, x);
+        self.pushd(z);
+    }
+
+    fn cbd_f64_relop(&mut self, op: FRelop) {
+        let y = self.popd();
+        let x = self.popd();
+        let z = self.f64_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_cvtop(&mut self, op: Cvtop) {
+        let x = self.pop();
+        let z = self.cvtop(op, x);
+        self.push(z);
+    }
+
+    fn cbd_i32_load(&mut self, op: I32LoadOp) {
+        let memarg = self.codeptr_mut().read_memarg();
+        let addr = self.popi();
+        let x = self.i32_load(op, memarg, addr);
+        self.pushi(x);
+    }
+
+    fn cbd_i32_store(&mut self, op: I32StoreOp) {
+        let memarg = self.codeptr_mut().read_memarg();
+        let val = self.popi();
+        let addr = self.popi();
+        self.i32_store(op, memarg, addr, val);
+    }
+
+    fn cbd_memory_size(&mut self) {
+        let x = self.memory_size();
+        self.pushi(x);
+    }
+
+    fn cbd_memory_grow(&mut self) {
+        let delta = self.popi();
+        let x = self.memory_grow(delta);
+        self.pushi(x);
+    }
+
+    fn cbd_memory_init(&mut self) {
+        let data_idx = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.memory_init(data_idx as usize, dst, src, len);
+    }
+
+    fn cbd_data_drop(&mut self) {
+        let data_idx = self.codeptr_mut().read_imm_i32();
+        self.data_drop(data_idx as usize);
+    }
+
+    fn cbd_memory_copy(&mut self) {
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.memory_copy(dst, src, len);
+    }
+
+    fn cbd_memory_fill(&mut self) {
+        let len = self.popi();
+        let val = self.popi();
+        let dst = self.popi();
+        self.memory_fill(dst, val, len);
+    }
+
+    // references are popped and pushed untyped, like cvtop operands
+    fn cbd_ref_null(&mut self) {
+        let t = self.codeptr_mut().read_val_type();
+        let x = self.ref_null(t);
+        self.push(x);
+    }
+
+    fn cbd_ref_is_null(&mut self) {
+        let x = self.pop();
+        let z = self.ref_is_null(x);
+        self.pushi(z);
+    }
+
+    fn cbd_ref_func(&mut self) {
+        let func_idx = self.codeptr_mut().read_imm_i32();
+        let x = self.ref_func(func_idx as usize);
+        self.push(x);
+    }
+
+    fn cbd_table_get(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let idx = self.popi();
+        let x = self.table_get(table_idx as usize, idx);
+        self.push(x);
+    }
+
+    fn cbd_table_set(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let val = self.pop();
+        let idx = self.popi();
+        self.table_set(table_idx as usize, idx, val);
+    }
+
+    fn cbd_table_size(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let x = self.table_size(table_idx as usize);
+        self.pushi(x);
+    }
+
+    fn cbd_table_grow(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let delta = self.popi();
+        let init = self.pop();
+        let x = self.table_grow(table_idx as usize, init, delta);
+        self.pushi(x);
+    }
+
+    fn cbd_table_fill(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let val = self.pop();
+        let dst = self.popi();
+        self.table_fill(table_idx as usize, dst, val, len);
+    }
+
+    fn cbd_table_copy(&mut self) {
+        let dst_table = self.codeptr_mut().read_imm_i32();
+        let src_table = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.table_copy(dst_table as usize, src_table as usize, dst, src, len);
+    }
+
+    fn cbd_table_init(&mut self) {
+        let elem_idx = self.codeptr_mut().read_imm_i32();
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.table_init(table_idx as usize, elem_idx as usize, dst, src, len);
+    }
+
+    fn cbd_elem_drop(&mut self) {
+        let elem_idx = self.codeptr_mut().read_imm_i32();
+        self.elem_drop(elem_idx as usize);
+    }
+
+    fn cbd_nop(&mut self) { }
+
+    fn cbd_unreachable(&mut self) {
+        self.unreachable();
+    }
+
+    fn cbd_drop(&mut self) {
+        self.pop();
+    }
+
+    fn cbd_select(&mut self, typed: bool) {
+        let ty = typed.then(|| self.codeptr_mut().read_val_type());
+        let cond = self.popi();
+        let y = self.pop();
+        let x = self.pop();
+        let z = self.select(ty, cond, x, y);
+        self.push(z);
+    }
+
     fn cbd_local_set(&mut self) {
         let idx = self.codeptr_mut().read_imm_i32();
         let val = self.pop();
@@ -100,6 +431,18 @@ pub trait CBD_FR {
         self.set_local(idx, val.into());
     }
 
+    fn cbd_global_get(&mut self) {
+        let idx = self.codeptr_mut().read_imm_i32();
+        let val = self.get_global(idx);
+        self.push(val);
+    }
+
+    fn cbd_global_set(&mut self) {
+        let idx = self.codeptr_mut().read_imm_i32();
+        let val = self.pop();
+        self.set_global(idx, val);
+    }
+
     fn cbd_block(&mut self) {
         let ty = self.codeptr_mut().read_block_type();
         self.start_block(ty);
@@ -110,6 +453,27 @@ pub trait CBD_FR {
         self.start_loop(ty);
     }
 
+    fn cbd_if(&mut self) {
+        let ty = self.codeptr_mut().read_block_type();
+        let condv = self.popi();
+        let condb = self.i32_eqz(condv);
+        self.start_if(ty);
+        // the false edge is the if's own sidetable entry, not a label
+        mif! {self:
+            if (condb) then {
+                self.branch(0)
+            }, else {
+                self.fallthru()
+            }
+        }
+    }
+
+    // only reached by falling out of the then arm
+    fn cbd_else(&mut self) {
+        self.branch(0);
+        self.start_else();
+    }
+
     fn cbd_br(&mut self) {
         let label_idx = self.codeptr_mut().read_imm_i32();
         self.branch(label_idx as usize);
@@ -128,6 +492,29 @@ pub trait CBD_FR {
             }
         }
     }
+
+    // a branch to the function's label, found in the sidetable like any other
+    fn cbd_return(&mut self) {
+        self.branch(0);
+    }
+
+    fn cbd_call(&mut self) {
+        let func_idx = self.codeptr_mut().read_imm_i32();
+        self.call(func_idx as usize);
+    }
+
+    fn cbd_call_indirect(&mut self) {
+        let type_idx = self.codeptr_mut().read_imm_i32();
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let idx = self.popi();
+        self.call_indirect(type_idx as usize, table_idx as usize, idx);
+    }
+
+    fn cbd_br_table(&mut self) {
+        let labels = self.codeptr_mut().read_labels();
+        let idx = self.popi();
+        self.branch_table(&labels, idx);
+    }
     
     fn cbd_end(&mut self) {
         self.end();
@@ -135,17 +522,41 @@ pub trait CBD_FR {
 }
 
 pub struct EvalFR {
-    pub stack: Vec<i32>,
-    pub locals: Vec<i32>,
+    pub stack: Vec<Slot>,
+    pub locals: Vec<Slot>,
     pub codeptr: CodePtr,
     pub sidetable: Vec<STEntry>,
     pub stp: usize,
+    pub trap: Option<TrapKind>,
+    pub frames: Vec<Frame>,
+    pub funcs: Vec<FuncEntry>, // the module's function index space, see module::Linked
+    pub hosts: Vec<HostFunc>, // see FuncEntry::host
+    pub tables: Vec<RefTable>,
+    pub elems: Vec<Vec<Option<usize>>>, // each segment's references, empty once dropped
+    pub sigs: Vec<usize>, // canonical id of each type index
+    pub memory: LinearMemory,
+    pub datas: Vec<Vec<u8>>, // each segment's bytes, empty once dropped
+    pub globals: Vec<Slot>,
+}
+
+impl EvalFR {
+    fn set_trap(&mut self, kind: TrapKind) {
+        self.trap.get_or_insert(kind);
+    }
+
+    // the params and locals of every active call are laid end to end
+    fn local_slot(&self, idx: i32) -> usize {
+        self.frames.last().map_or(0, |f| f.locals_base) + idx as u32 as usize
+    }
 }
 
 impl CBD_FR for EvalFR {
     type I32Val = i32;
-    type StackVal = i32;
-    type LocalVal = i32;
+    type I64Val = i64;
+    type F32Val = f32;
+    type F64Val = f64;
+    type StackVal = Slot;
+    type LocalVal = Slot;
     type CondVal = bool;
     type MergeState = ();
 
@@ -154,49 +565,340 @@ impl CBD_FR for EvalFR {
     }
 
     fn popi(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+        self.pop().i32()
     }
 
     fn pushi_imm(&mut self, x: i32) {
         self.pushi(x)
     }
     fn pushi(&mut self, x: i32) {
-        self.stack.push(x)
+        self.stack.push(x.into())
+    }
+
+    fn popl(&mut self) -> i64 {
+        self.pop().i64()
+    }
+
+    fn pushl_imm(&mut self, x: i64) {
+        self.pushl(x)
+    }
+    fn pushl(&mut self, x: i64) {
+        self.stack.push(x.into())
+    }
+
+    fn popf(&mut self) -> f32 {
+        self.pop().f32()
+    }
+
+    fn pushf_imm(&mut self, x: f32) {
+        self.pushf(x)
+    }
+    fn pushf(&mut self, x: f32) {
+        self.stack.push(x.into())
+    }
+
+    fn popd(&mut self) -> f64 {
+        self.pop().f64()
+    }
+
+    fn pushd_imm(&mut self, x: f64) {
+        self.pushd(x)
+    }
+    fn pushd(&mut self, x: f64) {
+        self.stack.push(x.into())
     }
 
-    fn push(&mut self, x: i32) {
+    fn push(&mut self, x: Slot) {
         self.stack.push(x)
     }
-    fn pop(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+    fn pop(&mut self) -> Slot {
+        self.stack.pop().unwrap_or_else(|| {
+            self.set_trap(TrapKind::StackUnderflow);
+            Slot::default()
+        })
     }
 
-    fn set_local(&mut self, idx: i32, val: i32) {
-        self.locals[idx as usize] = val;
+    fn set_local(&mut self, idx: i32, val: Slot) {
+        let slot = self.local_slot(idx);
+        match self.locals.get_mut(slot) {
+            Some(local) => *local = val,
+            None => self.set_trap(TrapKind::LocalOutOfRange),
+        }
     }
 
-    fn get_local(&mut self, idx: i32) -> i32 {
-        self.locals[idx as usize]
+    fn get_local(&mut self, idx: i32) -> Slot {
+        match self.locals.get(self.local_slot(idx)) {
+            Some(&local) => local,
+            None => {
+                self.set_trap(TrapKind::LocalOutOfRange);
+                Slot::default()
+            }
+        }
     }
 
-    fn start_block(&mut self, _ty_index: usize) { }
-    fn start_loop(&mut self, _ty_index: usize) { }
-    fn end(&mut self) { }
+    fn set_global(&mut self, idx: i32, val: Slot) {
+        match self.globals.get_mut(idx as u32 as usize) {
+            Some(global) => *global = val,
+            None => self.set_trap(TrapKind::GlobalOutOfRange),
+        }
+    }
+
+    fn get_global(&mut self, idx: i32) -> Slot {
+        match self.globals.get(idx as u32 as usize) {
+            Some(&global) => global,
+            None => {
+                self.set_trap(TrapKind::GlobalOutOfRange);
+                Slot::default()
+            }
+        }
+    }
+
+    fn start_block(&mut self, _ty: BlockSig) { }
+    fn start_loop(&mut self, _ty: BlockSig) { }
+    fn start_if(&mut self, _ty: BlockSig) { }
+    fn start_else(&mut self) { }
+    // only a function's own End returns, its results are already on top
+    fn end(&mut self) {
+        let Some(&frame) = self.frames.last() else { return };
+        if self.codeptr.ip == self.funcs[frame.func].end_ip {
+            self.frames.pop();
+            self.locals.truncate(frame.locals_base);
+            self.codeptr.ip = frame.ret_ip;
+            self.stp = frame.ret_stp;
+        }
+    }
+
+    fn call(&mut self, func_idx: usize) {
+        let Some(&f) = self.funcs.get(func_idx) else {
+            self.set_trap(TrapKind::FuncOutOfRange);
+            return;
+        };
+        if self.frames.len() == MAX_CALL_DEPTH {
+            self.set_trap(TrapKind::CallStackExhausted);
+            return;
+        }
+        // a host function returns before the next op, no frame needed
+        if let Some(host) = f.host {
+            if let Err(kind) = call_host(&self.hosts[host], &mut self.memory, &mut self.stack) {
+                self.set_trap(kind);
+            }
+            return;
+        }
+        let Some(args) = self.stack.len().checked_sub(f.params) else {
+            self.set_trap(TrapKind::StackUnderflow);
+            return;
+        };
+        // the args become the callee's first locals
+        let locals_base = self.locals.len();
+        self.locals.extend(self.stack.drain(args..));
+        self.locals.resize(locals_base + f.params + f.locals, Slot::default());
+        self.frames.push(Frame { func: func_idx, locals_base, ret_ip: self.codeptr.ip, ret_stp: self.stp });
+        self.codeptr.ip = f.ip;
+        self.stp = f.stp;
+    }
+
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, idx: i32) {
+        match resolve_indirect(&self.tables[table_idx].elems, &self.funcs, self.sigs[type_idx], idx) {
+            Ok(func_idx) => self.call(func_idx),
+            Err(kind) => self.set_trap(kind),
+        }
+    }
+
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, addr: i32) -> i32 {
+        self.memory.load(op, memarg, addr).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
+
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, addr: i32, val: i32) {
+        if let Err(kind) = self.memory.store(op, memarg, addr, val) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn memory_size(&mut self) -> i32 {
+        self.memory.pages() as i32
+    }
+
+    fn memory_grow(&mut self, delta: i32) -> i32 {
+        self.memory.grow(delta as u32)
+    }
+
+    fn memory_init(&mut self, data_idx: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.memory.init(dst, &self.datas[data_idx], src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn data_drop(&mut self, data_idx: usize) {
+        self.datas[data_idx] = vec![];
+    }
+
+    fn memory_copy(&mut self, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.memory.copy(dst, src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn memory_fill(&mut self, dst: i32, val: i32, len: i32) {
+        if let Err(kind) = self.memory.fill(dst, val, len) {
+            self.set_trap(kind);
+        }
+    }
 
     fn i32_add(&mut self, x: i32, y: i32) -> i32 {
-        x + y
+        x.wrapping_add(y)
     }
 
     fn i32_eqz(&mut self, x: i32) -> bool {
         x == 0
     }
 
+    fn i32_binop(&mut self, op: I32Binop, x: i32, y: i32) -> i32 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
+
+    fn i32_unop(&mut self, op: I32Unop, x: i32) -> i32 {
+        op.eval(x)
+    }
+
+    fn i32_relop(&mut self, op: I32Relop, x: i32, y: i32) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn i64_add(&mut self, x: i64, y: i64) -> i64 {
+        x.wrapping_add(y)
+    }
+
+    fn i64_binop(&mut self, op: I64Binop, x: i64, y: i64) -> i64 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
+
+    fn i64_unop(&mut self, op: I64Unop, x: i64) -> i64 {
+        op.eval(x)
+    }
+
+    fn i64_relop(&mut self, op: I64Relop, x: i64, y: i64) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn i64_eqz(&mut self, x: i64) -> i32 {
+        (x == 0) as i32
+    }
+
+    fn f32_binop(&mut self, op: FBinop, x: f32, y: f32) -> f32 {
+        op.eval(x, y)
+    }
+
+    fn f32_unop(&mut self, op: FUnop, x: f32) -> f32 {
+        op.eval(x)
+    }
+
+    fn f32_relop(&mut self, op: FRelop, x: f32, y: f32) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn f64_binop(&mut self, op: FBinop, x: f64, y: f64) -> f64 {
+        op.eval(x, y)
+    }
+
+    fn f64_unop(&mut self, op: FUnop, x: f64) -> f64 {
+        op.eval(x)
+    }
+
+    fn f64_relop(&mut self, op: FRelop, x: f64, y: f64) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn cvtop(&mut self, op: Cvtop, x: Slot) -> Slot {
+        op.eval(x).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            Slot::default()
+        })
+    }
+
+    fn select(&mut self, _ty: Option<Type>, cond: i32, x: Slot, y: Slot) -> Slot {
+        if cond != 0 { x } else { y }
+    }
+
+    fn unreachable(&mut self) {
+        self.set_trap(TrapKind::Unreachable);
+    }
+    fn ref_null(&mut self, _t: Type) -> Slot {
+        Slot::from(None)
+    }
+
+    fn ref_is_null(&mut self, x: Slot) -> i32 {
+        x.reference().is_none() as i32
+    }
+
+    fn ref_func(&mut self, func_idx: usize) -> Slot {
+        Slot::from(Some(func_idx))
+    }
+
+    fn table_get(&mut self, table_idx: usize, idx: i32) -> Slot {
+        self.tables[table_idx].get(idx).map(Slot::from).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            Slot::default()
+        })
+    }
+
+    fn table_set(&mut self, table_idx: usize, idx: i32, val: Slot) {
+        if let Err(kind) = self.tables[table_idx].set(idx, val.reference()) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn table_size(&mut self, table_idx: usize) -> i32 {
+        self.tables[table_idx].size() as i32
+    }
+
+    fn table_grow(&mut self, table_idx: usize, init: Slot, delta: i32) -> i32 {
+        self.tables[table_idx].grow(delta as u32, init.reference())
+    }
+
+    fn table_fill(&mut self, table_idx: usize, dst: i32, val: Slot, len: i32) {
+        if let Err(kind) = self.tables[table_idx].fill(dst, val.reference(), len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = table::copy(&mut self.tables, dst_table, src_table, dst, src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.tables[table_idx].init(dst, &self.elems[elem_idx], src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn elem_drop(&mut self, elem_idx: usize) {
+        self.elems[elem_idx] = vec![];
+    }
+
     fn branch(&mut self, _label_idx: usize) {
         self.stp += 1;
         let ste = self.sidetable[self.stp];
         // stupid casts
         self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
         self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
+        ste.unwind(&mut self.stack);
+    }
+
+    // a br_table's entries follow each other, skip to the one we take
+    fn branch_table(&mut self, labels: &[usize], idx: i32) {
+        self.stp += table_index(idx, labels.len());
+        self.branch(0);
     }
 
     fn fallthru(&mut self) {
@@ -204,6 +906,10 @@ impl CBD_FR for EvalFR {
     }
 
     fn merge(&mut self, _other: ()) {}
+
+    fn take_trap(&mut self) -> Option<TrapKind> {
+        self.trap.take()
+    }
 }
 
 use crate::cps::ContBlock;
@@ -232,6 +938,9 @@ impl AbstractCompiler {
 impl CBD_FR for AbstractCompiler {
     // vals are compiler indices
     type I32Val = usize;
+    type I64Val = usize;
+    type F32Val = usize;
+    type F64Val = usize;
     type StackVal = usize;
     type LocalVal = usize;
     type CondVal = usize;
@@ -253,8 +962,50 @@ impl CBD_FR for AbstractCompiler {
         self.block_bodies[self.stp].push(format!("i.pushi(x{x})"));
     }
 
+    fn popl(&mut self) -> Self::I64Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.popl()"));
+        i
+    }
+
+    fn pushl_imm(&mut self, x: i64) {
+        self.block_bodies[self.stp].push(format!("i.pushl_imm({x})"));
+    }
+
+    fn pushl(&mut self, x: Self::I64Val) {
+        self.block_bodies[self.stp].push(format!("i.pushl(x{x})"));
+    }
+
+    fn popf(&mut self) -> Self::F32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.popf()"));
+        i
+    }
+
+    fn pushf_imm(&mut self, x: f32) {
+        self.block_bodies[self.stp].push(format!("i.pushf_imm(f32::from_bits({:#x}))", x.to_bits()));
+    }
+
+    fn pushf(&mut self, x: Self::F32Val) {
+        self.block_bodies[self.stp].push(format!("i.pushf(x{x})"));
+    }
+
+    fn popd(&mut self) -> Self::F64Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.popd()"));
+        i
+    }
+
+    fn pushd_imm(&mut self, x: f64) {
+        self.block_bodies[self.stp].push(format!("i.pushd_imm(f64::from_bits({:#x}))", x.to_bits()));
+    }
+
+    fn pushd(&mut self, x: Self::F64Val) {
+        self.block_bodies[self.stp].push(format!("i.pushd(x{x})"));
+    }
+
     fn push(&mut self, x: Self::StackVal) {
-        self.block_bodies[self.stp].push(format!("i.pushi(x{x})"));
+        self.block_bodies[self.stp].push(format!("i.push(x{x})"));
     }
 
     fn pop(&mut self) -> Self::StackVal {
@@ -273,15 +1024,32 @@ impl CBD_FR for AbstractCompiler {
         i
     }
 
-    fn start_block(&mut self, ty_index: usize) { 
-        self.block_bodies[self.stp].push(format!("i.start_block({ty_index})"));
+    fn set_global(&mut self, idx: i32, val: Self::StackVal) {
+        self.block_bodies[self.stp].push(format!("i.set_global({idx}, x{val})"));
+    }
+
+    fn get_global(&mut self, idx: i32) -> Self::StackVal {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.get_global({idx})"));
+        i
+    }
+
+    fn start_block(&mut self, ty: BlockSig) { 
+        self.block_bodies[self.stp].push(format!("i.start_block(BlockSig::{ty:?})"));
     }
-    fn start_loop(&mut self, ty_index: usize) {
+    fn start_loop(&mut self, ty: BlockSig) {
         let f = self.stp + 1;
         self.block_bodies[self.stp].push(format!("wl.push_back({f})"));
 
         self.stp += 1;
-        self.block_bodies[self.stp].push(format!("i.start_loop({ty_index})"));
+        self.block_bodies[self.stp].push(format!("i.start_loop(BlockSig::{ty:?})"));
+    }
+    fn start_if(&mut self, ty: BlockSig) {
+        self.block_bodies[self.stp].push(format!("i.start_if(BlockSig::{ty:?})"));
+    }
+    // lands at the top of the else arm's block
+    fn start_else(&mut self) {
+        self.block_bodies[self.stp].push("i.start_else()".to_string());
     }
     fn end(&mut self) {
         self.block_bodies[self.stp].push(format!("i.end()"));
@@ -294,7 +1062,7 @@ impl CBD_FR for AbstractCompiler {
 
     fn i32_add(&mut self, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val {
         let i = self.fv();
-        self.block_bodies[self.stp].push(format!("let x{i} = x{x} + x{y}"));
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i32_add(x{x}, x{y})"));
         i
     }
     fn i32_eqz(&mut self, x: Self::I32Val) -> Self::CondVal {
@@ -302,6 +1070,90 @@ impl CBD_FR for AbstractCompiler {
         self.block_bodies[self.stp].push(format!("let x{i} = i.i32_eqz(x{x})"));
         i
     }
+    fn i32_binop(&mut self, op: I32Binop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i32_binop(I32Binop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn i32_unop(&mut self, op: I32Unop, x: Self::I32Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i32_unop(I32Unop::{op:?}, x{x})"));
+        i
+    }
+    fn i32_relop(&mut self, op: I32Relop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i32_relop(I32Relop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn i64_add(&mut self, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i64_add(x{x}, x{y})"));
+        i
+    }
+    fn i64_binop(&mut self, op: I64Binop, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i64_binop(I64Binop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn i64_unop(&mut self, op: I64Unop, x: Self::I64Val) -> Self::I64Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i64_unop(I64Unop::{op:?}, x{x})"));
+        i
+    }
+    fn i64_relop(&mut self, op: I64Relop, x: Self::I64Val, y: Self::I64Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i64_relop(I64Relop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn i64_eqz(&mut self, x: Self::I64Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i64_eqz(x{x})"));
+        i
+    }
+    fn f32_binop(&mut self, op: FBinop, x: Self::F32Val, y: Self::F32Val) -> Self::F32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.f32_binop(FBinop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn f32_unop(&mut self, op: FUnop, x: Self::F32Val) -> Self::F32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.f32_unop(FUnop::{op:?}, x{x})"));
+        i
+    }
+    fn f32_relop(&mut self, op: FRelop, x: Self::F32Val, y: Self::F32Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.f32_relop(FRelop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn f64_binop(&mut self, op: FBinop, x: Self::F64Val, y: Self::F64Val) -> Self::F64Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.f64_binop(FBinop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn f64_unop(&mut self, op: FUnop, x: Self::F64Val) -> Self::F64Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.f64_unop(FUnop::{op:?}, x{x})"));
+        i
+    }
+    fn f64_relop(&mut self, op: FRelop, x: Self::F64Val, y: Self::F64Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.f64_relop(FRelop::{op:?}, x{x}, x{y})"));
+        i
+    }
+    fn cvtop(&mut self, op: Cvtop, x: Self::StackVal) -> Self::StackVal {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.cvtop(Cvtop::{op:?}, x{x})"));
+        i
+    }
+    fn select(&mut self, ty: Option<Type>, cond: Self::I32Val, x: Self::StackVal, y: Self::StackVal) -> Self::StackVal {
+        let i = self.fv();
+        let ty = ty.map_or("None".to_string(), |t| format!("Some(Type::{t:?})"));
+        self.block_bodies[self.stp].push(format!("let x{i} = i.select({ty}, x{cond}, x{x}, x{y})"));
+        i
+    }
+    fn unreachable(&mut self) {
+        self.block_bodies[self.stp].push("i.unreachable()".to_string());
+    }
 
     fn cbd_br_if(&mut self) {
         let _label_idx = self.codeptr_mut().read_imm_i32();
@@ -309,7 +1161,7 @@ impl CBD_FR for AbstractCompiler {
         let condb = self.i32_eqz(condv);
 
         let fallthru = self.stp + 1;
-        let branch = unsafe { (*self.cont_blocks)[self.stp].br_tgt };
+        let branch = unsafe { (&(*self.cont_blocks))[self.stp].br_tgt.stp };
 
         self.block_bodies[self.stp].push(format!("
         let _ = if (x{condb}.maybe_true()) {{ i.merge(state_{fallthru}); wl.push_back({fallthru}) }} else {{}};
@@ -317,13 +1169,121 @@ impl CBD_FR for AbstractCompiler {
         self.stp += 1;
     }
 
+    fn cbd_if(&mut self) {
+        let ty = self.codeptr_mut().read_block_type();
+        let condv = self.popi();
+        let condb = self.i32_eqz(condv);
+        self.start_if(ty);
+
+        let then = self.stp + 1;
+        let els = unsafe { (&(*self.cont_blocks))[self.stp].br_tgt.stp };
+
+        self.block_bodies[self.stp].push(format!("
+        let _ = if (x{condb}.maybe_true()) {{ i.merge(state_{els}); wl.push_back({els}) }} else {{}};
+        let _ = if (x{condb}.maybe_false()) {{ i.merge(state_{then}); wl.push_back({then}) }} else {{}}"));
+        self.stp += 1;
+    }
+
     fn branch(&mut self, _label_idx: usize) -> Self::MergeState {
-        let tgt = unsafe { (*self.cont_blocks)[self.stp].br_tgt };
+        let tgt = unsafe { (&(*self.cont_blocks))[self.stp].br_tgt.stp };
         self.block_bodies[self.stp].push(format!("wl.push_back({tgt})"));
         self.stp += 1;
         tgt
     }
 
+    fn branch_table(&mut self, _labels: &[usize], idx: Self::I32Val) -> Self::MergeState {
+        let tgts: Vec<usize> = unsafe { &(&(*self.cont_blocks))[self.stp].br_tgts }.iter().map(|t| t.stp).collect();
+        let n = tgts.len();
+        self.block_bodies[self.stp].push(format!("wl.push_back({tgts:?}[table_index(x{idx}, {n})])"));
+        self.stp += 1;
+        tgts[n - 1]
+    }
+
+    fn call(&mut self, func_idx: usize) {
+        self.block_bodies[self.stp].push(format!("i.call({func_idx})"));
+    }
+
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, addr: Self::I32Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.i32_load(I32LoadOp::{op:?}, {memarg:?}, x{addr})"));
+        i
+    }
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, addr: Self::I32Val, val: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.i32_store(I32StoreOp::{op:?}, {memarg:?}, x{addr}, x{val})"));
+    }
+    fn memory_size(&mut self) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.memory_size()"));
+        i
+    }
+    fn memory_grow(&mut self, delta: Self::I32Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.memory_grow(x{delta})"));
+        i
+    }
+    fn memory_init(&mut self, data_idx: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.memory_init({data_idx}, x{dst}, x{src}, x{len})"));
+    }
+    fn data_drop(&mut self, data_idx: usize) {
+        self.block_bodies[self.stp].push(format!("i.data_drop({data_idx})"));
+    }
+    fn memory_copy(&mut self, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.memory_copy(x{dst}, x{src}, x{len})"));
+    }
+    fn memory_fill(&mut self, dst: Self::I32Val, val: Self::I32Val, len: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.memory_fill(x{dst}, x{val}, x{len})"));
+    }
+
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, idx: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.call_indirect({type_idx}, {table_idx}, x{idx})"));
+    }
+
+    fn ref_null(&mut self, t: Type) -> Self::StackVal {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.ref_null(Type::{t:?})"));
+        i
+    }
+    fn ref_is_null(&mut self, x: Self::StackVal) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.ref_is_null(x{x})"));
+        i
+    }
+    fn ref_func(&mut self, func_idx: usize) -> Self::StackVal {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.ref_func({func_idx})"));
+        i
+    }
+    fn table_get(&mut self, table_idx: usize, idx: Self::I32Val) -> Self::StackVal {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.table_get({table_idx}, x{idx})"));
+        i
+    }
+    fn table_set(&mut self, table_idx: usize, idx: Self::I32Val, val: Self::StackVal) {
+        self.block_bodies[self.stp].push(format!("i.table_set({table_idx}, x{idx}, x{val})"));
+    }
+    fn table_size(&mut self, table_idx: usize) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.table_size({table_idx})"));
+        i
+    }
+    fn table_grow(&mut self, table_idx: usize, init: Self::StackVal, delta: Self::I32Val) -> Self::I32Val {
+        let i = self.fv();
+        self.block_bodies[self.stp].push(format!("let x{i} = i.table_grow({table_idx}, x{init}, x{delta})"));
+        i
+    }
+    fn table_fill(&mut self, table_idx: usize, dst: Self::I32Val, val: Self::StackVal, len: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.table_fill({table_idx}, x{dst}, x{val}, x{len})"));
+    }
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.table_copy({dst_table}, {src_table}, x{dst}, x{src}, x{len})"));
+    }
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val) {
+        self.block_bodies[self.stp].push(format!("i.table_init({table_idx}, {elem_idx}, x{dst}, x{src}, x{len})"));
+    }
+    fn elem_drop(&mut self, elem_idx: usize) {
+        self.block_bodies[self.stp].push(format!("i.elem_drop({elem_idx})"));
+    }
+
     fn fallthru(&mut self) -> Self::MergeState {
         let f = self.stp + 1;
         self.block_bodies[self.stp].push(format!("wl.push_back({f})"));
@@ -414,7 +1374,7 @@ const BLOCKS: [&'static dyn Fn(&mut AI, &mut VecDeque<usize>); 6] = [
 
 fn block_0(i: &mut AI, wl: &mut VecDeque<usize>) {
 	i.pushi_imm(5);
-	i.start_block(0);
+	i.start_block(BlockSig::Empty);
 	i.pushi_imm(-15);
 	i.pushi_imm(20);
 	let x1 = i.popi();
@@ -444,11 +1404,11 @@ fn block_2(i: &mut AI, wl: &mut VecDeque<usize>) {
 } /* block_2 */
 
 fn block_3(i: &mut AI, wl: &mut VecDeque<usize>) {
-	i.start_loop(0);
+	i.start_loop(BlockSig::Empty);
 	let x9 = i.get_local(0);
-	i.pushi(x9);
+	i.push(x9);
 	let x10 = i.get_local(1);
-	i.pushi(x10);
+	i.push(x10);
 	let x11 = i.popi();
 	let x12 = i.popi();
 	let x13 = x11 + x12;
@@ -456,7 +1416,7 @@ fn block_3(i: &mut AI, wl: &mut VecDeque<usize>) {
 	let x14 = i.pop();
 	i.set_local(1, x14);
 	let x15 = i.get_local(0);
-	i.pushi(x15);
+	i.push(x15);
 	i.pushi_imm(-1);
 	let x16 = i.popi();
 	let x17 = i.popi();
@@ -465,7 +1425,7 @@ fn block_3(i: &mut AI, wl: &mut VecDeque<usize>) {
 	let x19 = i.pop();
 	i.set_local(0, x19);
 	let x20 = i.get_local(0);
-	i.pushi(x20);
+	i.push(x20);
 	let x21 = i.popi();
 	let x22 = i.i32_eqz(x21);
 	
@@ -480,7 +1440,7 @@ fn block_4(i: &mut AI, wl: &mut VecDeque<usize>) {
 
 fn block_5(i: &mut AI, wl: &mut VecDeque<usize>) {
 	let x23 = i.get_local(1);
-	i.pushi(x23);
+	i.push(x23);
 } /* block_5 */
 
 
@@ -488,10 +1448,20 @@ fn block_5(i: &mut AI, wl: &mut VecDeque<usize>) {
     let nlocals = 2;
     let mut interpreter = EvalFR {
             stack: vec![],
-            locals: vec![0; nlocals],
+            locals: vec![Slot::default(); nlocals],
             codeptr: CodePtr { code: vec![], ip: 0 },
             sidetable: vec![],
             stp: 0,
+            trap: None,
+            frames: vec![],
+            funcs: vec![],
+            hosts: vec![],
+            tables: vec![],
+            elems: vec![],
+            sigs: vec![],
+            memory: LinearMemory::default(),
+            datas: vec![],
+            globals: vec![],
     };
 
     wl.push_back(0);
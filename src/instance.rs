@@ -0,0 +1,572 @@
+use crate::{BlockSig, CodeEntry, CodePtr, Eval, FuncEntry, Opcode, Run, Trap, TrapKind, Type, ValidationError, ValidationErrorKind};
+use crate::tf::{TypedEval, TypedValidate, CBD};
+use crate::frfr::{CBD_FR, EvalFR};
+use crate::mem::{LinearMemory, MAX_PAGES};
+use crate::module::{ConstExpr, Data, DataMode, Elem, ElemMode, ExportDesc, FuncType, GlobalType, ImportDesc, LinkError, Linked, Memory, Module, Table};
+use crate::num::Slot;
+use crate::table::{RefTable, MAX_TABLE_SIZE};
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Instantiation. A Store owns everything instances allocate, laid out in one
+// Linked so a call into another instance is still just a jump. Code refers
+// to store addresses: instantiating rewrites each function's indices through
+// its instance's maps, after validating it against the module's own.
+
+// a store address of each kind
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Extern {
+    Func(usize),
+    Table(usize),
+    Memory(usize),
+    Global(usize),
+}
+
+// the types of what's allocated, for matching imports. The evaluators share
+// one memory, so a store holds at most one.
+#[derive(Debug, Clone, Default)]
+pub struct Store {
+    pub linked: Linked,
+    pub types: Vec<FuncType>, // Linked::sigs index into these
+    pub tables: Vec<Table>,
+    pub memory: Option<Memory>,
+    pub globals: Vec<GlobalType>,
+    registry: HashMap<String, HashMap<String, Extern>>, // what imports resolve to
+}
+
+// how much of each kind a store had allocated, see Store::rollback
+struct Checkpoint {
+    types: usize,
+    funcs: usize,
+    code: usize,
+    sidetable: usize,
+    tables: usize,
+    memory: bool,
+    globals: usize,
+    elems: usize,
+    datas: usize,
+}
+
+// an instance's index spaces, mapping module indices to store addresses
+#[derive(Debug, Clone, Default)]
+pub struct Instance {
+    pub types: Vec<usize>,
+    pub funcs: Vec<usize>,
+    pub func_types: Vec<FuncType>, // of each of funcs, so calls can be checked without the store
+    pub tables: Vec<usize>,
+    pub memories: Vec<usize>,
+    pub globals: Vec<usize>,
+    pub elems: Vec<usize>,
+    pub datas: Vec<usize>,
+    pub exports: HashMap<String, Extern>,
+}
+
+// a Rust closure standing in for a function. It sees the store's memory,
+// and returns the results or a trap, Host(code) for reasons of its own.
+pub type HostFn = dyn Fn(&mut LinearMemory, &[Slot]) -> Result<Vec<Slot>, TrapKind>;
+
+#[derive(Clone)]
+pub struct HostFunc {
+    pub ty: FuncType,
+    pub f: Rc<HostFn>,
+}
+
+impl std::fmt::Debug for HostFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HostFunc").field("ty", &self.ty).finish_non_exhaustive()
+    }
+}
+
+// a call to a host function, from the evaluators' side: the args are popped
+// and the results pushed
+pub fn call_host(host: &HostFunc, memory: &mut LinearMemory, stack: &mut Vec<Slot>) -> Result<(), TrapKind> {
+    let args = stack.len().checked_sub(host.ty.params.len()).ok_or(TrapKind::StackUnderflow)?;
+    let args: Vec<Slot> = stack.drain(args..).collect();
+    let results = (host.f)(memory, &args)?;
+    if results.len() != host.ty.results.len() {
+        return Err(TrapKind::HostResultArity);
+    }
+    stack.extend(results);
+    Ok(())
+}
+
+// what can run code in a store: it takes the store's Linked for a call and
+// hands it back after, with memory, tables and globals as the call left them
+pub trait Evaluator {
+    fn from_linked(linked: Linked) -> Self;
+    fn into_linked(self) -> Linked;
+    // the results, or the trap that ended the call
+    fn invoke(&mut self, func_addr: usize, args: &[Slot]) -> Result<Vec<Slot>, Trap>;
+}
+
+impl Store {
+    // makes an instance's exports importable as module `name`, alongside
+    // anything already defined there
+    pub fn register(&mut self, name: &str, instance: &Instance) {
+        self.registry.entry(name.to_string()).or_default().extend(instance.exports.clone());
+    }
+
+    // allocates a host function, importable as module.name. Its results
+    // must match the type, the slots themselves aren't checked.
+    pub fn define_func(
+        &mut self,
+        module: &str,
+        name: &str,
+        ty: FuncType,
+        f: impl Fn(&mut LinearMemory, &[Slot]) -> Result<Vec<Slot>, TrapKind> + 'static,
+    ) -> usize {
+        let type_addr = self.add_type(&ty);
+        let linked = &mut self.linked;
+        let entry = FuncEntry {
+            ip: 0,
+            stp: 0,
+            end_ip: 0,
+            params: ty.params.len(),
+            locals: 0,
+            sig: linked.sigs[type_addr],
+            host: Some(linked.hosts.len()),
+        };
+        linked.hosts.push(HostFunc { ty, f: Rc::new(f) });
+        linked.funcs.push(entry);
+        let func_addr = linked.funcs.len() - 1;
+        self.registry.entry(module.to_string()).or_default().insert(name.to_string(), Extern::Func(func_addr));
+        func_addr
+    }
+
+    pub fn func_type(&self, func_addr: usize) -> &FuncType {
+        &self.types[self.linked.funcs[func_addr].sig]
+    }
+
+    pub fn global(&self, global_addr: usize) -> Slot {
+        self.linked.globals[global_addr]
+    }
+
+    pub fn invoke<E: Evaluator>(&mut self, func_addr: usize, args: &[Slot]) -> Result<Vec<Slot>, Trap> {
+        let mut eval = E::from_linked(std::mem::take(&mut self.linked));
+        let res = eval.invoke(func_addr, args);
+        self.linked = eval.into_linked();
+        res
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        let linked = &self.linked;
+        Checkpoint {
+            types: self.types.len(),
+            funcs: linked.funcs.len(),
+            code: linked.code.len(),
+            sidetable: linked.sidetable.len(),
+            tables: linked.tables.len(),
+            memory: self.memory.is_some(),
+            globals: linked.globals.len(),
+            elems: linked.elems.len(),
+            datas: linked.datas.len(),
+        }
+    }
+
+    // drops whatever was allocated since the checkpoint
+    fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.types.truncate(checkpoint.types);
+        self.tables.truncate(checkpoint.tables);
+        self.globals.truncate(checkpoint.globals);
+        if !checkpoint.memory {
+            self.memory = None;
+            self.linked.memory = LinearMemory::default();
+        }
+        let linked = &mut self.linked;
+        linked.sigs.truncate(checkpoint.types);
+        linked.funcs.truncate(checkpoint.funcs);
+        linked.code.truncate(checkpoint.code);
+        linked.sidetable.truncate(checkpoint.sidetable);
+        linked.tables.truncate(checkpoint.tables);
+        linked.globals.truncate(checkpoint.globals);
+        linked.elems.truncate(checkpoint.elems);
+        linked.datas.truncate(checkpoint.datas);
+    }
+
+    // the first type with the same signature, so call_indirect can compare
+    // types across instances with one integer compare
+    fn add_type(&mut self, ty: &FuncType) -> usize {
+        let sig = self.types.iter().position(|t| t == ty).unwrap_or(self.types.len());
+        self.types.push(ty.clone());
+        self.linked.sigs.push(sig);
+        self.types.len() - 1
+    }
+
+    fn resolve(&self, module: &Module, import: usize) -> Result<Extern, LinkError> {
+        let import = &module.imports[import];
+        let unknown = || LinkError::UnknownImport { module: import.module.clone(), name: import.name.clone() };
+        let incompatible = || LinkError::IncompatibleImport { module: import.module.clone(), name: import.name.clone() };
+        let ext = *self.registry.get(&import.module).and_then(|m| m.get(&import.name)).ok_or_else(unknown)?;
+        let matches = match (&import.desc, ext) {
+            (&ImportDesc::Func(ty), Extern::Func(addr)) => *self.func_type(addr) == module.types[ty],
+            (ImportDesc::Table(table), Extern::Table(addr)) => {
+                let actual = &self.tables[addr];
+                actual.elem_type == table.elem_type
+                    && limits_match(self.linked.tables[addr].size(), actual.max, table.min, table.max)
+            }
+            (ImportDesc::Memory(memory), Extern::Memory(_)) => {
+                let actual = self.memory.as_ref().unwrap();
+                limits_match(self.linked.memory.pages(), actual.max, memory.min, memory.max)
+            }
+            (ImportDesc::Global(ty), Extern::Global(addr)) => self.globals[addr] == *ty,
+            _ => false,
+        };
+        if !matches {
+            return Err(incompatible());
+        }
+        Ok(ext)
+    }
+}
+
+// an import may ask for less than it gets, but not for more
+fn limits_match(size: u32, max: Option<u32>, want_min: u32, want_max: Option<u32>) -> bool {
+    size >= want_min && match (max, want_max) {
+        (_, None) => true,
+        (None, Some(_)) => false,
+        (Some(max), Some(want_max)) => max <= want_max,
+    }
+}
+
+impl Instance {
+    // instantiates and runs the start function, if any, with E
+    pub fn new<E: Evaluator>(store: &mut Store, module: &Module) -> Result<Self, LinkError> {
+        let instance = Instance::allocate(store, module)?;
+        if let Some(start) = module.start {
+            store.invoke::<E>(instance.funcs[start], &[]).map_err(LinkError::StartTrapped)?;
+        }
+        Ok(instance)
+    }
+
+    // everything instantiation does but run the start function. On error
+    // the store is left as it was, but for segments already copied into
+    // imported tables or memory.
+    pub fn allocate(store: &mut Store, module: &Module) -> Result<Self, LinkError> {
+        let checkpoint = store.checkpoint();
+        Instance::allocate_in(store, module).inspect_err(|_| store.rollback(checkpoint))
+    }
+
+    fn allocate_in(store: &mut Store, module: &Module) -> Result<Self, LinkError> {
+        check_imports(module)?;
+        check_funcs(module)?;
+        let mut instance = Instance::default();
+        for i in 0..module.imports.len() {
+            match store.resolve(module, i)? {
+                Extern::Func(addr) => instance.funcs.push(addr),
+                Extern::Table(addr) => instance.tables.push(addr),
+                Extern::Memory(addr) => instance.memories.push(addr),
+                Extern::Global(addr) => instance.globals.push(addr),
+            }
+        }
+        // validates every function, the sidetables only exist for valid code
+        let mut sidetables = vec![];
+        for func_idx in 0..module.funcs.len() {
+            let mut validate = TypedValidate::from_module(module, func_idx);
+            validate.dispatch()?;
+            sidetables.push(validate.build_sidetable());
+        }
+        let memories = module.memory_types();
+        match (memories.as_slice(), &module.memories[..]) {
+            (_, []) => {}
+            ([_], [Memory { min, max }]) if *min <= max.unwrap_or(MAX_PAGES) && max.unwrap_or(0) <= MAX_PAGES => {
+                if store.memory.is_some() {
+                    return Err(LinkError::MultipleMemories);
+                }
+            }
+            ([_], [Memory { min, max }]) => return Err(LinkError::MemoryLimits { min: *min, max: *max }),
+            _ => return Err(LinkError::MultipleMemories),
+        }
+        for (i, Table { elem_type, min, max }) in module.tables.iter().enumerate() {
+            if !elem_type.is_ref() {
+                return Err(LinkError::InvalidTable { table: i, kind: ValidationErrorKind::ExpectedRef(*elem_type) });
+            }
+            // tables are allocated at their minimum, so that's capped too
+            if *min > max.unwrap_or(MAX_TABLE_SIZE) || *min > MAX_TABLE_SIZE {
+                return Err(LinkError::TableLimits { min: *min, max: *max });
+            }
+        }
+        module.check_exports()?;
+        if let Some(start) = module.start {
+            let func_types = module.func_types();
+            let empty = FuncType { params: vec![], results: vec![] };
+            if func_types.get(start) != Some(&empty) {
+                return Err(LinkError::InvalidStart(start));
+            }
+        }
+
+        instance.types = module.types.iter().map(|ty| store.add_type(ty)).collect();
+        let first_func = store.linked.funcs.len();
+        instance.funcs.extend(first_func..first_func + module.funcs.len());
+        // imported functions matched these exactly
+        instance.func_types = module.func_types();
+        let first_table = store.linked.tables.len();
+        instance.tables.extend(first_table..first_table + module.tables.len());
+        let first_elem = store.linked.elems.len();
+        instance.elems.extend(first_elem..first_elem + module.elems.len());
+        let first_data = store.linked.datas.len();
+        instance.datas.extend(first_data..first_data + module.datas.len());
+        let imported_globals = instance.globals.len();
+        let first_global = store.linked.globals.len();
+        instance.globals.extend(first_global..first_global + module.globals.len());
+        for (func_idx, (func, sidetable)) in module.funcs.iter().zip(sidetables).enumerate() {
+            let linked = &mut store.linked;
+            // deltas are relative, so entries stay valid wherever they land
+            linked.funcs.push(FuncEntry {
+                ip: linked.code.len(),
+                stp: linked.sidetable.len(),
+                end_ip: linked.code.len() + func.code.len(),
+                params: module.func_type(func_idx).params.len(),
+                locals: func.locals.len(),
+                sig: linked.sigs[instance.types[func.ty]],
+                host: None,
+            });
+            linked.code.extend(instance.relocate(&func.code));
+            linked.sidetable.extend(sidetable);
+        }
+        for table in &module.tables {
+            store.linked.tables.push(RefTable::new(table.min, table.max));
+            store.tables.push(table.clone());
+        }
+        if let [memory] = &module.memories[..] {
+            store.linked.memory = LinearMemory::new(memory.min, memory.max);
+            store.memory = Some(memory.clone());
+            instance.memories.push(0);
+        }
+        // initializers only see the globals before them
+        for (i, global) in module.globals.iter().enumerate() {
+            let global_idx = imported_globals + i;
+            let val = instance.eval_const(store, global.init, global.ty.ty, global_idx)
+                .map_err(|kind| LinkError::InvalidGlobal { global: global_idx, kind })?;
+            store.linked.globals.push(val);
+            store.globals.push(global.ty);
+        }
+        for (i, elem) in module.elems.iter().enumerate() {
+            let refs = instance.init_elem(store, i, elem)?;
+            store.linked.elems.push(refs);
+        }
+        for (i, data) in module.datas.iter().enumerate() {
+            let bytes = instance.init_data(store, i, data)?;
+            store.linked.datas.push(bytes);
+        }
+        instance.exports = module.exports.iter().map(|export| {
+            let ext = match export.desc {
+                ExportDesc::Func(idx) => Extern::Func(instance.funcs[idx]),
+                ExportDesc::Table(idx) => Extern::Table(instance.tables[idx]),
+                ExportDesc::Memory(idx) => Extern::Memory(instance.memories[idx]),
+                ExportDesc::Global(idx) => Extern::Global(instance.globals[idx]),
+            };
+            (export.name.clone(), ext)
+        }).collect();
+        Ok(instance)
+    }
+
+    pub fn export(&self, name: &str) -> Option<Extern> {
+        self.exports.get(name).copied()
+    }
+
+    // the store address of an exported function
+    pub fn func(&self, name: &str) -> Option<usize> {
+        match self.export(name)? {
+            Extern::Func(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    // module indices in validated code to store addresses
+    fn relocate(&self, code: &[CodeEntry]) -> Vec<CodeEntry> {
+        use Opcode::*;
+        let mut code = code.to_vec();
+        for ip in 0..code.len() {
+            let CodeEntry::Op(op) = code[ip] else { continue };
+            let spaces: &[&[usize]] = match op {
+                Call | RefFunc => &[&self.funcs],
+                CallIndirect => &[&self.types, &self.tables],
+                GlobalGet | GlobalSet => &[&self.globals],
+                TableGet | TableSet | TableSize | TableGrow | TableFill => &[&self.tables],
+                TableCopy => &[&self.tables, &self.tables],
+                TableInit => &[&self.elems, &self.tables],
+                ElemDrop => &[&self.elems],
+                MemoryInit | DataDrop => &[&self.datas],
+                _ => &[],
+            };
+            for (entry, space) in code[ip + 1..].iter_mut().zip(spaces) {
+                if let CodeEntry::I32Imm(idx) = entry {
+                    *idx = space[*idx as usize] as i32;
+                }
+            }
+            if let Some(CodeEntry::BlockType(BlockSig::Index(idx))) = code.get_mut(ip + 1) {
+                *idx = self.types[*idx];
+            }
+        }
+        code
+    }
+
+    // the value of expr, which must have type `expected` and may only read
+    // the first `globals` globals
+    fn eval_const(&self, store: &Store, expr: ConstExpr, expected: Type, globals: usize) -> Result<Slot, ValidationErrorKind> {
+        let (found, val) = match expr {
+            ConstExpr::I32Const(n) => (Type::I32, n.into()),
+            ConstExpr::I64Const(n) => (Type::I64, n.into()),
+            ConstExpr::F32Const(bits) => (Type::F32, f32::from_bits(bits).into()),
+            ConstExpr::F64Const(bits) => (Type::F64, Slot(bits)),
+            ConstExpr::GlobalGet(idx) if idx >= globals => return Err(ValidationErrorKind::UnknownGlobal(idx)),
+            ConstExpr::GlobalGet(idx) => {
+                let ty = store.globals[self.globals[idx]];
+                if ty.mutable {
+                    return Err(ValidationErrorKind::ConstExprRequired);
+                }
+                (ty.ty, store.global(self.globals[idx]))
+            }
+            ConstExpr::RefNull(t) => (t, Slot::from(None)),
+            ConstExpr::RefFunc(idx) if idx >= self.funcs.len() => return Err(ValidationErrorKind::UnknownFunc(idx)),
+            ConstExpr::RefFunc(idx) => (Type::FuncRef, Slot::from(Some(self.funcs[idx]))),
+        };
+        if found != expected {
+            return Err(ValidationErrorKind::TypeMismatch { expected, found });
+        }
+        Ok(val)
+    }
+
+    // the segment's references, left for table.init only if it's passive
+    fn init_elem(&self, store: &mut Store, i: usize, elem: &Elem) -> Result<Vec<Option<usize>>, LinkError> {
+        let invalid = |kind| LinkError::InvalidElem { elem: i, kind };
+        if !elem.ty.is_ref() {
+            return Err(invalid(ValidationErrorKind::ExpectedRef(elem.ty)));
+        }
+        let refs = elem.init.iter()
+            .map(|&expr| self.eval_const(store, expr, elem.ty, self.globals.len()).map(Slot::reference))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        let (table_idx, offset) = match elem.mode {
+            ElemMode::Active { table, offset } => (table, offset),
+            ElemMode::Passive => return Ok(refs),
+            ElemMode::Declarative => return Ok(vec![]),
+        };
+        let offset = self.eval_const(store, offset, Type::I32, self.globals.len()).map_err(invalid)?.i32();
+        let &table_addr = self.tables.get(table_idx).ok_or(invalid(ValidationErrorKind::UnknownTable(table_idx)))?;
+        let ty = store.tables[table_addr].elem_type;
+        if ty != elem.ty {
+            return Err(invalid(ValidationErrorKind::TypeMismatch { expected: ty, found: elem.ty }));
+        }
+        store.linked.tables[table_addr].init(offset, &refs, 0, refs.len() as i32).map_err(|_| LinkError::ElemOutOfBounds(i))?;
+        Ok(vec![])
+    }
+
+    // the segment's bytes, left for memory.init only if it's passive
+    fn init_data(&self, store: &mut Store, i: usize, data: &Data) -> Result<Vec<u8>, LinkError> {
+        let invalid = |kind| LinkError::InvalidData { data: i, kind };
+        let (memory_idx, offset) = match data.mode {
+            DataMode::Active { memory, offset } => (memory, offset),
+            DataMode::Passive => return Ok(data.init.clone()),
+        };
+        if memory_idx >= self.memories.len() {
+            return Err(invalid(ValidationErrorKind::UnknownMemory(memory_idx)));
+        }
+        let offset = self.eval_const(store, offset, Type::I32, self.globals.len()).map_err(invalid)?.i32();
+        store.linked.memory.init(offset, &data.init, 0, data.init.len() as i32).map_err(|_| LinkError::DataOutOfBounds(i))?;
+        Ok(vec![])
+    }
+}
+
+// type indices first, the index spaces depend on them
+fn check_imports(module: &Module) -> Result<(), LinkError> {
+    for (i, import) in module.imports.iter().enumerate() {
+        let kind = match &import.desc {
+            &ImportDesc::Func(ty) if ty >= module.types.len() => ValidationErrorKind::UnknownType(ty),
+            ImportDesc::Table(table) if !table.elem_type.is_ref() => ValidationErrorKind::ExpectedRef(table.elem_type),
+            _ => continue,
+        };
+        return Err(LinkError::InvalidImport { import: i, kind });
+    }
+    Ok(())
+}
+
+// likewise for defined functions, before anything looks up their types
+fn check_funcs(module: &Module) -> Result<(), LinkError> {
+    match module.funcs.iter().find(|f| f.ty >= module.types.len()) {
+        Some(func) => Err(LinkError::Invalid(ValidationError { kind: ValidationErrorKind::UnknownType(func.ty), ip: 0, depth: 0 })),
+        None => Ok(()),
+    }
+}
+
+impl Module {
+    fn check_exports(&self) -> Result<(), LinkError> {
+        let (funcs, tables) = (self.func_types().len(), self.table_types().len());
+        let (memories, globals) = (self.memory_types().len(), self.global_types().len());
+        let mut names = std::collections::HashSet::new();
+        for export in &self.exports {
+            let kind = match export.desc {
+                ExportDesc::Func(idx) if idx >= funcs => Some(ValidationErrorKind::UnknownFunc(idx)),
+                ExportDesc::Table(idx) if idx >= tables => Some(ValidationErrorKind::UnknownTable(idx)),
+                ExportDesc::Memory(idx) if idx >= memories => Some(ValidationErrorKind::UnknownMemory(idx)),
+                ExportDesc::Global(idx) if idx >= globals => Some(ValidationErrorKind::UnknownGlobal(idx)),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                return Err(LinkError::InvalidExport { name: export.name.clone(), kind });
+            }
+            if !names.insert(&export.name) {
+                return Err(LinkError::DuplicateExport(export.name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+// the evaluators start at the end of the code, where returning from the
+// outermost call goes
+macro_rules! evaluator {
+    ($eval:ident, $run:ident) => {
+        impl Evaluator for $eval {
+            fn from_linked(linked: Linked) -> Self {
+                $eval {
+                    stack: vec![],
+                    locals: vec![],
+                    codeptr: CodePtr { ip: linked.code.len(), code: linked.code },
+                    sidetable: linked.sidetable,
+                    stp: 0,
+                    trap: None,
+                    frames: vec![],
+                    funcs: linked.funcs,
+                    hosts: linked.hosts,
+                    tables: linked.tables,
+                    elems: linked.elems,
+                    sigs: linked.sigs,
+                    memory: linked.memory,
+                    datas: linked.datas,
+                    globals: linked.globals,
+                }
+            }
+
+            fn into_linked(self) -> Linked {
+                Linked {
+                    code: self.codeptr.code,
+                    sidetable: self.sidetable,
+                    funcs: self.funcs,
+                    hosts: self.hosts,
+                    tables: self.tables,
+                    elems: self.elems,
+                    sigs: self.sigs,
+                    memory: self.memory,
+                    datas: self.datas,
+                    globals: self.globals,
+                }
+            }
+
+            fn invoke(&mut self, func_addr: usize, args: &[Slot]) -> Result<Vec<Slot>, Trap> {
+                self.stack.extend_from_slice(args);
+                self.call(func_addr);
+                // a bad address or too few args
+                if let Some(kind) = self.trap.take() {
+                    return Err(Trap { kind, ip: self.codeptr.ip });
+                }
+                self.$run()?;
+                Ok(std::mem::take(&mut self.stack))
+            }
+        }
+    };
+}
+
+evaluator!(Eval, dispatch);
+evaluator!(TypedEval, dispatch);
+evaluator!(EvalFR, run);
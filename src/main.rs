@@ -5,9 +5,25 @@ use tf::{TypedEval, TypedValidate, TypedCompiler, CBD};
 
 mod cps;
 mod frfr;
+mod module;
+mod decode;
+mod wat;
+mod disasm;
+mod num;
+mod mem;
+mod table;
+mod instance;
+mod embed;
 
 use frfr::{CBD_FR, EvalFR, AbstractCompiler};
 
+use num::{I32Binop, I32Unop, I32Relop, I64Binop, I64Unop, I64Relop, FBinop, FUnop, FRelop, Cvtop, Slot};
+use mem::{I32LoadOp, I32StoreOp, LinearMemory, MemArg};
+use table::RefTable;
+use instance::{call_host, HostFunc, Instance, Store};
+use embed::Value;
+use module::FuncType;
+
 #[cfg(test)]
 mod test;
 
@@ -60,6 +76,271 @@ macro_rules! cbd {
             self.pushi(z);
         }
 
+        fn cbd_i32_binop(&mut self, op: I32Binop) {
+            let y = self.popi();
+            let x = self.popi();
+            let z = self.i32_binop(op, x, y);
+            self.pushi(z);
+        }
+
+        fn cbd_i32_unop(&mut self, op: I32Unop) {
+            let x = self.popi();
+            let z = self.i32_unop(op, x);
+            self.pushi(z);
+        }
+
+        fn cbd_i32_relop(&mut self, op: I32Relop) {
+            let y = self.popi();
+            let x = self.popi();
+            let z = self.i32_relop(op, x, y);
+            self.pushi(z);
+        }
+
+        fn cbd_i64_const(&mut self) {
+            let x = self.codeptr.read_imm_i64();
+            self.pushl_imm(x);
+        }
+
+        fn cbd_i64_add(&mut self) {
+            let y = self.popl();
+            let x = self.popl();
+            let z = self.i64_add(x, y);
+            self.pushl(z);
+        }
+
+        fn cbd_i64_binop(&mut self, op: I64Binop) {
+            let y = self.popl();
+            let x = self.popl();
+            let z = self.i64_binop(op, x, y);
+            self.pushl(z);
+        }
+
+        fn cbd_i64_unop(&mut self, op: I64Unop) {
+            let x = self.popl();
+            let z = self.i64_unop(op, x);
+            self.pushl(z);
+        }
+
+        fn cbd_i64_relop(&mut self, op: I64Relop) {
+            let y = self.popl();
+            let x = self.popl();
+            let z = self.i64_relop(op, x, y);
+            self.pushi(z);
+        }
+
+        fn cbd_i64_eqz(&mut self) {
+            let x = self.popl();
+            let z = self.i64_eqz(x);
+            self.pushi(z);
+        }
+
+        fn cbd_f32_const(&mut self) {
+            let x = self.codeptr.read_imm_f32();
+            self.pushf_imm(x);
+        }
+
+        fn cbd_f32_binop(&mut self, op: FBinop) {
+            let y = self.popf();
+            let x = self.popf();
+            let z = self.f32_binop(op, x, y);
+            self.pushf(z);
+        }
+
+        fn cbd_f32_unop(&mut self, op: FUnop) {
+            let x = self.popf();
+            let z = self.f32_unop(op, x);
+            self.pushf(z);
+        }
+
+        fn cbd_f32_relop(&mut self, op: FRelop) {
+            let y = self.popf();
+            let x = self.popf();
+            let z = self.f32_relop(op, x, y);
+            self.pushi(z);
+        }
+
+        fn cbd_f64_const(&mut self) {
+            let x = self.codeptr.read_imm_f64();
+            self.pushd_imm(x);
+        }
+
+        fn cbd_f64_binop(&mut self, op: FBinop) {
+            let y = self.popd();
+            let x = self.popd();
+            let z = self.f64_binop(op, x, y);
+            self.pushd(z);
+        }
+
+        fn cbd_f64_unop(&mut self, op: FUnop) {
+            let x = self.popd();
+            let z = self.f64_unop(op, x);
+            self.pushd(z);
+        }
+
+        fn cbd_f64_relop(&mut self, op: FRelop) {
+            let y = self.popd();
+            let x = self.popd();
+            let z = self.f64_relop(op, x, y);
+            self.pushi(z);
+        }
+
+        fn cbd_cvtop(&mut self, op: Cvtop) {
+            let x = self.pop();
+            let z = self.cvtop(op, x);
+            self.push(z);
+        }
+
+        fn cbd_i32_load(&mut self, op: I32LoadOp) {
+            let memarg = self.codeptr.read_memarg();
+            let addr = self.popi();
+            let x = self.i32_load(op, memarg, addr);
+            self.pushi(x);
+        }
+
+        fn cbd_i32_store(&mut self, op: I32StoreOp) {
+            let memarg = self.codeptr.read_memarg();
+            let val = self.popi();
+            let addr = self.popi();
+            self.i32_store(op, memarg, addr, val);
+        }
+
+        fn cbd_memory_size(&mut self) {
+            let x = self.memory_size();
+            self.pushi(x);
+        }
+
+        fn cbd_memory_grow(&mut self) {
+            let delta = self.popi();
+            let x = self.memory_grow(delta);
+            self.pushi(x);
+        }
+
+        fn cbd_memory_init(&mut self) {
+            let data_idx = self.codeptr.read_imm_i32();
+            let len = self.popi();
+            let src = self.popi();
+            let dst = self.popi();
+            self.memory_init(data_idx as usize, dst, src, len);
+        }
+
+        fn cbd_data_drop(&mut self) {
+            let data_idx = self.codeptr.read_imm_i32();
+            self.data_drop(data_idx as usize);
+        }
+
+        fn cbd_memory_copy(&mut self) {
+            let len = self.popi();
+            let src = self.popi();
+            let dst = self.popi();
+            self.memory_copy(dst, src, len);
+        }
+
+        fn cbd_memory_fill(&mut self) {
+            let len = self.popi();
+            let val = self.popi();
+            let dst = self.popi();
+            self.memory_fill(dst, val, len);
+        }
+
+        fn cbd_ref_null(&mut self) {
+            let t = self.codeptr.read_val_type();
+            let x = self.ref_null(t);
+            self.push(x);
+        }
+
+        fn cbd_ref_is_null(&mut self) {
+            let x = self.pop();
+            let z = self.ref_is_null(x);
+            self.pushi(z);
+        }
+
+        fn cbd_ref_func(&mut self) {
+            let func_idx = self.codeptr.read_imm_i32();
+            let x = self.ref_func(func_idx as usize);
+            self.push(x);
+        }
+
+        fn cbd_table_get(&mut self) {
+            let table_idx = self.codeptr.read_imm_i32();
+            let idx = self.popi();
+            let x = self.table_get(table_idx as usize, idx);
+            self.push(x);
+        }
+
+        fn cbd_table_set(&mut self) {
+            let table_idx = self.codeptr.read_imm_i32();
+            let val = self.pop();
+            let idx = self.popi();
+            self.table_set(table_idx as usize, idx, val);
+        }
+
+        fn cbd_table_size(&mut self) {
+            let table_idx = self.codeptr.read_imm_i32();
+            let x = self.table_size(table_idx as usize);
+            self.pushi(x);
+        }
+
+        fn cbd_table_grow(&mut self) {
+            let table_idx = self.codeptr.read_imm_i32();
+            let delta = self.popi();
+            let init = self.pop();
+            let x = self.table_grow(table_idx as usize, init, delta);
+            self.pushi(x);
+        }
+
+        fn cbd_table_fill(&mut self) {
+            let table_idx = self.codeptr.read_imm_i32();
+            let len = self.popi();
+            let val = self.pop();
+            let dst = self.popi();
+            self.table_fill(table_idx as usize, dst, val, len);
+        }
+
+        fn cbd_table_copy(&mut self) {
+            let dst_table = self.codeptr.read_imm_i32();
+            let src_table = self.codeptr.read_imm_i32();
+            let len = self.popi();
+            let src = self.popi();
+            let dst = self.popi();
+            self.table_copy(dst_table as usize, src_table as usize, dst, src, len);
+        }
+
+        fn cbd_table_init(&mut self) {
+            let elem_idx = self.codeptr.read_imm_i32();
+            let table_idx = self.codeptr.read_imm_i32();
+            let len = self.popi();
+            let src = self.popi();
+            let dst = self.popi();
+            self.table_init(table_idx as usize, elem_idx as usize, dst, src, len);
+        }
+
+        fn cbd_elem_drop(&mut self) {
+            let elem_idx = self.codeptr.read_imm_i32();
+            self.elem_drop(elem_idx as usize);
+        }
+
+        fn cbd_nop(&mut self) { }
+
+        fn cbd_unreachable(&mut self) {
+            self.unreachable();
+            self.set_unreachable();
+        }
+
+        fn cbd_drop(&mut self) {
+            self.pop();
+        }
+
+        // the typed form names the operands' type, the untyped one leaves
+        // it to validation to check they match
+        fn cbd_select(&mut self, typed: bool) {
+            let ty = typed.then(|| self.codeptr.read_val_type());
+            let cond = self.popi();
+            let y = self.pop();
+            let x = self.pop();
+            let z = self.select(ty, cond, x, y);
+            self.push(z);
+        }
+
         fn cbd_local_set(&mut self) {
             let idx = self.codeptr.read_imm_i32();
             let val = self.pop();
@@ -79,6 +360,18 @@ macro_rules! cbd {
             self.set_local(idx, val);
         }
 
+        fn cbd_global_get(&mut self) {
+            let idx = self.codeptr.read_imm_i32();
+            let val = self.get_global(idx);
+            self.push(val);
+        }
+
+        fn cbd_global_set(&mut self) {
+            let idx = self.codeptr.read_imm_i32();
+            let val = self.pop();
+            self.set_global(idx, val);
+        }
+
         fn cbd_block(&mut self) {
             let ty = self.codeptr.read_block_type();
             self.start_block(ty);
@@ -89,9 +382,30 @@ macro_rules! cbd {
             self.start_loop(ty);
         }
 
+        fn cbd_if(&mut self) {
+            let ty = self.codeptr.read_block_type();
+            let condv = self.popi();
+            let condb = Self::i32_eqz(condv);
+            self.start_if(ty);
+            cbdif! {
+                if (condb) then {
+                    self.branch_if_false();
+                }, else {
+                    self.fallthru();
+                }
+            }
+        }
+
+        // only reached by falling out of the then arm
+        fn cbd_else(&mut self) {
+            self.branch(0);
+            self.start_else();
+        }
+
         fn cbd_br(&mut self) {
             let label_idx = self.codeptr.read_imm_i32();
             self.branch(label_idx as usize);
+            self.set_unreachable();
         }
 
         fn cbd_br_if(&mut self) {
@@ -108,34 +422,112 @@ macro_rules! cbd {
             }
         }
         
-        fn cbd_end(&mut self) {
-            self.end();
+        fn cbd_return(&mut self) {
+            self.branch_return();
+            self.set_unreachable();
         }
 
-        fn dispatch(&mut self) {
-            while let Some(op) = self.codeptr.read_op() {
-                op_dispatch!(op, self)
-            }
+        fn cbd_call(&mut self) {
+            let func_idx = self.codeptr.read_imm_i32();
+            self.call(func_idx as usize);
+        }
+
+        fn cbd_call_indirect(&mut self) {
+            let type_idx = self.codeptr.read_imm_i32();
+            let table_idx = self.codeptr.read_imm_i32();
+            let idx = self.popi();
+            self.call_indirect(type_idx as usize, table_idx as usize, idx);
+        }
+
+        fn cbd_br_table(&mut self) {
+            let labels = self.codeptr.read_labels();
+            let idx = self.popi();
+            self.branch_table(&labels, idx);
+            self.set_unreachable();
+        }
+
+        fn cbd_end(&mut self) {
+            self.end();
         }
     }
 }
 
+// the sub-opcode following a prefix byte, if the op has one
+macro_rules! sub_opcode {
+    () => { None::<u32> };
+    ($sub:literal) => { Some::<u32>($sub) };
+}
+
 #[macro_export]
 macro_rules! mk_opcodes {
-    ($(($op:ident, $f:ident)),*) => {
-        #[derive(Debug, Copy, Clone)]
+    ($(($op:ident, $f:ident $(($($arg:expr),*))?, $byte:literal $($sub:literal)?, $name:expr)),*) => {
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
         pub enum Opcode {
             $(
                 $op,
             )*
         }
 
+        impl Opcode {
+            // binary encoding, used by the decoder: a single byte, or a
+            // prefix byte and then a u32 sub-opcode
+            pub fn from_byte(byte: u8) -> Option<Opcode> {
+                match byte {
+                    $(b if b == $byte && sub_opcode!($($sub)?).is_none() => Some(Opcode::$op),)*
+                    _ => None,
+                }
+            }
+
+            pub fn from_prefixed(prefix: u8, sub: u32) -> Option<Opcode> {
+                match (prefix, Some(sub)) {
+                    $((b, s) if b == $byte && s == sub_opcode!($($sub)?) => Some(Opcode::$op),)*
+                    _ => None,
+                }
+            }
+
+            pub fn is_prefix(byte: u8) -> bool {
+                match byte {
+                    $(b if b == $byte && sub_opcode!($($sub)?).is_some() => true,)*
+                    _ => false,
+                }
+            }
+
+            // the prefix, for prefixed ops
+            pub fn byte(self) -> u8 {
+                match self {
+                    $(Opcode::$op => $byte),*
+                }
+            }
+
+            pub fn sub_opcode(self) -> Option<u32> {
+                match self {
+                    $(Opcode::$op => sub_opcode!($($sub)?)),*
+                }
+            }
+
+            // text format mnemonic. The typed select shares the untyped
+            // one's, the parser tells them apart by the (result t).
+            #[allow(unreachable_patterns)]
+            pub fn from_name(name: &str) -> Option<Opcode> {
+                match name {
+                    $($name => Some(Opcode::$op),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Opcode::$op => $name),*
+                }
+            }
+        }
+
         #[macro_export]
         macro_rules! op_dispatch {
             ($dispatch_op:expr, $dispatcher:expr) => {{
                 use Opcode::*;
                 match $dispatch_op {
-                    $($op => $dispatcher.$f()),*
+                    $($op => $dispatcher.$f($($($arg),*)?)),*
                 }
             }}
         }
@@ -143,23 +535,227 @@ macro_rules! mk_opcodes {
 }
 
 mk_opcodes! {
-    (I32Const, cbd_i32_const),
-    (I32Add, cbd_i32_add),
-    (LocalSet, cbd_local_set),
-    (LocalGet, cbd_local_get),
-    (Block, cbd_block),
-    (Loop, cbd_loop),
-    (End, cbd_end),
-    (Br, cbd_br),
-    (BrIf, cbd_br_if)
-}
-
-#[derive(Copy, Clone, Debug)]
+    (I32Const, cbd_i32_const, 0x41, "i32.const"),
+    (I32Add, cbd_i32_add, 0x6A, "i32.add"),
+    (LocalSet, cbd_local_set, 0x21, "local.set"),
+    (LocalGet, cbd_local_get, 0x20, "local.get"),
+    (LocalTee, cbd_local_tee, 0x22, "local.tee"),
+    (GlobalGet, cbd_global_get, 0x23, "global.get"),
+    (GlobalSet, cbd_global_set, 0x24, "global.set"),
+    (Unreachable, cbd_unreachable, 0x00, "unreachable"),
+    (Nop, cbd_nop, 0x01, "nop"),
+    (Drop, cbd_drop, 0x1A, "drop"),
+    (Select, cbd_select(false), 0x1B, "select"),
+    (SelectTyped, cbd_select(true), 0x1C, "select"),
+    (Block, cbd_block, 0x02, "block"),
+    (Loop, cbd_loop, 0x03, "loop"),
+    (If, cbd_if, 0x04, "if"),
+    (Else, cbd_else, 0x05, "else"),
+    (End, cbd_end, 0x0B, "end"),
+    (Br, cbd_br, 0x0C, "br"),
+    (BrIf, cbd_br_if, 0x0D, "br_if"),
+    (BrTable, cbd_br_table, 0x0E, "br_table"),
+    (Return, cbd_return, 0x0F, "return"),
+    (Call, cbd_call, 0x10, "call"),
+    (CallIndirect, cbd_call_indirect, 0x11, "call_indirect"),
+    (I32Load, cbd_i32_load(I32LoadOp::Load32), 0x28, "i32.load"),
+    (I32Load8S, cbd_i32_load(I32LoadOp::Load8S), 0x2C, "i32.load8_s"),
+    (I32Load8U, cbd_i32_load(I32LoadOp::Load8U), 0x2D, "i32.load8_u"),
+    (I32Load16S, cbd_i32_load(I32LoadOp::Load16S), 0x2E, "i32.load16_s"),
+    (I32Load16U, cbd_i32_load(I32LoadOp::Load16U), 0x2F, "i32.load16_u"),
+    (I32Store, cbd_i32_store(I32StoreOp::Store32), 0x36, "i32.store"),
+    (I32Store8, cbd_i32_store(I32StoreOp::Store8), 0x3A, "i32.store8"),
+    (I32Store16, cbd_i32_store(I32StoreOp::Store16), 0x3B, "i32.store16"),
+    (MemorySize, cbd_memory_size, 0x3F, "memory.size"),
+    (MemoryGrow, cbd_memory_grow, 0x40, "memory.grow"),
+    (MemoryInit, cbd_memory_init, 0xFC 8, "memory.init"),
+    (DataDrop, cbd_data_drop, 0xFC 9, "data.drop"),
+    (MemoryCopy, cbd_memory_copy, 0xFC 10, "memory.copy"),
+    (MemoryFill, cbd_memory_fill, 0xFC 11, "memory.fill"),
+    (RefNull, cbd_ref_null, 0xD0, "ref.null"),
+    (RefIsNull, cbd_ref_is_null, 0xD1, "ref.is_null"),
+    (RefFunc, cbd_ref_func, 0xD2, "ref.func"),
+    (TableGet, cbd_table_get, 0x25, "table.get"),
+    (TableSet, cbd_table_set, 0x26, "table.set"),
+    (TableInit, cbd_table_init, 0xFC 12, "table.init"),
+    (ElemDrop, cbd_elem_drop, 0xFC 13, "elem.drop"),
+    (TableCopy, cbd_table_copy, 0xFC 14, "table.copy"),
+    (TableGrow, cbd_table_grow, 0xFC 15, "table.grow"),
+    (TableSize, cbd_table_size, 0xFC 16, "table.size"),
+    (TableFill, cbd_table_fill, 0xFC 17, "table.fill"),
+    (I32Eqz, cbd_i32_unop(I32Unop::Eqz), 0x45, "i32.eqz"),
+    (I32Eq, cbd_i32_relop(I32Relop::Eq), 0x46, "i32.eq"),
+    (I32Ne, cbd_i32_relop(I32Relop::Ne), 0x47, "i32.ne"),
+    (I32LtS, cbd_i32_relop(I32Relop::LtS), 0x48, "i32.lt_s"),
+    (I32LtU, cbd_i32_relop(I32Relop::LtU), 0x49, "i32.lt_u"),
+    (I32GtS, cbd_i32_relop(I32Relop::GtS), 0x4A, "i32.gt_s"),
+    (I32GtU, cbd_i32_relop(I32Relop::GtU), 0x4B, "i32.gt_u"),
+    (I32LeS, cbd_i32_relop(I32Relop::LeS), 0x4C, "i32.le_s"),
+    (I32LeU, cbd_i32_relop(I32Relop::LeU), 0x4D, "i32.le_u"),
+    (I32GeS, cbd_i32_relop(I32Relop::GeS), 0x4E, "i32.ge_s"),
+    (I32GeU, cbd_i32_relop(I32Relop::GeU), 0x4F, "i32.ge_u"),
+    (I32Clz, cbd_i32_unop(I32Unop::Clz), 0x67, "i32.clz"),
+    (I32Ctz, cbd_i32_unop(I32Unop::Ctz), 0x68, "i32.ctz"),
+    (I32Popcnt, cbd_i32_unop(I32Unop::Popcnt), 0x69, "i32.popcnt"),
+    (I32Sub, cbd_i32_binop(I32Binop::Sub), 0x6B, "i32.sub"),
+    (I32Mul, cbd_i32_binop(I32Binop::Mul), 0x6C, "i32.mul"),
+    (I32DivS, cbd_i32_binop(I32Binop::DivS), 0x6D, "i32.div_s"),
+    (I32DivU, cbd_i32_binop(I32Binop::DivU), 0x6E, "i32.div_u"),
+    (I32RemS, cbd_i32_binop(I32Binop::RemS), 0x6F, "i32.rem_s"),
+    (I32RemU, cbd_i32_binop(I32Binop::RemU), 0x70, "i32.rem_u"),
+    (I32And, cbd_i32_binop(I32Binop::And), 0x71, "i32.and"),
+    (I32Or, cbd_i32_binop(I32Binop::Or), 0x72, "i32.or"),
+    (I32Xor, cbd_i32_binop(I32Binop::Xor), 0x73, "i32.xor"),
+    (I32Shl, cbd_i32_binop(I32Binop::Shl), 0x74, "i32.shl"),
+    (I32ShrS, cbd_i32_binop(I32Binop::ShrS), 0x75, "i32.shr_s"),
+    (I32ShrU, cbd_i32_binop(I32Binop::ShrU), 0x76, "i32.shr_u"),
+    (I32Rotl, cbd_i32_binop(I32Binop::Rotl), 0x77, "i32.rotl"),
+    (I32Rotr, cbd_i32_binop(I32Binop::Rotr), 0x78, "i32.rotr"),
+    (I64Const, cbd_i64_const, 0x42, "i64.const"),
+    (I64Eqz, cbd_i64_eqz, 0x50, "i64.eqz"),
+    (I64Eq, cbd_i64_relop(I64Relop::Eq), 0x51, "i64.eq"),
+    (I64Ne, cbd_i64_relop(I64Relop::Ne), 0x52, "i64.ne"),
+    (I64LtS, cbd_i64_relop(I64Relop::LtS), 0x53, "i64.lt_s"),
+    (I64LtU, cbd_i64_relop(I64Relop::LtU), 0x54, "i64.lt_u"),
+    (I64GtS, cbd_i64_relop(I64Relop::GtS), 0x55, "i64.gt_s"),
+    (I64GtU, cbd_i64_relop(I64Relop::GtU), 0x56, "i64.gt_u"),
+    (I64LeS, cbd_i64_relop(I64Relop::LeS), 0x57, "i64.le_s"),
+    (I64LeU, cbd_i64_relop(I64Relop::LeU), 0x58, "i64.le_u"),
+    (I64GeS, cbd_i64_relop(I64Relop::GeS), 0x59, "i64.ge_s"),
+    (I64GeU, cbd_i64_relop(I64Relop::GeU), 0x5A, "i64.ge_u"),
+    (I64Clz, cbd_i64_unop(I64Unop::Clz), 0x79, "i64.clz"),
+    (I64Ctz, cbd_i64_unop(I64Unop::Ctz), 0x7A, "i64.ctz"),
+    (I64Popcnt, cbd_i64_unop(I64Unop::Popcnt), 0x7B, "i64.popcnt"),
+    (I32Extend8S, cbd_i32_unop(I32Unop::Extend8S), 0xC0, "i32.extend8_s"),
+    (I32Extend16S, cbd_i32_unop(I32Unop::Extend16S), 0xC1, "i32.extend16_s"),
+    (I64Extend8S, cbd_i64_unop(I64Unop::Extend8S), 0xC2, "i64.extend8_s"),
+    (I64Extend16S, cbd_i64_unop(I64Unop::Extend16S), 0xC3, "i64.extend16_s"),
+    (I64Extend32S, cbd_i64_unop(I64Unop::Extend32S), 0xC4, "i64.extend32_s"),
+    (I64Add, cbd_i64_add, 0x7C, "i64.add"),
+    (I64Sub, cbd_i64_binop(I64Binop::Sub), 0x7D, "i64.sub"),
+    (I64Mul, cbd_i64_binop(I64Binop::Mul), 0x7E, "i64.mul"),
+    (I64DivS, cbd_i64_binop(I64Binop::DivS), 0x7F, "i64.div_s"),
+    (I64DivU, cbd_i64_binop(I64Binop::DivU), 0x80, "i64.div_u"),
+    (I64RemS, cbd_i64_binop(I64Binop::RemS), 0x81, "i64.rem_s"),
+    (I64RemU, cbd_i64_binop(I64Binop::RemU), 0x82, "i64.rem_u"),
+    (I64And, cbd_i64_binop(I64Binop::And), 0x83, "i64.and"),
+    (I64Or, cbd_i64_binop(I64Binop::Or), 0x84, "i64.or"),
+    (I64Xor, cbd_i64_binop(I64Binop::Xor), 0x85, "i64.xor"),
+    (I64Shl, cbd_i64_binop(I64Binop::Shl), 0x86, "i64.shl"),
+    (I64ShrS, cbd_i64_binop(I64Binop::ShrS), 0x87, "i64.shr_s"),
+    (I64ShrU, cbd_i64_binop(I64Binop::ShrU), 0x88, "i64.shr_u"),
+    (I64Rotl, cbd_i64_binop(I64Binop::Rotl), 0x89, "i64.rotl"),
+    (I64Rotr, cbd_i64_binop(I64Binop::Rotr), 0x8A, "i64.rotr"),
+    (F32Const, cbd_f32_const, 0x43, "f32.const"),
+    (F64Const, cbd_f64_const, 0x44, "f64.const"),
+    (F32Eq, cbd_f32_relop(FRelop::Eq), 0x5B, "f32.eq"),
+    (F32Ne, cbd_f32_relop(FRelop::Ne), 0x5C, "f32.ne"),
+    (F32Lt, cbd_f32_relop(FRelop::Lt), 0x5D, "f32.lt"),
+    (F32Gt, cbd_f32_relop(FRelop::Gt), 0x5E, "f32.gt"),
+    (F32Le, cbd_f32_relop(FRelop::Le), 0x5F, "f32.le"),
+    (F32Ge, cbd_f32_relop(FRelop::Ge), 0x60, "f32.ge"),
+    (F64Eq, cbd_f64_relop(FRelop::Eq), 0x61, "f64.eq"),
+    (F64Ne, cbd_f64_relop(FRelop::Ne), 0x62, "f64.ne"),
+    (F64Lt, cbd_f64_relop(FRelop::Lt), 0x63, "f64.lt"),
+    (F64Gt, cbd_f64_relop(FRelop::Gt), 0x64, "f64.gt"),
+    (F64Le, cbd_f64_relop(FRelop::Le), 0x65, "f64.le"),
+    (F64Ge, cbd_f64_relop(FRelop::Ge), 0x66, "f64.ge"),
+    (F32Abs, cbd_f32_unop(FUnop::Abs), 0x8B, "f32.abs"),
+    (F32Neg, cbd_f32_unop(FUnop::Neg), 0x8C, "f32.neg"),
+    (F32Ceil, cbd_f32_unop(FUnop::Ceil), 0x8D, "f32.ceil"),
+    (F32Floor, cbd_f32_unop(FUnop::Floor), 0x8E, "f32.floor"),
+    (F32Trunc, cbd_f32_unop(FUnop::Trunc), 0x8F, "f32.trunc"),
+    (F32Nearest, cbd_f32_unop(FUnop::Nearest), 0x90, "f32.nearest"),
+    (F32Sqrt, cbd_f32_unop(FUnop::Sqrt), 0x91, "f32.sqrt"),
+    (F32Add, cbd_f32_binop(FBinop::Add), 0x92, "f32.add"),
+    (F32Sub, cbd_f32_binop(FBinop::Sub), 0x93, "f32.sub"),
+    (F32Mul, cbd_f32_binop(FBinop::Mul), 0x94, "f32.mul"),
+    (F32Div, cbd_f32_binop(FBinop::Div), 0x95, "f32.div"),
+    (F32Min, cbd_f32_binop(FBinop::Min), 0x96, "f32.min"),
+    (F32Max, cbd_f32_binop(FBinop::Max), 0x97, "f32.max"),
+    (F32Copysign, cbd_f32_binop(FBinop::Copysign), 0x98, "f32.copysign"),
+    (F64Abs, cbd_f64_unop(FUnop::Abs), 0x99, "f64.abs"),
+    (F64Neg, cbd_f64_unop(FUnop::Neg), 0x9A, "f64.neg"),
+    (F64Ceil, cbd_f64_unop(FUnop::Ceil), 0x9B, "f64.ceil"),
+    (F64Floor, cbd_f64_unop(FUnop::Floor), 0x9C, "f64.floor"),
+    (F64Trunc, cbd_f64_unop(FUnop::Trunc), 0x9D, "f64.trunc"),
+    (F64Nearest, cbd_f64_unop(FUnop::Nearest), 0x9E, "f64.nearest"),
+    (F64Sqrt, cbd_f64_unop(FUnop::Sqrt), 0x9F, "f64.sqrt"),
+    (F64Add, cbd_f64_binop(FBinop::Add), 0xA0, "f64.add"),
+    (F64Sub, cbd_f64_binop(FBinop::Sub), 0xA1, "f64.sub"),
+    (F64Mul, cbd_f64_binop(FBinop::Mul), 0xA2, "f64.mul"),
+    (F64Div, cbd_f64_binop(FBinop::Div), 0xA3, "f64.div"),
+    (F64Min, cbd_f64_binop(FBinop::Min), 0xA4, "f64.min"),
+    (F64Max, cbd_f64_binop(FBinop::Max), 0xA5, "f64.max"),
+    (F64Copysign, cbd_f64_binop(FBinop::Copysign), 0xA6, "f64.copysign"),
+    (I32WrapI64, cbd_cvtop(Cvtop::I32WrapI64), 0xA7, "i32.wrap_i64"),
+    (I32TruncF32S, cbd_cvtop(Cvtop::I32TruncF32S), 0xA8, "i32.trunc_f32_s"),
+    (I32TruncF32U, cbd_cvtop(Cvtop::I32TruncF32U), 0xA9, "i32.trunc_f32_u"),
+    (I32TruncF64S, cbd_cvtop(Cvtop::I32TruncF64S), 0xAA, "i32.trunc_f64_s"),
+    (I32TruncF64U, cbd_cvtop(Cvtop::I32TruncF64U), 0xAB, "i32.trunc_f64_u"),
+    (I64ExtendI32S, cbd_cvtop(Cvtop::I64ExtendI32S), 0xAC, "i64.extend_i32_s"),
+    (I64ExtendI32U, cbd_cvtop(Cvtop::I64ExtendI32U), 0xAD, "i64.extend_i32_u"),
+    (I64TruncF32S, cbd_cvtop(Cvtop::I64TruncF32S), 0xAE, "i64.trunc_f32_s"),
+    (I64TruncF32U, cbd_cvtop(Cvtop::I64TruncF32U), 0xAF, "i64.trunc_f32_u"),
+    (I64TruncF64S, cbd_cvtop(Cvtop::I64TruncF64S), 0xB0, "i64.trunc_f64_s"),
+    (I64TruncF64U, cbd_cvtop(Cvtop::I64TruncF64U), 0xB1, "i64.trunc_f64_u"),
+    (F32ConvertI32S, cbd_cvtop(Cvtop::F32ConvertI32S), 0xB2, "f32.convert_i32_s"),
+    (F32ConvertI32U, cbd_cvtop(Cvtop::F32ConvertI32U), 0xB3, "f32.convert_i32_u"),
+    (F32ConvertI64S, cbd_cvtop(Cvtop::F32ConvertI64S), 0xB4, "f32.convert_i64_s"),
+    (F32ConvertI64U, cbd_cvtop(Cvtop::F32ConvertI64U), 0xB5, "f32.convert_i64_u"),
+    (F32DemoteF64, cbd_cvtop(Cvtop::F32DemoteF64), 0xB6, "f32.demote_f64"),
+    (F64ConvertI32S, cbd_cvtop(Cvtop::F64ConvertI32S), 0xB7, "f64.convert_i32_s"),
+    (F64ConvertI32U, cbd_cvtop(Cvtop::F64ConvertI32U), 0xB8, "f64.convert_i32_u"),
+    (F64ConvertI64S, cbd_cvtop(Cvtop::F64ConvertI64S), 0xB9, "f64.convert_i64_s"),
+    (F64ConvertI64U, cbd_cvtop(Cvtop::F64ConvertI64U), 0xBA, "f64.convert_i64_u"),
+    (F64PromoteF32, cbd_cvtop(Cvtop::F64PromoteF32), 0xBB, "f64.promote_f32"),
+    (I32ReinterpretF32, cbd_cvtop(Cvtop::I32ReinterpretF32), 0xBC, "i32.reinterpret_f32"),
+    (I64ReinterpretF64, cbd_cvtop(Cvtop::I64ReinterpretF64), 0xBD, "i64.reinterpret_f64"),
+    (F32ReinterpretI32, cbd_cvtop(Cvtop::F32ReinterpretI32), 0xBE, "f32.reinterpret_i32"),
+    (F64ReinterpretI64, cbd_cvtop(Cvtop::F64ReinterpretI64), 0xBF, "f64.reinterpret_i64"),
+    (I32TruncSatF32S, cbd_cvtop(Cvtop::I32TruncSatF32S), 0xFC 0, "i32.trunc_sat_f32_s"),
+    (I32TruncSatF32U, cbd_cvtop(Cvtop::I32TruncSatF32U), 0xFC 1, "i32.trunc_sat_f32_u"),
+    (I32TruncSatF64S, cbd_cvtop(Cvtop::I32TruncSatF64S), 0xFC 2, "i32.trunc_sat_f64_s"),
+    (I32TruncSatF64U, cbd_cvtop(Cvtop::I32TruncSatF64U), 0xFC 3, "i32.trunc_sat_f64_u"),
+    (I64TruncSatF32S, cbd_cvtop(Cvtop::I64TruncSatF32S), 0xFC 4, "i64.trunc_sat_f32_s"),
+    (I64TruncSatF32U, cbd_cvtop(Cvtop::I64TruncSatF32U), 0xFC 5, "i64.trunc_sat_f32_u"),
+    (I64TruncSatF64S, cbd_cvtop(Cvtop::I64TruncSatF64S), 0xFC 6, "i64.trunc_sat_f64_s"),
+    (I64TruncSatF64U, cbd_cvtop(Cvtop::I64TruncSatF64U), 0xFC 7, "i64.trunc_sat_f64_u")
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum CodeEntry {
     Op(Opcode),
     I32Imm(i32),
-    BlockType(usize),
+    I64Imm(i64),
+    F32Imm(u32), // bits, so NaN payloads survive and entries stay Eq
+    F64Imm(u64),
+    BlockType(BlockSig),
+    Labels(Vec<usize>), // br_table's targets, the default last
+    MemArg(MemArg),
+    ValType(Type), // the typed select's, or ref.null's heap type
+}
+
+// as in the binary format: no values, a single result, or an index into the
+// module's types for anything with params or several results
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockSig {
+    Empty,
+    Value(Type),
+    Index(usize),
 }
+
+impl BlockSig {
+    // None if the type index is out of range
+    pub fn params_results(self, types: &[FuncType]) -> Option<(Vec<Type>, Vec<Type>)> {
+        match self {
+            BlockSig::Empty => Some((vec![], vec![])),
+            BlockSig::Value(t) => Some((vec![], vec![t])),
+            BlockSig::Index(idx) => types.get(idx).map(|ty| (ty.params.clone(), ty.results.clone())),
+        }
+    }
+}
+
 pub struct CodePtr {
     pub code: Vec<CodeEntry>,
     pub ip: usize,
@@ -179,9 +775,9 @@ impl CodePtr {
         }
     }
 
-    pub fn read_block_type(&mut self) -> usize {
+    pub fn read_block_type(&mut self) -> BlockSig {
         match self.next() {
-            Some(CodeEntry::BlockType(i)) => *i,
+            Some(CodeEntry::BlockType(ty)) => *ty,
             _ => panic!("not a block type"),
         }
     }
@@ -191,221 +787,740 @@ impl CodePtr {
             _ => panic!("not an i32 imm"),
         }
     }
+    pub fn read_imm_i64(&mut self) -> i64 {
+        match self.next() {
+            Some(CodeEntry::I64Imm(i)) => *i,
+            _ => panic!("not an i64 imm"),
+        }
+    }
+    pub fn read_imm_f32(&mut self) -> f32 {
+        match self.next() {
+            Some(CodeEntry::F32Imm(bits)) => f32::from_bits(*bits),
+            _ => panic!("not an f32 imm"),
+        }
+    }
+    pub fn read_imm_f64(&mut self) -> f64 {
+        match self.next() {
+            Some(CodeEntry::F64Imm(bits)) => f64::from_bits(*bits),
+            _ => panic!("not an f64 imm"),
+        }
+    }
+    pub fn read_val_type(&mut self) -> Type {
+        match self.next() {
+            Some(CodeEntry::ValType(t)) => *t,
+            _ => panic!("not a value type"),
+        }
+    }
+    pub fn read_labels(&mut self) -> Vec<usize> {
+        match self.next() {
+            Some(CodeEntry::Labels(labels)) => labels.clone(),
+            _ => panic!("not a label vector"),
+        }
+    }
+    pub fn read_memarg(&mut self) -> MemArg {
+        match self.next() {
+            Some(CodeEntry::MemArg(memarg)) => *memarg,
+            _ => panic!("not a memarg"),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct STEntry {
     pub ip_delta: isize,
     pub stp_delta: isize,
+    pub val_count: usize, // values carried to the target
+    pub pop_count: usize, // values dropped from under them
+}
+
+impl STEntry {
+    pub fn unwind<T>(&self, stack: &mut Vec<T>) {
+        unwind(stack, self.val_count, self.pop_count);
+    }
+}
+
+// keeps the top val_count values, dropping the pop_count under them
+pub fn unwind<T>(stack: &mut Vec<T>, val_count: usize, pop_count: usize) {
+    let top = stack.len().saturating_sub(val_count);
+    stack.drain(top.saturating_sub(pop_count)..top);
+}
+
+// one per active call, the last being the running function, holding what
+// to restore once it returns
+#[derive(Debug, Copy, Clone)]
+pub struct Frame {
+    pub func: usize,
+    pub locals_base: usize,
+    pub ret_ip: usize,
+    pub ret_stp: usize,
+}
+
+// where a function's code and sidetable start once laid out with the rest of
+// the module's, see module::Linked
+#[derive(Debug, Copy, Clone)]
+pub struct FuncEntry {
+    pub ip: usize,
+    pub stp: usize,
+    pub end_ip: usize, // just past the function's End
+    pub params: usize,
+    pub locals: usize, // declared locals, not including params
+    pub sig: usize, // canonical type id, see module::Linked::sigs
+    pub host: Option<usize>, // index into the hosts for a host function, which has no code
+}
+
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+// the function call_indirect calls, checked against the canonical id of the
+// expected type
+pub fn resolve_indirect(table: &[Option<usize>], funcs: &[FuncEntry], sig: usize, idx: i32) -> Result<usize, TrapKind> {
+    let entry = table.get(idx as u32 as usize).ok_or(TrapKind::UndefinedElement)?;
+    let func_idx = entry.ok_or(TrapKind::UninitializedElement)?;
+    if funcs[func_idx].sig != sig {
+        return Err(TrapKind::IndirectCallTypeMismatch);
+    }
+    Ok(func_idx)
+}
+
+// which of br_table's n targets to take, anything out of range (including
+// negative indices, which are large unsigned) takes the default
+pub fn table_index(idx: i32, n: usize) -> usize {
+    usize::min(idx as u32 as usize, n - 1)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrapKind {
+    IntDivByZero,
+    IntOverflow,
+    StackUnderflow,
+    LocalOutOfRange,
+    FuncOutOfRange,
+    CallStackExhausted,
+    UndefinedElement,
+    UninitializedElement,
+    IndirectCallTypeMismatch,
+    MemoryOutOfBounds,
+    GlobalOutOfRange,
+    InvalidConversion, // float to int of a NaN
+    Unreachable,
+    TableOutOfBounds,
+    Host(u32), // raised by a host function, the code is its own
+    HostResultArity, // a host function returned the wrong number of results
+    Unsupported, // an op the evaluator can't run, e.g. a call in a lone WASMFun
+}
+
+impl std::fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let TrapKind::Host(code) = self {
+            return write!(f, "host trap {code}");
+        }
+        f.write_str(match self {
+            TrapKind::IntDivByZero => "integer divide by zero",
+            TrapKind::IntOverflow => "integer overflow",
+            TrapKind::StackUnderflow => "stack underflow",
+            TrapKind::LocalOutOfRange => "local index out of range",
+            TrapKind::FuncOutOfRange => "function index out of range",
+            TrapKind::CallStackExhausted => "call stack exhausted",
+            TrapKind::UndefinedElement => "undefined element",
+            TrapKind::UninitializedElement => "uninitialized element",
+            TrapKind::IndirectCallTypeMismatch => "indirect call type mismatch",
+            TrapKind::MemoryOutOfBounds => "out of bounds memory access",
+            TrapKind::GlobalOutOfRange => "global index out of range",
+            TrapKind::InvalidConversion => "invalid conversion to integer",
+            TrapKind::Unreachable => "unreachable executed",
+            TrapKind::TableOutOfBounds => "out of bounds table access",
+            TrapKind::HostResultArity => "host function returned the wrong number of results",
+            TrapKind::Unsupported => "unsupported operation",
+            TrapKind::Host(_) => unreachable!(),
+        })
+    }
+}
+
+// Evaluators don't unwind on a fault: the hook that faults records a
+// TrapKind and returns a dummy value, and the dispatch loop stops after the
+// current op and reports it with the op's ip.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub ip: usize,
+}
+
+impl std::fmt::Display for Trap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "trap at ip {}: {}", self.ip, self.kind)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    TypeMismatch { expected: Type, found: Type },
+    StackUnderflow,
+    UnknownLabel(usize),
+    UnknownLocal(i32),
+    UnbalancedEnd,
+    BlockArity { expected: usize, found: usize },
+    UnknownType(usize),
+    ElseWithoutIf,
+    BrTableArity { expected: usize, found: usize },
+    UnknownFunc(usize),
+    UnknownTable(usize),
+    UnknownMemory(usize),
+    AlignmentTooLarge { align: u32, natural: u32 }, // both log2
+    UnknownGlobal(usize),
+    ImmutableGlobal(usize),
+    ConstExprRequired,
+    UnknownElem(usize),
+    UnknownData(usize),
+    UndeclaredFuncRef(usize), // ref.func of a function no segment or global mentions
+    ExpectedRef(Type),
+    ExpectedNum(Type), // untyped select only takes numbers
+}
+
+impl std::fmt::Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use ValidationErrorKind::*;
+        match self {
+            TypeMismatch { expected, found } => write!(f, "type mismatch: expected {expected:?}, found {found:?}"),
+            StackUnderflow => write!(f, "operand stack underflow"),
+            UnknownLabel(label) => write!(f, "unknown label {label}"),
+            UnknownLocal(idx) => write!(f, "unknown local {idx}"),
+            UnbalancedEnd => write!(f, "unbalanced end"),
+            BlockArity { expected, found } => write!(f, "block leaves {found} values, expected {expected}"),
+            UnknownType(idx) => write!(f, "unknown type {idx}"),
+            ElseWithoutIf => write!(f, "else without matching if"),
+            BrTableArity { expected, found } => write!(f, "br_table target takes {found} values, default takes {expected}"),
+            UnknownFunc(idx) => write!(f, "unknown function {idx}"),
+            UnknownTable(idx) => write!(f, "unknown table {idx}"),
+            UnknownMemory(idx) => write!(f, "unknown memory {idx}"),
+            AlignmentTooLarge { align, natural } => write!(f, "alignment 2^{align} larger than natural 2^{natural}"),
+            UnknownGlobal(idx) => write!(f, "unknown global {idx}"),
+            ImmutableGlobal(idx) => write!(f, "global {idx} is immutable"),
+            ConstExprRequired => write!(f, "constant expression required"),
+            UnknownElem(idx) => write!(f, "unknown element segment {idx}"),
+            UnknownData(idx) => write!(f, "unknown data segment {idx}"),
+            UndeclaredFuncRef(idx) => write!(f, "undeclared function reference {idx}"),
+            ExpectedRef(found) => write!(f, "type mismatch: expected a reference, found {found:?}"),
+            ExpectedNum(found) => write!(f, "type mismatch: expected a number, found {found:?}"),
+        }
+    }
+}
+
+// Like traps, validator hooks record the first error and carry on with a
+// dummy type; dispatch stops after the op. depth is the number of open
+// control frames, counting the function body.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub kind: ValidationErrorKind,
+    pub ip: usize,
+    pub depth: usize,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid at ip {} (depth {}): {}", self.ip, self.depth, self.kind)
+    }
 }
 
 pub struct Eval {
-    pub stack: Vec<i32>,
-    pub locals: Vec<i32>,
+    pub stack: Vec<Slot>,
+    pub locals: Vec<Slot>,
     pub codeptr: CodePtr,
     pub sidetable: Vec<STEntry>,
     pub stp: usize,
+    pub trap: Option<TrapKind>,
+    pub frames: Vec<Frame>,
+    pub funcs: Vec<FuncEntry>, // the module's function index space, see module::Linked
+    pub hosts: Vec<HostFunc>, // see FuncEntry::host
+    pub tables: Vec<RefTable>,
+    pub elems: Vec<Vec<Option<usize>>>, // each segment's references, empty once dropped
+    pub sigs: Vec<usize>, // canonical id of each type index
+    pub memory: LinearMemory,
+    pub datas: Vec<Vec<u8>>, // each segment's bytes, empty once dropped
+    pub globals: Vec<Slot>,
 }
 
 impl Eval {
+    fn set_trap(&mut self, kind: TrapKind) {
+        self.trap.get_or_insert(kind);
+    }
+
+    // the params and locals of every active call are laid end to end
+    fn local_slot(&self, idx: i32) -> usize {
+        self.frames.last().map_or(0, |f| f.locals_base) + idx as u32 as usize
+    }
+
+    fn call(&mut self, func_idx: usize) {
+        let Some(&f) = self.funcs.get(func_idx) else {
+            self.set_trap(TrapKind::FuncOutOfRange);
+            return;
+        };
+        if self.frames.len() == MAX_CALL_DEPTH {
+            self.set_trap(TrapKind::CallStackExhausted);
+            return;
+        }
+        // a host function returns before the next op, no frame needed
+        if let Some(host) = f.host {
+            if let Err(kind) = call_host(&self.hosts[host], &mut self.memory, &mut self.stack) {
+                self.set_trap(kind);
+            }
+            return;
+        }
+        let Some(args) = self.stack.len().checked_sub(f.params) else {
+            self.set_trap(TrapKind::StackUnderflow);
+            return;
+        };
+        // the args become the callee's first locals
+        let locals_base = self.locals.len();
+        self.locals.extend(self.stack.drain(args..));
+        self.locals.resize(locals_base + f.params + f.locals, Slot::default());
+        self.frames.push(Frame { func: func_idx, locals_base, ret_ip: self.codeptr.ip, ret_stp: self.stp });
+        self.codeptr.ip = f.ip;
+        self.stp = f.stp;
+    }
+
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, idx: i32) {
+        match resolve_indirect(&self.tables[table_idx].elems, &self.funcs, self.sigs[type_idx], idx) {
+            Ok(func_idx) => self.call(func_idx),
+            Err(kind) => self.set_trap(kind),
+        }
+    }
+
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, addr: i32) -> i32 {
+        self.memory.load(op, memarg, addr).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
+
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, addr: i32, val: i32) {
+        if let Err(kind) = self.memory.store(op, memarg, addr, val) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn memory_size(&mut self) -> i32 {
+        self.memory.pages() as i32
+    }
+
+    fn memory_grow(&mut self, delta: i32) -> i32 {
+        self.memory.grow(delta as u32)
+    }
+
+    fn memory_init(&mut self, data_idx: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.memory.init(dst, &self.datas[data_idx], src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn data_drop(&mut self, data_idx: usize) {
+        self.datas[data_idx] = vec![];
+    }
+
+    fn memory_copy(&mut self, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.memory.copy(dst, src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn memory_fill(&mut self, dst: i32, val: i32, len: i32) {
+        if let Err(kind) = self.memory.fill(dst, val, len) {
+            self.set_trap(kind);
+        }
+    }
+
     fn popi(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+        self.pop().i32()
     }
 
     fn pushi_imm(&mut self, x: i32) {
         self.pushi(x)
     }
     fn pushi(&mut self, x: i32) {
-        self.stack.push(x)
+        self.stack.push(x.into())
     }
 
-    fn push(&mut self, x: i32) {
+    fn popl(&mut self) -> i64 {
+        self.pop().i64()
+    }
+
+    fn pushl_imm(&mut self, x: i64) {
+        self.pushl(x)
+    }
+    fn pushl(&mut self, x: i64) {
+        self.stack.push(x.into())
+    }
+
+    fn popf(&mut self) -> f32 {
+        self.pop().f32()
+    }
+
+    fn pushf_imm(&mut self, x: f32) {
+        self.pushf(x)
+    }
+    fn pushf(&mut self, x: f32) {
+        self.stack.push(x.into())
+    }
+
+    fn popd(&mut self) -> f64 {
+        self.pop().f64()
+    }
+
+    fn pushd_imm(&mut self, x: f64) {
+        self.pushd(x)
+    }
+    fn pushd(&mut self, x: f64) {
+        self.stack.push(x.into())
+    }
+
+    fn push(&mut self, x: Slot) {
         self.stack.push(x)
     }
-    fn pop(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+    fn pop(&mut self) -> Slot {
+        self.stack.pop().unwrap_or_else(|| {
+            self.set_trap(TrapKind::StackUnderflow);
+            Slot::default()
+        })
+    }
+
+    fn set_local(&mut self, idx: i32, val: Slot) {
+        let slot = self.local_slot(idx);
+        match self.locals.get_mut(slot) {
+            Some(local) => *local = val,
+            None => self.set_trap(TrapKind::LocalOutOfRange),
+        }
+    }
+
+    fn get_local(&mut self, idx: i32) -> Slot {
+        match self.locals.get(self.local_slot(idx)) {
+            Some(&local) => local,
+            None => {
+                self.set_trap(TrapKind::LocalOutOfRange);
+                Slot::default()
+            }
+        }
     }
 
-    fn set_local(&mut self, idx: i32, val: i32) {
-        self.locals[idx as usize] = val;
+    fn set_global(&mut self, idx: i32, val: Slot) {
+        match self.globals.get_mut(idx as u32 as usize) {
+            Some(global) => *global = val,
+            None => self.set_trap(TrapKind::GlobalOutOfRange),
+        }
     }
 
-    fn get_local(&mut self, idx: i32) -> i32 {
-        self.locals[idx as usize]
+    fn get_global(&mut self, idx: i32) -> Slot {
+        match self.globals.get(idx as u32 as usize) {
+            Some(&global) => global,
+            None => {
+                self.set_trap(TrapKind::GlobalOutOfRange);
+                Slot::default()
+            }
+        }
     }
 
-    fn start_block(&mut self, _ty_index: usize) { }
-    fn start_loop(&mut self, _ty_index: usize) { }
-    fn end(&mut self) { }
+    fn start_block(&mut self, _ty: BlockSig) { }
+    fn start_loop(&mut self, _ty: BlockSig) { }
+    fn start_if(&mut self, _ty: BlockSig) { }
+    fn start_else(&mut self) { }
+    // only a function's own End returns, its results are already on top
+    fn end(&mut self) {
+        let Some(&frame) = self.frames.last() else { return };
+        if self.codeptr.ip == self.funcs[frame.func].end_ip {
+            self.frames.pop();
+            self.locals.truncate(frame.locals_base);
+            self.codeptr.ip = frame.ret_ip;
+            self.stp = frame.ret_stp;
+        }
+    }
 
     fn addi32(x: i32, y: i32) -> i32 {
-        x + y
+        x.wrapping_add(y)
     }
 
     fn i32_eqz(x: i32) -> bool {
         x == 0
     }
 
-    fn branch(&mut self, _label_idx: usize) {
-        self.stp += 1;
-        let ste = self.sidetable[self.stp];
-        // stupid casts
-        self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
-        self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
+    fn i32_binop(&mut self, op: I32Binop, x: i32, y: i32) -> i32 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
     }
 
-    fn fallthru(&mut self) {
-        self.stp += 1; // we know that only one of branch or fallthru will run in this case,
-                       // but seems iffy
+    fn i32_unop(&mut self, op: I32Unop, x: i32) -> i32 {
+        op.eval(x)
     }
 
-    cbd!();
-}
+    fn i32_relop(&mut self, op: I32Relop, x: i32, y: i32) -> i32 {
+        op.eval(x, y)
+    }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub enum Type {
-    I32,
-}
+    fn i64_add(&mut self, x: i64, y: i64) -> i64 {
+        x.wrapping_add(y)
+    }
 
-#[derive(Debug, PartialEq, Eq)]
-enum CtlType {
-    Func,
-    Block,
-    Loop,
-}
+    fn i64_binop(&mut self, op: I64Binop, x: i64, y: i64) -> i64 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct CtlEntry {
-    tipe: CtlType,
-    cont_ip: usize,
-    cont_stp: usize, // essentially idx of first branch
-}
+    fn i64_unop(&mut self, op: I64Unop, x: i64) -> i64 {
+        op.eval(x)
+    }
 
-pub struct SidetableMeta {
-    br_ip: usize,
-    target_ctl_idx: usize,
-}
+    fn i64_relop(&mut self, op: I64Relop, x: i64, y: i64) -> i32 {
+        op.eval(x, y)
+    }
 
-struct Validate {
-    pub stack: Vec<Type>,
-    pub locals: Vec<Type>,
-    pub ctl_entries: Vec<CtlEntry>,
-    pub ctl_stack: Vec<usize>,
-    pub codeptr: CodePtr,
-    pub sidetable_meta: Vec<SidetableMeta>, // idx = br_index
-}
+    fn i64_eqz(&mut self, x: i64) -> i32 {
+        (x == 0) as i32
+    }
 
-impl Validate {
-    fn popi(&mut self) -> Type {
-        assert!(self.stack.pop().is_some_and(|t| t == Type::I32));
-        Type::I32
+    fn f32_binop(&mut self, op: FBinop, x: f32, y: f32) -> f32 {
+        op.eval(x, y)
     }
 
-    fn pushi_imm(&mut self, _: i32) {
-        self.stack.push(Type::I32)
+    fn f32_unop(&mut self, op: FUnop, x: f32) -> f32 {
+        op.eval(x)
     }
 
-    fn pushi(&mut self, t: Type) {
-        assert!(t == Type::I32);
-        self.stack.push(Type::I32)
+    fn f32_relop(&mut self, op: FRelop, x: f32, y: f32) -> i32 {
+        op.eval(x, y)
     }
 
-    fn push(&mut self, t: Type) {
-        self.stack.push(t)
+    fn f64_binop(&mut self, op: FBinop, x: f64, y: f64) -> f64 {
+        op.eval(x, y)
     }
 
-    fn pop(&mut self) -> Type {
-        self.stack.pop().unwrap()
+    fn f64_unop(&mut self, op: FUnop, x: f64) -> f64 {
+        op.eval(x)
     }
 
-    fn set_local(&mut self, idx: i32, val: Type) {
-        self.locals[idx as usize] = val;
+    fn f64_relop(&mut self, op: FRelop, x: f64, y: f64) -> i32 {
+        op.eval(x, y)
     }
 
-    fn get_local(&mut self, idx: i32) -> Type {
-        self.locals[idx as usize]
+    fn cvtop(&mut self, op: Cvtop, x: Slot) -> Slot {
+        op.eval(x).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            Slot::default()
+        })
     }
 
-    fn is_loop(&self) -> bool {
-        self.ctl_entries.last().unwrap().tipe == CtlType::Loop
+    fn select(&mut self, _ty: Option<Type>, cond: i32, x: Slot, y: Slot) -> Slot {
+        if cond != 0 { x } else { y }
     }
 
-    fn start_block(&mut self, _ty_index: usize) {
-        self.ctl_stack.push(self.ctl_entries.len());
-        self.ctl_entries.push(CtlEntry {
-            tipe: CtlType::Block,
-            cont_ip: 0, // filled in later
-            cont_stp: self.sidetable_meta.len() - 1,
-        });
+    fn unreachable(&mut self) {
+        self.set_trap(TrapKind::Unreachable);
     }
 
-    fn start_loop(&mut self, _ty_index: usize) { 
-        self.ctl_stack.push(self.ctl_entries.len());
-        self.ctl_entries.push(CtlEntry {
-            tipe: CtlType::Loop,
-            cont_ip: self.codeptr.ip,
-            cont_stp: self.sidetable_meta.len() - 1,
-        });
+    fn ref_null(&mut self, _t: Type) -> Slot {
+        Slot::from(None)
     }
 
-    fn addi32(_: Type, _: Type) -> Type {
-        Type::I32
+    fn ref_is_null(&mut self, x: Slot) -> i32 {
+        x.reference().is_none() as i32
     }
 
-    fn i32_eqz(t: Type) -> Idk {
-        assert!(t == Type::I32);
-        Idk
+    fn ref_func(&mut self, func_idx: usize) -> Slot {
+        Slot::from(Some(func_idx))
     }
 
-    fn branch(&mut self, label_idx: usize) {
-        let ctl_idx = self.ctl_stack[self.ctl_stack.len() - 1 - label_idx];
-        self.sidetable_meta.push(SidetableMeta {
-            br_ip: self.codeptr.ip,
-            target_ctl_idx: ctl_idx,
-        });
-        // validate
+    fn table_get(&mut self, table_idx: usize, idx: i32) -> Slot {
+        self.tables[table_idx].get(idx).map(Slot::from).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            Slot::default()
+        })
     }
 
-    fn fallthru(&mut self) {
-        // validate
+    fn table_set(&mut self, table_idx: usize, idx: i32, val: Slot) {
+        if let Err(kind) = self.tables[table_idx].set(idx, val.reference()) {
+            self.set_trap(kind);
+        }
     }
 
-    fn end(&mut self) {
-        let ctl_idx = self.ctl_stack.pop().unwrap();
-        let ctl = &mut self.ctl_entries[ctl_idx];
-        if ctl.tipe == CtlType::Block {
-            ctl.cont_ip = self.codeptr.ip;
-            ctl.cont_stp = self.sidetable_meta.len() - 1;
+    fn table_size(&mut self, table_idx: usize) -> i32 {
+        self.tables[table_idx].size() as i32
+    }
+
+    fn table_grow(&mut self, table_idx: usize, init: Slot, delta: i32) -> i32 {
+        self.tables[table_idx].grow(delta as u32, init.reference())
+    }
+
+    fn table_fill(&mut self, table_idx: usize, dst: i32, val: Slot, len: i32) {
+        if let Err(kind) = self.tables[table_idx].fill(dst, val.reference(), len) {
+            self.set_trap(kind);
         }
     }
 
-    fn build_sidetable(&self) -> Vec<STEntry> {
-        self.sidetable_meta.iter().enumerate().map(|(stp, br_meta)| {
-            let target_ctl_idx = br_meta.target_ctl_idx;
-            let target_ctl = &self.ctl_entries[target_ctl_idx];
-            STEntry {
-                ip_delta: (target_ctl.cont_ip as isize) - (br_meta.br_ip as isize),
-                stp_delta: (target_ctl.cont_stp as isize) - (stp as isize),
-            }
-        }).collect()
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = table::copy(&mut self.tables, dst_table, src_table, dst, src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.tables[table_idx].init(dst, &self.elems[elem_idx], src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn elem_drop(&mut self, elem_idx: usize) {
+        self.elems[elem_idx] = vec![];
+    }
+
+    fn branch(&mut self, _label_idx: usize) {
+        self.stp += 1;
+        let ste = self.sidetable[self.stp];
+        // stupid casts
+        self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
+        self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
+        ste.unwind(&mut self.stack);
+    }
+
+    fn branch_if_false(&mut self) {
+        self.branch(0)
+    }
+
+    fn branch_return(&mut self) {
+        self.branch(0)
+    }
+
+    // a br_table's entries follow each other, skip to the one we take
+    fn branch_table(&mut self, labels: &[usize], idx: i32) {
+        self.stp += table_index(idx, labels.len());
+        self.branch(0)
     }
 
+    fn fallthru(&mut self) {
+        self.stp += 1; // we know that only one of branch or fallthru will run in this case,
+                       // but seems iffy
+    }
+
+    fn set_unreachable(&mut self) { }
+
     cbd!();
+
+    fn dispatch(&mut self) -> Result<(), Trap> {
+        loop {
+            let ip = self.codeptr.ip;
+            let Some(op) = self.codeptr.read_op() else { return Ok(()) };
+            op_dispatch!(op, self);
+            if let Some(kind) = self.trap.take() {
+                return Err(Trap { kind, ip });
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Type {
+    I32,
+    I64,
+    F32,
+    F64,
+    FuncRef,
+    ExternRef,
+    Unknown, // validator only, popped from the stack of unreachable code
+}
+
+impl Type {
+    // text format name
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::I32 => "i32",
+            Type::I64 => "i64",
+            Type::F32 => "f32",
+            Type::F64 => "f64",
+            Type::FuncRef => "funcref",
+            Type::ExternRef => "externref",
+            Type::Unknown => "unknown",
+        }
+    }
+
+    pub fn is_ref(self) -> bool {
+        matches!(self, Type::FuncRef | Type::ExternRef)
+    }
+
+    // the heap type ref.null names, "func" or "extern"
+    pub fn heap_type(self) -> &'static str {
+        self.name().strip_suffix("ref").unwrap()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CtlType {
+    Func,
+    Block,
+    Loop,
+    If,
+    Else, // an If once its else is reached
+    IfFalse, // not a frame, just where an if's false edge lands, always right after the If
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CtlEntry {
+    tipe: CtlType,
+    cont_ip: usize,
+    cont_stp: usize, // essentially idx of first branch
+    height: usize, // operand stack height on entry, below the params
+    unreachable: bool, // after br, the rest of the block's stack is polymorphic
+    params: Vec<Type>,
+    results: Vec<Type>,
+}
+
+impl CtlEntry {
+    // what a branch to this block carries
+    fn label_types(&self) -> &[Type] {
+        match self.tipe {
+            CtlType::Loop => &self.params,
+            _ => &self.results,
+        }
+    }
+}
+
+pub struct SidetableMeta {
+    br_ip: usize,
+    target_ctl_idx: usize,
+    val_count: usize,
+    pop_count: usize,
 }
 
 impl TypedEval {
-    fn dispatch(&mut self) {
-        while let Some(op) = self.codeptr_mut().read_op() {
-            op_dispatch!(op, self)
+    fn dispatch(&mut self) -> Result<(), Trap> {
+        loop {
+            let ip = self.codeptr.ip;
+            let Some(op) = self.codeptr.read_op() else { return Ok(()) };
+            op_dispatch!(op, self);
+            if let Some(kind) = self.trap.take() {
+                return Err(Trap { kind, ip });
+            }
         }
     }
 }
 
 impl TypedValidate {
-    fn dispatch(&mut self) {
-        while let Some(op) = self.codeptr_mut().read_op() {
-            op_dispatch!(op, self)
+    fn dispatch(&mut self) -> Result<(), ValidationError> {
+        loop {
+            self.op_ip = self.codeptr.ip;
+            let Some(op) = self.codeptr.read_op() else { break };
+            if self.ctl_stack.is_empty() {
+                // something after the function's final end
+                self.fail(ValidationErrorKind::UnbalancedEnd);
+            } else {
+                op_dispatch!(op, self);
+            }
+            if let Some(err) = self.error.take() {
+                return Err(err);
+            }
         }
+        // the function's own end is optional, sum_code() doesn't have one
+        if self.ctl_stack.len() > 1 {
+            self.fail(ValidationErrorKind::UnbalancedEnd);
+        }
+        self.error.take().map_or(Ok(()), Err)
     }
 }
 
@@ -418,19 +1533,30 @@ impl TypedCompiler {
 }
 
 pub trait Run {
-    fn run(&mut self);
-    fn step(&mut self, op: Opcode);
+    fn run(&mut self) -> Result<(), Trap>;
+    fn step(&mut self, op: Opcode) -> Result<(), Trap>;
 }
 
 impl<T: CBD_FR> Run for T {
-    fn run(&mut self) {
-        while let Some(op) = self.codeptr_mut().read_op() {
-            op_dispatch!(op, self)
+    fn run(&mut self) -> Result<(), Trap> {
+        loop {
+            let ip = self.codeptr_mut().ip;
+            let Some(op) = self.codeptr_mut().read_op() else { return Ok(()) };
+            op_dispatch!(op, self);
+            if let Some(kind) = self.take_trap() {
+                return Err(Trap { kind, ip });
+            }
         }
     }
 
-    fn step(&mut self, op: Opcode) {
-        op_dispatch!(op, self)
+    // expects op to have just been read from codeptr
+    fn step(&mut self, op: Opcode) -> Result<(), Trap> {
+        let ip = self.codeptr_mut().ip - 1;
+        op_dispatch!(op, self);
+        match self.take_trap() {
+            Some(kind) => Err(Trap { kind, ip }),
+            None => Ok(()),
+        }
     }
 }
 
@@ -438,22 +1564,22 @@ fn sum_code() -> Vec<CodeEntry> {
     use CodeEntry::*;
     use Opcode::*;
     vec![
-        Op(I32Const), I32Imm(5),
-        Op(Block), BlockType(0),
+        Op(Block), BlockType(BlockSig::Empty),
+            Op(I32Const), I32Imm(5),
             Op(I32Const), I32Imm(-15),
             Op(I32Const), I32Imm(20),
             Op(I32Add),
             Op(I32Add),
+            Op(LocalSet), I32Imm(0), // index
             Op(Br), I32Imm(0),
             Op(I32Const), I32Imm(-999),
+            Op(LocalSet), I32Imm(0),
         Op(End),
 
-        Op(LocalSet), I32Imm(0), // index
-
         Op(I32Const), I32Imm(0), // accumulator
         Op(LocalSet), I32Imm(1),
 
-        Op(Loop), BlockType(0),
+        Op(Loop), BlockType(BlockSig::Empty),
             Op(LocalGet),I32Imm(0), // add
             Op(LocalGet),I32Imm(1),
             Op(I32Add),
@@ -470,55 +1596,102 @@ fn sum_code() -> Vec<CodeEntry> {
     ]
 }
 
+// decodes a .wasm file, instantiates it and runs one of its functions, by
+// export name or function index. args are parsed as its param types.
+// failures go to stderr with a nonzero exit status, so scripts can tell
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("{msg}");
+    std::process::exit(1)
+}
+
+fn run_wasm(path: &str, func: &str, args: &[String]) {
+    let bytes = std::fs::read(path).unwrap_or_else(|err| fail(format!("couldn't read {path}: {err}")));
+    let module = decode::decode(&bytes).unwrap_or_else(|err| fail(format!("couldn't decode {path}: {err}")));
+
+    let mut store = Store::default();
+    let instance = Instance::new::<EvalFR>(&mut store, &module).unwrap_or_else(|err| fail(err));
+    let func_idx = match func.parse::<usize>() {
+        Ok(idx) => Some(idx).filter(|&idx| idx < instance.funcs.len()),
+        Err(_) => instance.func(func).and_then(|addr| instance.funcs.iter().position(|&a| a == addr)),
+    };
+    let Some(func_idx) = func_idx else { fail(format!("unknown function {func}")) };
+    let ty = &instance.func_types[func_idx];
+    if args.len() != ty.params.len() {
+        fail(format!("expected {} args", ty.params.len()));
+    }
+    let args: Vec<Slot> = args.iter().zip(&ty.params).map(|(arg, &t)| {
+        let val = match t {
+            Type::I32 => arg.parse().ok().map(Value::I32),
+            Type::I64 => arg.parse().ok().map(Value::I64),
+            Type::F32 => arg.parse().ok().map(Value::F32),
+            Type::F64 => arg.parse().ok().map(Value::F64),
+            _ => fail(format!("can't pass a {} param", t.name())),
+        };
+        val.unwrap_or_else(|| fail(format!("bad {} param {arg}", t.name()))).into()
+    }).collect();
+    match store.invoke::<EvalFR>(instance.funcs[func_idx], &args) {
+        Ok(results) => {
+            let results: Vec<Value> = results.into_iter().zip(&ty.results).map(|(slot, &t)| Value::from_slot(slot, t)).collect();
+            println!("{results:?}");
+        }
+        Err(trap) => fail(trap),
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(path) = args.get(1) {
+        let func = args.get(2).map_or("0", String::as_str);
+        run_wasm(path, func, args.get(3..).unwrap_or_default());
+        return;
+    }
+
     let code = sum_code();
 
     let nlocals = 2;
 
-    let mut validate = Validate {
-        stack: vec![],
-        locals: vec![Type::I32; nlocals],
-        ctl_entries: vec![CtlEntry { tipe: CtlType::Func, cont_ip: code.len(), cont_stp: 0 }],
-        ctl_stack: vec![0],
-        codeptr: CodePtr { code: code.clone(), ip: 0 },
-        sidetable_meta: vec![SidetableMeta { br_ip: 0, target_ctl_idx: 0 } ],
-    };
-    validate.dispatch();
-    // dbg!(&validate.ctl_stack);
-    // dbg!(&validate.ctl_entries);
+    let mut validate = TypedValidate::new(code.clone(), vec![Type::I32; nlocals], vec![Type::I32]);
+    validate.dispatch().unwrap();
     let sidetable = validate.build_sidetable();
 
     let mut eval = Eval {
         stack: vec![],
-        locals: vec![0; nlocals],
+        locals: vec![Slot::default(); nlocals],
         codeptr: CodePtr { code: code.clone(), ip: 0 },
-        sidetable: sidetable,
+        sidetable: sidetable.clone(),
         stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
     };
-    eval.dispatch();
+    eval.dispatch().unwrap();
     dbg!(eval.stack);
 
-    let mut tvalidate = TypedValidate {
-        stack: vec![],
-        locals: vec![Type::I32; nlocals],
-        ctl_entries: vec![CtlEntry { tipe: CtlType::Func, cont_ip: code.len(), cont_stp: 0 }],
-        ctl_stack: vec![0],
-        codeptr: CodePtr { code: code.clone(), ip: 0 },
-        sidetable_meta: vec![SidetableMeta { br_ip: 0, target_ctl_idx: 0 } ],
-    };
-    tvalidate.dispatch();
-    // dbg!(&validate.ctl_stack);
-    // dbg!(&validate.ctl_entries);
-    let sidetable = tvalidate.build_sidetable();
-
     let mut teval = TypedEval {
         stack: vec![],
-        locals: vec![0; nlocals],
+        locals: vec![Slot::default(); nlocals],
         codeptr: CodePtr { code: code.clone(), ip: 0 },
         sidetable: sidetable.clone(),
         stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
     };
-    teval.dispatch();
+    teval.dispatch().unwrap();
     dbg!(teval.stack);
 
     // let mut tcompiler = TypedCompiler {
@@ -529,15 +1702,15 @@ fn main() {
     // tcompiler.dispatch();
     // println!("{}", tcompiler.gen);
 
-    // let mut wasm_fun = cps::WASMFun::new(code.clone());
+    // let mut wasm_fun = cps::WASMFun::new(code.clone(), &sidetable);
     // dbg!(&wasm_fun.cont_blocks);
 
-    // let mut interpreter = cps::CPSEval { stack: vec![], locals: vec![0; nlocals] };
+    // let mut interpreter = cps::CPSEval { stack: vec![], locals: vec![0; nlocals], globals: vec![], trap: None };
     // let interpreter = wasm_fun.run(interpreter);
     // dbg!(interpreter.stack);
 
     // unsafe {
-    //     let mut interpreter = cps::CPSEval { stack: vec![], locals: vec![0; nlocals] };
+    //     let mut interpreter = cps::CPSEval { stack: vec![], locals: vec![0; nlocals], globals: vec![], trap: None };
     //     let mut codeptr = CodePtr { code: code.clone(), ip: 0 };
 
     //     let compiled = wasm_fun.compile::<cps::CPSEval>();
@@ -551,15 +1724,25 @@ fn main() {
 
     let mut fr_eval = EvalFR {
         stack: vec![],
-        locals: vec![0; nlocals],
+        locals: vec![Slot::default(); nlocals],
         codeptr: CodePtr { code: code.clone(), ip: 0 },
         sidetable: sidetable.clone(),
         stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
     };
-    fr_eval.run();
+    fr_eval.run().unwrap();
     dbg!(fr_eval.stack);
 
-    let wasm_fun = crate::cps::WASMFun::new(code.clone());
+    let wasm_fun = crate::cps::WASMFun::new(code.clone(), &sidetable);
     dbg!(&wasm_fun.cont_blocks);
 
     let mut ac = AbstractCompiler {
@@ -569,7 +1752,7 @@ fn main() {
         cont_blocks: &wasm_fun.cont_blocks,
         stp: 0,
     };
-    ac.run();
+    ac.run().unwrap();
     dbg!(&ac.block_bodies);
     let code = ac.emit();
     println!("{}", code);
@@ -0,0 +1,172 @@
+use crate::{Opcode, TrapKind};
+use crate::table;
+
+use std::ops::Range;
+
+// Linear memory. Loads and stores are operator families, as in num.rs, and
+// load()/store() here do the bounds checks and extension.
+
+pub const PAGE_SIZE: usize = 65536;
+pub const MAX_PAGES: u32 = 65536; // all an i32 address can reach
+
+// align is the log2 of the alignment hint, as in the binary format
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemArg {
+    pub align: u32,
+    pub offset: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I32LoadOp {
+    Load32,
+    Load8S,
+    Load8U,
+    Load16S,
+    Load16U,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I32StoreOp {
+    Store32,
+    Store8,
+    Store16,
+}
+
+impl I32LoadOp {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I32LoadOp::*;
+        Some(match op {
+            Opcode::I32Load => Load32,
+            Opcode::I32Load8S => Load8S,
+            Opcode::I32Load8U => Load8U,
+            Opcode::I32Load16S => Load16S,
+            Opcode::I32Load16U => Load16U,
+            _ => return None,
+        })
+    }
+
+    pub fn width(self) -> usize {
+        use I32LoadOp::*;
+        match self {
+            Load32 => 4,
+            Load8S | Load8U => 1,
+            Load16S | Load16U => 2,
+        }
+    }
+
+    // little endian bytes to the i32 pushed
+    fn extend(self, bytes: &[u8]) -> i32 {
+        use I32LoadOp::*;
+        match self {
+            Load32 => i32::from_le_bytes(bytes.try_into().unwrap()),
+            Load8S => bytes[0] as i8 as i32,
+            Load8U => bytes[0] as i32,
+            Load16S => i16::from_le_bytes(bytes.try_into().unwrap()) as i32,
+            Load16U => u16::from_le_bytes(bytes.try_into().unwrap()) as i32,
+        }
+    }
+}
+
+impl I32StoreOp {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I32StoreOp::*;
+        Some(match op {
+            Opcode::I32Store => Store32,
+            Opcode::I32Store8 => Store8,
+            Opcode::I32Store16 => Store16,
+            _ => return None,
+        })
+    }
+
+    pub fn width(self) -> usize {
+        use I32StoreOp::*;
+        match self {
+            Store32 => 4,
+            Store8 => 1,
+            Store16 => 2,
+        }
+    }
+}
+
+// bytes a load or store touches, None for other ops. Its log2 is the
+// natural alignment, the most a memarg may claim.
+pub fn access_width(op: Opcode) -> Option<usize> {
+    I32LoadOp::from_opcode(op).map(I32LoadOp::width)
+        .or_else(|| I32StoreOp::from_opcode(op).map(I32StoreOp::width))
+}
+
+// the default is a module without memory, where every access traps and
+// nothing can grow
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinearMemory {
+    pub bytes: Vec<u8>,
+    pub max: u32, // in pages
+}
+
+impl LinearMemory {
+    pub fn new(min: u32, max: Option<u32>) -> Self {
+        LinearMemory { bytes: vec![0; min as usize * PAGE_SIZE], max: max.unwrap_or(MAX_PAGES) }
+    }
+
+    pub fn pages(&self) -> u32 {
+        (self.bytes.len() / PAGE_SIZE) as u32
+    }
+
+    // the old size in pages, or -1 if it can't grow that far
+    pub fn grow(&mut self, delta: u32) -> i32 {
+        let old = self.pages();
+        match old.checked_add(delta) {
+            Some(new) if new <= self.max => {
+                self.bytes.resize(new as usize * PAGE_SIZE, 0);
+                old as i32
+            }
+            _ => -1,
+        }
+    }
+
+    // addresses are unsigned and the offset is added without wrapping
+    fn range(&self, memarg: MemArg, addr: i32, width: usize) -> Result<Range<usize>, TrapKind> {
+        let start = addr as u32 as u64 + memarg.offset as u64;
+        let end = start + width as u64;
+        if end > self.bytes.len() as u64 {
+            return Err(TrapKind::MemoryOutOfBounds);
+        }
+        Ok(start as usize..end as usize)
+    }
+
+    pub fn load(&self, op: I32LoadOp, memarg: MemArg, addr: i32) -> Result<i32, TrapKind> {
+        let range = self.range(memarg, addr, op.width())?;
+        Ok(op.extend(&self.bytes[range]))
+    }
+
+    // narrow stores keep the low bytes
+    pub fn store(&mut self, op: I32StoreOp, memarg: MemArg, addr: i32, val: i32) -> Result<(), TrapKind> {
+        let range = self.range(memarg, addr, op.width())?;
+        let n = range.len();
+        self.bytes[range].copy_from_slice(&val.to_le_bytes()[..n]);
+        Ok(())
+    }
+
+    // from a data segment's bytes
+    pub fn init(&mut self, dst: i32, data: &[u8], src: i32, len: i32) -> Result<(), TrapKind> {
+        let src = table::range(data.len(), src, len, TrapKind::MemoryOutOfBounds)?;
+        let dst = table::range(self.bytes.len(), dst, len, TrapKind::MemoryOutOfBounds)?;
+        self.bytes[dst].copy_from_slice(&data[src]);
+        Ok(())
+    }
+
+    // both ranges are checked before anything moves, and they may overlap
+    pub fn copy(&mut self, dst: i32, src: i32, len: i32) -> Result<(), TrapKind> {
+        let src = table::range(self.bytes.len(), src, len, TrapKind::MemoryOutOfBounds)?;
+        let dst = table::range(self.bytes.len(), dst, len, TrapKind::MemoryOutOfBounds)?;
+        self.bytes.copy_within(src, dst.start);
+        Ok(())
+    }
+
+    // with the value's low byte
+    pub fn fill(&mut self, dst: i32, val: i32, len: i32) -> Result<(), TrapKind> {
+        let dst = table::range(self.bytes.len(), dst, len, TrapKind::MemoryOutOfBounds)?;
+        self.bytes[dst].fill(val as u8);
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{CodeEntry, CodePtr, FuncEntry, STEntry, Trap, Type, ValidationError, ValidationErrorKind};
+use crate::instance::{HostFunc, Instance, Store};
+use crate::mem::LinearMemory;
+use crate::num::Slot;
+use crate::table::RefTable;
+
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuncType {
+    pub params: Vec<Type>,
+    pub results: Vec<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Func {
+    pub ty: usize, // index into Module::types
+    pub locals: Vec<Type>, // declared locals, not including params
+    pub code: Vec<CodeEntry>,
+}
+
+// limits in elements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub elem_type: Type, // funcref or externref
+    pub min: u32,
+    pub max: Option<u32>,
+}
+
+// limits in pages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory {
+    pub min: u32,
+    pub max: Option<u32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GlobalType {
+    pub ty: Type,
+    pub mutable: bool,
+}
+
+// initializers and segment offsets, evaluated when linking. global.get may
+// only read an earlier immutable global.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConstExpr {
+    I32Const(i32),
+    I64Const(i64),
+    F32Const(u32), // bits, as in CodeEntry
+    F64Const(u64),
+    GlobalGet(usize),
+    RefNull(Type),
+    RefFunc(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Global {
+    pub ty: GlobalType,
+    pub init: ConstExpr,
+}
+
+// active segments are copied into their table when linking, passive ones
+// wait for table.init, and declarative ones only declare what ref.func may
+// name. Active and declarative segments are dropped once linked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ElemMode {
+    Active { table: usize, offset: ConstExpr },
+    Passive,
+    Declarative,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Elem {
+    pub ty: Type,
+    pub mode: ElemMode,
+    pub init: Vec<ConstExpr>,
+}
+
+// likewise for memory: active segments are copied in and dropped when
+// linking, passive ones wait for memory.init
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataMode {
+    Active { memory: usize, offset: ConstExpr },
+    Passive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Data {
+    pub mode: DataMode,
+    pub init: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportDesc {
+    Func(usize), // type index
+    Table(Table),
+    Memory(Memory),
+    Global(GlobalType),
+}
+
+// imports come first in their index space, in the order they're listed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub desc: ImportDesc,
+}
+
+// indices into the module's index spaces, imports included
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportDesc {
+    Func(usize),
+    Table(usize),
+    Memory(usize),
+    Global(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    pub desc: ExportDesc,
+}
+
+// funcs, tables, memories and globals are only the ones the module defines,
+// see func_types() and co. for whole index spaces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Module {
+    pub types: Vec<FuncType>,
+    pub imports: Vec<Import>,
+    pub funcs: Vec<Func>,
+    pub tables: Vec<Table>,
+    pub elems: Vec<Elem>,
+    pub memories: Vec<Memory>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub start: Option<usize>,
+    pub datas: Vec<Data>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    Invalid(ValidationError),
+    InvalidElem { elem: usize, kind: ValidationErrorKind },
+    ElemOutOfBounds(usize),
+    InvalidData { data: usize, kind: ValidationErrorKind },
+    DataOutOfBounds(usize),
+    MultipleMemories,
+    MemoryLimits { min: u32, max: Option<u32> },
+    InvalidTable { table: usize, kind: ValidationErrorKind },
+    TableLimits { min: u32, max: Option<u32> },
+    InvalidGlobal { global: usize, kind: ValidationErrorKind },
+    InvalidImport { import: usize, kind: ValidationErrorKind },
+    UnknownImport { module: String, name: String },
+    IncompatibleImport { module: String, name: String },
+    InvalidExport { name: String, kind: ValidationErrorKind },
+    DuplicateExport(String),
+    InvalidStart(usize), // unknown, or takes or returns something
+    StartTrapped(Trap),
+}
+
+impl From<ValidationError> for LinkError {
+    fn from(err: ValidationError) -> Self {
+        LinkError::Invalid(err)
+    }
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LinkError::Invalid(err) => write!(f, "{err}"),
+            LinkError::InvalidElem { elem, kind } => write!(f, "invalid element segment {elem}: {kind}"),
+            LinkError::ElemOutOfBounds(elem) => write!(f, "element segment {elem} out of bounds"),
+            LinkError::InvalidData { data, kind } => write!(f, "invalid data segment {data}: {kind}"),
+            LinkError::DataOutOfBounds(data) => write!(f, "data segment {data} out of bounds"),
+            LinkError::MultipleMemories => write!(f, "multiple memories"),
+            LinkError::MemoryLimits { min, max } => write!(f, "bad memory limits {min} {max:?}"),
+            LinkError::InvalidTable { table, kind } => write!(f, "invalid table {table}: {kind}"),
+            LinkError::TableLimits { min, max } => write!(f, "bad table limits {min} {max:?}"),
+            LinkError::InvalidGlobal { global, kind } => write!(f, "invalid global {global}: {kind}"),
+            LinkError::InvalidImport { import, kind } => write!(f, "invalid import {import}: {kind}"),
+            LinkError::UnknownImport { module, name } => write!(f, "unknown import {module}.{name}"),
+            LinkError::IncompatibleImport { module, name } => write!(f, "incompatible import type for {module}.{name}"),
+            LinkError::InvalidExport { name, kind } => write!(f, "invalid export {name}: {kind}"),
+            LinkError::DuplicateExport(name) => write!(f, "duplicate export name {name}"),
+            LinkError::InvalidStart(func) => write!(f, "invalid start function {func}"),
+            LinkError::StartTrapped(trap) => write!(f, "start function: {trap}"),
+        }
+    }
+}
+
+// every function's code and sidetable laid end to end, so a call is just a
+// jump to the callee's FuncEntry and a return a jump back. A Store keeps
+// one for all its instances, indexed by store address.
+#[derive(Debug, Clone, Default)]
+pub struct Linked {
+    pub code: Vec<CodeEntry>,
+    pub sidetable: Vec<STEntry>,
+    pub funcs: Vec<FuncEntry>,
+    pub hosts: Vec<HostFunc>,
+    pub tables: Vec<RefTable>,
+    pub elems: Vec<Vec<Option<usize>>>, // each segment's references, empty once dropped
+    // the first type index with the same signature as each type index, so
+    // call_indirect compares types structurally with one integer compare
+    pub sigs: Vec<usize>,
+    pub memory: LinearMemory, // empty if the module has none
+    pub datas: Vec<Vec<u8>>, // each segment's bytes, empty once dropped
+    pub globals: Vec<Slot>, // initial values
+}
+
+impl Linked {
+    // starts at the end, where returning from the outermost call goes
+    pub fn codeptr(&self) -> CodePtr {
+        CodePtr { code: self.code.clone(), ip: self.code.len() }
+    }
+}
+
+impl Module {
+    pub fn func_type(&self, func_idx: usize) -> &FuncType {
+        &self.types[self.funcs[func_idx].ty]
+    }
+
+    // what local.get/local.set index into: params first, then declared locals
+    pub fn local_types(&self, func_idx: usize) -> Vec<Type> {
+        let mut locals = self.func_type(func_idx).params.clone();
+        locals.extend_from_slice(&self.funcs[func_idx].locals);
+        locals
+    }
+
+    pub fn codeptr(&self, func_idx: usize) -> CodePtr {
+        CodePtr { code: self.funcs[func_idx].code.clone(), ip: 0 }
+    }
+
+    // instantiates into a store of its own, without imports or running the
+    // start function
+    pub fn link(&self) -> Result<Linked, LinkError> {
+        let mut store = Store::default();
+        Instance::allocate(&mut store, self)?;
+        Ok(store.linked)
+    }
+
+    // every function's type, imports first as in the function index space.
+    // Type indices must be in range, instantiation checks them first.
+    pub fn func_types(&self) -> Vec<FuncType> {
+        let imported = self.imports.iter().filter_map(|i| match i.desc {
+            ImportDesc::Func(ty) => Some(self.types[ty].clone()),
+            _ => None,
+        });
+        imported.chain(self.funcs.iter().map(|f| self.types[f.ty].clone())).collect()
+    }
+
+    pub fn table_types(&self) -> Vec<Table> {
+        let imported = self.imports.iter().filter_map(|i| match &i.desc {
+            ImportDesc::Table(table) => Some(table.clone()),
+            _ => None,
+        });
+        imported.chain(self.tables.iter().cloned()).collect()
+    }
+
+    pub fn memory_types(&self) -> Vec<Memory> {
+        let imported = self.imports.iter().filter_map(|i| match &i.desc {
+            ImportDesc::Memory(memory) => Some(memory.clone()),
+            _ => None,
+        });
+        imported.chain(self.memories.iter().cloned()).collect()
+    }
+
+    pub fn global_types(&self) -> Vec<GlobalType> {
+        let imported = self.imports.iter().filter_map(|i| match i.desc {
+            ImportDesc::Global(ty) => Some(ty),
+            _ => None,
+        });
+        imported.chain(self.globals.iter().map(|g| g.ty)).collect()
+    }
+
+    // the functions ref.func may name: those in element segments, global
+    // initializers and exports
+    pub fn declared_refs(&self) -> HashSet<usize> {
+        let exprs = self.elems.iter().flat_map(|e| &e.init).chain(self.globals.iter().map(|g| &g.init));
+        let mut refs: HashSet<usize> = exprs.filter_map(|expr| match *expr {
+            ConstExpr::RefFunc(idx) => Some(idx),
+            _ => None,
+        }).collect();
+        refs.extend(self.exports.iter().filter_map(|e| match e.desc {
+            ExportDesc::Func(idx) => Some(idx),
+            _ => None,
+        }));
+        refs
+    }
+}
@@ -0,0 +1,773 @@
+use crate::{Opcode, TrapKind, Type};
+
+use std::ops::{Add, Div, Mul, Sub};
+
+// Numeric operator families. Each CBD gets one hook per family, taking the
+// operator as an argument, rather than one hook per instruction; evaluators
+// just call eval() here so the semantics live in one place.
+
+// An operand stack, local or global slot. Slots aren't tagged: validation
+// guarantees an op reads a slot as the type it was written as. i32s and
+// f32 bits are kept zero-extended, so slots holding equal values compare
+// equal, and a reinterpretation leaves the slot as it is. A reference is its
+// index plus one, so a zeroed slot is null.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Slot(pub u64);
+
+impl Slot {
+    pub fn i32(self) -> i32 {
+        self.0 as i32
+    }
+
+    pub fn i64(self) -> i64 {
+        self.0 as i64
+    }
+
+    pub fn f32(self) -> f32 {
+        f32::from_bits(self.0 as u32)
+    }
+
+    pub fn f64(self) -> f64 {
+        f64::from_bits(self.0)
+    }
+
+    pub fn reference(self) -> Option<usize> {
+        (self.0 as usize).checked_sub(1)
+    }
+}
+
+impl From<i32> for Slot {
+    fn from(x: i32) -> Self {
+        Slot(x as u32 as u64)
+    }
+}
+
+impl From<i64> for Slot {
+    fn from(x: i64) -> Self {
+        Slot(x as u64)
+    }
+}
+
+impl From<f32> for Slot {
+    fn from(x: f32) -> Self {
+        Slot(x.to_bits() as u64)
+    }
+}
+
+impl From<f64> for Slot {
+    fn from(x: f64) -> Self {
+        Slot(x.to_bits())
+    }
+}
+
+impl From<Option<usize>> for Slot {
+    fn from(x: Option<usize>) -> Self {
+        Slot(x.map_or(0, |idx| idx as u64 + 1))
+    }
+}
+
+impl From<Slot> for i32 {
+    fn from(slot: Slot) -> Self {
+        slot.i32()
+    }
+}
+
+impl From<Slot> for i64 {
+    fn from(slot: Slot) -> Self {
+        slot.i64()
+    }
+}
+
+impl From<Slot> for f32 {
+    fn from(slot: Slot) -> Self {
+        slot.f32()
+    }
+}
+
+impl From<Slot> for f64 {
+    fn from(slot: Slot) -> Self {
+        slot.f64()
+    }
+}
+
+impl From<Slot> for Option<usize> {
+    fn from(slot: Slot) -> Self {
+        slot.reference()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I32Binop {
+    Sub,
+    Mul,
+    DivS,
+    DivU,
+    RemS,
+    RemU,
+    And,
+    Or,
+    Xor,
+    Shl,
+    ShrS,
+    ShrU,
+    Rotl,
+    Rotr,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I32Unop {
+    Clz,
+    Ctz,
+    Popcnt,
+    Eqz,
+    Extend8S,
+    Extend16S,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I32Relop {
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    GtS,
+    GtU,
+    LeS,
+    LeU,
+    GeS,
+    GeU,
+}
+
+impl I32Binop {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I32Binop::*;
+        Some(match op {
+            Opcode::I32Sub => Sub,
+            Opcode::I32Mul => Mul,
+            Opcode::I32DivS => DivS,
+            Opcode::I32DivU => DivU,
+            Opcode::I32RemS => RemS,
+            Opcode::I32RemU => RemU,
+            Opcode::I32And => And,
+            Opcode::I32Or => Or,
+            Opcode::I32Xor => Xor,
+            Opcode::I32Shl => Shl,
+            Opcode::I32ShrS => ShrS,
+            Opcode::I32ShrU => ShrU,
+            Opcode::I32Rotl => Rotl,
+            Opcode::I32Rotr => Rotr,
+            _ => return None,
+        })
+    }
+
+    // x is the lhs, i.e. the deeper of the two operands on the stack
+    pub fn eval(self, x: i32, y: i32) -> Result<i32, TrapKind> {
+        use I32Binop::*;
+        let (ux, uy) = (x as u32, y as u32);
+        if matches!(self, DivS | DivU | RemS | RemU) && y == 0 {
+            return Err(TrapKind::IntDivByZero);
+        }
+        Ok(match self {
+            Sub => x.wrapping_sub(y),
+            Mul => x.wrapping_mul(y),
+            DivS => x.checked_div(y).ok_or(TrapKind::IntOverflow)?,
+            DivU => (ux / uy) as i32,
+            RemS => x.wrapping_rem(y),
+            RemU => (ux % uy) as i32,
+            And => x & y,
+            Or => x | y,
+            Xor => x ^ y,
+            // shift counts are taken mod 32
+            Shl => x.wrapping_shl(uy),
+            ShrS => x.wrapping_shr(uy),
+            ShrU => ux.wrapping_shr(uy) as i32,
+            Rotl => ux.rotate_left(uy % 32) as i32,
+            Rotr => ux.rotate_right(uy % 32) as i32,
+        })
+    }
+}
+
+impl I32Unop {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I32Unop::*;
+        Some(match op {
+            Opcode::I32Clz => Clz,
+            Opcode::I32Ctz => Ctz,
+            Opcode::I32Popcnt => Popcnt,
+            Opcode::I32Eqz => Eqz,
+            Opcode::I32Extend8S => Extend8S,
+            Opcode::I32Extend16S => Extend16S,
+            _ => return None,
+        })
+    }
+
+    pub fn eval(self, x: i32) -> i32 {
+        use I32Unop::*;
+        match self {
+            Clz => x.leading_zeros() as i32,
+            Ctz => x.trailing_zeros() as i32,
+            Popcnt => x.count_ones() as i32,
+            Eqz => (x == 0) as i32,
+            Extend8S => x as i8 as i32,
+            Extend16S => x as i16 as i32,
+        }
+    }
+}
+
+impl I32Relop {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I32Relop::*;
+        Some(match op {
+            Opcode::I32Eq => Eq,
+            Opcode::I32Ne => Ne,
+            Opcode::I32LtS => LtS,
+            Opcode::I32LtU => LtU,
+            Opcode::I32GtS => GtS,
+            Opcode::I32GtU => GtU,
+            Opcode::I32LeS => LeS,
+            Opcode::I32LeU => LeU,
+            Opcode::I32GeS => GeS,
+            Opcode::I32GeU => GeU,
+            _ => return None,
+        })
+    }
+
+    pub fn eval(self, x: i32, y: i32) -> i32 {
+        use I32Relop::*;
+        let (ux, uy) = (x as u32, y as u32);
+        let res = match self {
+            Eq => x == y,
+            Ne => x != y,
+            LtS => x < y,
+            LtU => ux < uy,
+            GtS => x > y,
+            GtU => ux > uy,
+            LeS => x <= y,
+            LeU => ux <= uy,
+            GeS => x >= y,
+            GeU => ux >= uy,
+        };
+        res as i32
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I64Binop {
+    Sub,
+    Mul,
+    DivS,
+    DivU,
+    RemS,
+    RemU,
+    And,
+    Or,
+    Xor,
+    Shl,
+    ShrS,
+    ShrU,
+    Rotl,
+    Rotr,
+}
+
+// i64.eqz produces an i32, so it's a hook of its own rather than a unop
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I64Unop {
+    Clz,
+    Ctz,
+    Popcnt,
+    Extend8S,
+    Extend16S,
+    Extend32S,
+}
+
+// i64 operands, i32 result
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum I64Relop {
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    GtS,
+    GtU,
+    LeS,
+    LeU,
+    GeS,
+    GeU,
+}
+
+impl I64Binop {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I64Binop::*;
+        Some(match op {
+            Opcode::I64Sub => Sub,
+            Opcode::I64Mul => Mul,
+            Opcode::I64DivS => DivS,
+            Opcode::I64DivU => DivU,
+            Opcode::I64RemS => RemS,
+            Opcode::I64RemU => RemU,
+            Opcode::I64And => And,
+            Opcode::I64Or => Or,
+            Opcode::I64Xor => Xor,
+            Opcode::I64Shl => Shl,
+            Opcode::I64ShrS => ShrS,
+            Opcode::I64ShrU => ShrU,
+            Opcode::I64Rotl => Rotl,
+            Opcode::I64Rotr => Rotr,
+            _ => return None,
+        })
+    }
+
+    pub fn eval(self, x: i64, y: i64) -> Result<i64, TrapKind> {
+        use I64Binop::*;
+        let (ux, uy) = (x as u64, y as u64);
+        if matches!(self, DivS | DivU | RemS | RemU) && y == 0 {
+            return Err(TrapKind::IntDivByZero);
+        }
+        Ok(match self {
+            Sub => x.wrapping_sub(y),
+            Mul => x.wrapping_mul(y),
+            DivS => x.checked_div(y).ok_or(TrapKind::IntOverflow)?,
+            DivU => (ux / uy) as i64,
+            RemS => x.wrapping_rem(y),
+            RemU => (ux % uy) as i64,
+            And => x & y,
+            Or => x | y,
+            Xor => x ^ y,
+            // shift counts are taken mod 64
+            Shl => x.wrapping_shl(uy as u32),
+            ShrS => x.wrapping_shr(uy as u32),
+            ShrU => ux.wrapping_shr(uy as u32) as i64,
+            Rotl => ux.rotate_left((uy % 64) as u32) as i64,
+            Rotr => ux.rotate_right((uy % 64) as u32) as i64,
+        })
+    }
+}
+
+impl I64Unop {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I64Unop::*;
+        Some(match op {
+            Opcode::I64Clz => Clz,
+            Opcode::I64Ctz => Ctz,
+            Opcode::I64Popcnt => Popcnt,
+            Opcode::I64Extend8S => Extend8S,
+            Opcode::I64Extend16S => Extend16S,
+            Opcode::I64Extend32S => Extend32S,
+            _ => return None,
+        })
+    }
+
+    pub fn eval(self, x: i64) -> i64 {
+        use I64Unop::*;
+        match self {
+            Clz => x.leading_zeros() as i64,
+            Ctz => x.trailing_zeros() as i64,
+            Popcnt => x.count_ones() as i64,
+            Extend8S => x as i8 as i64,
+            Extend16S => x as i16 as i64,
+            Extend32S => x as i32 as i64,
+        }
+    }
+}
+
+impl I64Relop {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use I64Relop::*;
+        Some(match op {
+            Opcode::I64Eq => Eq,
+            Opcode::I64Ne => Ne,
+            Opcode::I64LtS => LtS,
+            Opcode::I64LtU => LtU,
+            Opcode::I64GtS => GtS,
+            Opcode::I64GtU => GtU,
+            Opcode::I64LeS => LeS,
+            Opcode::I64LeU => LeU,
+            Opcode::I64GeS => GeS,
+            Opcode::I64GeU => GeU,
+            _ => return None,
+        })
+    }
+
+    pub fn eval(self, x: i64, y: i64) -> i32 {
+        use I64Relop::*;
+        let (ux, uy) = (x as u64, y as u64);
+        let res = match self {
+            Eq => x == y,
+            Ne => x != y,
+            LtS => x < y,
+            LtU => ux < uy,
+            GtS => x > y,
+            GtU => ux > uy,
+            LeS => x <= y,
+            LeU => ux <= uy,
+            GeS => x >= y,
+            GeU => ux >= uy,
+        };
+        res as i32
+    }
+}
+
+// The float families are shared by f32 and f64, the opcode picks the width.
+// abs, neg and copysign only touch the sign bit, so they keep NaN payloads;
+// everything else returning a NaN returns whatever the hardware's arithmetic
+// NaN is, which the spec allows.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FBinop {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Min,
+    Max,
+    Copysign,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FUnop {
+    Abs,
+    Neg,
+    Ceil,
+    Floor,
+    Trunc,
+    Nearest,
+    Sqrt,
+}
+
+// float operands, i32 result
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FRelop {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+pub trait Float: Copy + PartialOrd + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> {
+    fn is_nan(self) -> bool;
+    fn is_sign_negative(self) -> bool;
+    fn abs(self) -> Self;
+    fn neg(self) -> Self;
+    fn ceil(self) -> Self;
+    fn floor(self) -> Self;
+    fn trunc(self) -> Self;
+    fn nearest(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn copysign(self, sign: Self) -> Self;
+}
+
+macro_rules! impl_float {
+    ($t:ty) => {
+        impl Float for $t {
+            fn is_nan(self) -> bool { <$t>::is_nan(self) }
+            fn is_sign_negative(self) -> bool { <$t>::is_sign_negative(self) }
+            fn abs(self) -> Self { <$t>::abs(self) }
+            fn neg(self) -> Self { -self }
+            fn ceil(self) -> Self { <$t>::ceil(self) }
+            fn floor(self) -> Self { <$t>::floor(self) }
+            fn trunc(self) -> Self { <$t>::trunc(self) }
+            fn nearest(self) -> Self { <$t>::round_ties_even(self) }
+            fn sqrt(self) -> Self { <$t>::sqrt(self) }
+            fn copysign(self, sign: Self) -> Self { <$t>::copysign(self, sign) }
+        }
+    };
+}
+
+impl_float!(f32);
+impl_float!(f64);
+
+impl FBinop {
+    pub fn from_f32_opcode(op: Opcode) -> Option<Self> {
+        use FBinop::*;
+        Some(match op {
+            Opcode::F32Add => Add,
+            Opcode::F32Sub => Sub,
+            Opcode::F32Mul => Mul,
+            Opcode::F32Div => Div,
+            Opcode::F32Min => Min,
+            Opcode::F32Max => Max,
+            Opcode::F32Copysign => Copysign,
+            _ => return None,
+        })
+    }
+
+    pub fn from_f64_opcode(op: Opcode) -> Option<Self> {
+        use FBinop::*;
+        Some(match op {
+            Opcode::F64Add => Add,
+            Opcode::F64Sub => Sub,
+            Opcode::F64Mul => Mul,
+            Opcode::F64Div => Div,
+            Opcode::F64Min => Min,
+            Opcode::F64Max => Max,
+            Opcode::F64Copysign => Copysign,
+            _ => return None,
+        })
+    }
+
+    pub fn eval<F: Float>(self, x: F, y: F) -> F {
+        use FBinop::*;
+        match self {
+            Add => x + y,
+            Sub => x - y,
+            Mul => x * y,
+            Div => x / y,
+            // unlike Rust's min and max, a NaN operand wins and -0 < +0
+            Min | Max if x.is_nan() || y.is_nan() => x + y,
+            Min if x == y => if x.is_sign_negative() { x } else { y },
+            Max if x == y => if x.is_sign_negative() { y } else { x },
+            Min => if x < y { x } else { y },
+            Max => if x > y { x } else { y },
+            Copysign => x.copysign(y),
+        }
+    }
+}
+
+impl FUnop {
+    pub fn from_f32_opcode(op: Opcode) -> Option<Self> {
+        use FUnop::*;
+        Some(match op {
+            Opcode::F32Abs => Abs,
+            Opcode::F32Neg => Neg,
+            Opcode::F32Ceil => Ceil,
+            Opcode::F32Floor => Floor,
+            Opcode::F32Trunc => Trunc,
+            Opcode::F32Nearest => Nearest,
+            Opcode::F32Sqrt => Sqrt,
+            _ => return None,
+        })
+    }
+
+    pub fn from_f64_opcode(op: Opcode) -> Option<Self> {
+        use FUnop::*;
+        Some(match op {
+            Opcode::F64Abs => Abs,
+            Opcode::F64Neg => Neg,
+            Opcode::F64Ceil => Ceil,
+            Opcode::F64Floor => Floor,
+            Opcode::F64Trunc => Trunc,
+            Opcode::F64Nearest => Nearest,
+            Opcode::F64Sqrt => Sqrt,
+            _ => return None,
+        })
+    }
+
+    pub fn eval<F: Float>(self, x: F) -> F {
+        use FUnop::*;
+        match self {
+            Abs => x.abs(),
+            Neg => x.neg(),
+            Ceil => x.ceil(),
+            Floor => x.floor(),
+            Trunc => x.trunc(),
+            Nearest => x.nearest(), // ties to even
+            Sqrt => x.sqrt(),
+        }
+    }
+}
+
+impl FRelop {
+    pub fn from_f32_opcode(op: Opcode) -> Option<Self> {
+        use FRelop::*;
+        Some(match op {
+            Opcode::F32Eq => Eq,
+            Opcode::F32Ne => Ne,
+            Opcode::F32Lt => Lt,
+            Opcode::F32Gt => Gt,
+            Opcode::F32Le => Le,
+            Opcode::F32Ge => Ge,
+            _ => return None,
+        })
+    }
+
+    pub fn from_f64_opcode(op: Opcode) -> Option<Self> {
+        use FRelop::*;
+        Some(match op {
+            Opcode::F64Eq => Eq,
+            Opcode::F64Ne => Ne,
+            Opcode::F64Lt => Lt,
+            Opcode::F64Gt => Gt,
+            Opcode::F64Le => Le,
+            Opcode::F64Ge => Ge,
+            _ => return None,
+        })
+    }
+
+    // every comparison with a NaN is false, except ne
+    pub fn eval<F: Float>(self, x: F, y: F) -> i32 {
+        use FRelop::*;
+        let res = match self {
+            Eq => x == y,
+            Ne => x != y,
+            Lt => x < y,
+            Gt => x > y,
+            Le => x <= y,
+            Ge => x >= y,
+        };
+        res as i32
+    }
+}
+
+// Conversions between value types. Their operand and result types differ
+// from op to op, so the hook takes and returns whole stack values rather
+// than having one per pair of types; types() says what they hold.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Cvtop {
+    I32WrapI64,
+    I64ExtendI32S,
+    I64ExtendI32U,
+    I32TruncF32S,
+    I32TruncF32U,
+    I32TruncF64S,
+    I32TruncF64U,
+    I64TruncF32S,
+    I64TruncF32U,
+    I64TruncF64S,
+    I64TruncF64U,
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+    F32ConvertI32S,
+    F32ConvertI32U,
+    F32ConvertI64S,
+    F32ConvertI64U,
+    F32DemoteF64,
+    F64ConvertI32S,
+    F64ConvertI32U,
+    F64ConvertI64S,
+    F64ConvertI64U,
+    F64PromoteF32,
+    I32ReinterpretF32,
+    I64ReinterpretF64,
+    F32ReinterpretI32,
+    F64ReinterpretI64,
+}
+
+impl Cvtop {
+    pub fn from_opcode(op: Opcode) -> Option<Self> {
+        use Cvtop::*;
+        Some(match op {
+            Opcode::I32WrapI64 => I32WrapI64,
+            Opcode::I64ExtendI32S => I64ExtendI32S,
+            Opcode::I64ExtendI32U => I64ExtendI32U,
+            Opcode::I32TruncF32S => I32TruncF32S,
+            Opcode::I32TruncF32U => I32TruncF32U,
+            Opcode::I32TruncF64S => I32TruncF64S,
+            Opcode::I32TruncF64U => I32TruncF64U,
+            Opcode::I64TruncF32S => I64TruncF32S,
+            Opcode::I64TruncF32U => I64TruncF32U,
+            Opcode::I64TruncF64S => I64TruncF64S,
+            Opcode::I64TruncF64U => I64TruncF64U,
+            Opcode::I32TruncSatF32S => I32TruncSatF32S,
+            Opcode::I32TruncSatF32U => I32TruncSatF32U,
+            Opcode::I32TruncSatF64S => I32TruncSatF64S,
+            Opcode::I32TruncSatF64U => I32TruncSatF64U,
+            Opcode::I64TruncSatF32S => I64TruncSatF32S,
+            Opcode::I64TruncSatF32U => I64TruncSatF32U,
+            Opcode::I64TruncSatF64S => I64TruncSatF64S,
+            Opcode::I64TruncSatF64U => I64TruncSatF64U,
+            Opcode::F32ConvertI32S => F32ConvertI32S,
+            Opcode::F32ConvertI32U => F32ConvertI32U,
+            Opcode::F32ConvertI64S => F32ConvertI64S,
+            Opcode::F32ConvertI64U => F32ConvertI64U,
+            Opcode::F32DemoteF64 => F32DemoteF64,
+            Opcode::F64ConvertI32S => F64ConvertI32S,
+            Opcode::F64ConvertI32U => F64ConvertI32U,
+            Opcode::F64ConvertI64S => F64ConvertI64S,
+            Opcode::F64ConvertI64U => F64ConvertI64U,
+            Opcode::F64PromoteF32 => F64PromoteF32,
+            Opcode::I32ReinterpretF32 => I32ReinterpretF32,
+            Opcode::I64ReinterpretF64 => I64ReinterpretF64,
+            Opcode::F32ReinterpretI32 => F32ReinterpretI32,
+            Opcode::F64ReinterpretI64 => F64ReinterpretI64,
+            _ => return None,
+        })
+    }
+
+    // operand and result
+    pub fn types(self) -> (Type, Type) {
+        use Cvtop::*;
+        use Type::*;
+        match self {
+            I32WrapI64 => (I64, I32),
+            I64ExtendI32S | I64ExtendI32U => (I32, I64),
+            I32TruncF32S | I32TruncF32U | I32TruncSatF32S | I32TruncSatF32U => (F32, I32),
+            I32TruncF64S | I32TruncF64U | I32TruncSatF64S | I32TruncSatF64U => (F64, I32),
+            I64TruncF32S | I64TruncF32U | I64TruncSatF32S | I64TruncSatF32U => (F32, I64),
+            I64TruncF64S | I64TruncF64U | I64TruncSatF64S | I64TruncSatF64U => (F64, I64),
+            F32ConvertI32S | F32ConvertI32U => (I32, F32),
+            F32ConvertI64S | F32ConvertI64U => (I64, F32),
+            F32DemoteF64 => (F64, F32),
+            F64ConvertI32S | F64ConvertI32U => (I32, F64),
+            F64ConvertI64S | F64ConvertI64U => (I64, F64),
+            F64PromoteF32 => (F32, F64),
+            I32ReinterpretF32 => (F32, I32),
+            I64ReinterpretF64 => (F64, I64),
+            F32ReinterpretI32 => (I32, F32),
+            F64ReinterpretI64 => (I64, F64),
+        }
+    }
+
+    pub fn eval(self, x: Slot) -> Result<Slot, TrapKind> {
+        use Cvtop::*;
+        // f32 -> f64 is exact, so one range check does for both widths
+        const I32_MIN: f64 = -2147483648.0;
+        const U32_END: f64 = 4294967296.0;
+        const I64_MIN: f64 = -9223372036854775808.0;
+        const U64_END: f64 = 18446744073709551616.0;
+        Ok(match self {
+            I32WrapI64 => Slot::from(x.i64() as i32),
+            I64ExtendI32S => Slot::from(x.i32() as i64),
+            I64ExtendI32U => Slot::from(x.i32() as u32 as i64),
+            I32TruncF32S => Slot::from(trunc(x.f32() as f64, I32_MIN, -I32_MIN)? as i32),
+            I32TruncF32U => Slot::from(trunc(x.f32() as f64, 0.0, U32_END)? as u32 as i32),
+            I32TruncF64S => Slot::from(trunc(x.f64(), I32_MIN, -I32_MIN)? as i32),
+            I32TruncF64U => Slot::from(trunc(x.f64(), 0.0, U32_END)? as u32 as i32),
+            I64TruncF32S => Slot::from(trunc(x.f32() as f64, I64_MIN, -I64_MIN)? as i64),
+            I64TruncF32U => Slot::from(trunc(x.f32() as f64, 0.0, U64_END)? as u64 as i64),
+            I64TruncF64S => Slot::from(trunc(x.f64(), I64_MIN, -I64_MIN)? as i64),
+            I64TruncF64U => Slot::from(trunc(x.f64(), 0.0, U64_END)? as u64 as i64),
+            // Rust's float to int casts saturate and take NaN to 0, as these do
+            I32TruncSatF32S => Slot::from(x.f32() as i32),
+            I32TruncSatF32U => Slot::from(x.f32() as u32 as i32),
+            I32TruncSatF64S => Slot::from(x.f64() as i32),
+            I32TruncSatF64U => Slot::from(x.f64() as u32 as i32),
+            I64TruncSatF32S => Slot::from(x.f32() as i64),
+            I64TruncSatF32U => Slot::from(x.f32() as u64 as i64),
+            I64TruncSatF64S => Slot::from(x.f64() as i64),
+            I64TruncSatF64U => Slot::from(x.f64() as u64 as i64),
+            // Rust's int to float casts round to nearest, ties to even
+            F32ConvertI32S => Slot::from(x.i32() as f32),
+            F32ConvertI32U => Slot::from(x.i32() as u32 as f32),
+            F32ConvertI64S => Slot::from(x.i64() as f32),
+            F32ConvertI64U => Slot::from(x.i64() as u64 as f32),
+            F32DemoteF64 => Slot::from(x.f64() as f32),
+            F64ConvertI32S => Slot::from(x.i32() as f64),
+            F64ConvertI32U => Slot::from(x.i32() as u32 as f64),
+            F64ConvertI64S => Slot::from(x.i64() as f64),
+            F64ConvertI64U => Slot::from(x.i64() as u64 as f64),
+            F64PromoteF32 => Slot::from(x.f32() as f64),
+            // same bits, and slots don't know their type
+            I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => x,
+        })
+    }
+}
+
+// x truncated toward zero, which must land in [min, end)
+fn trunc(x: f64, min: f64, end: f64) -> Result<f64, TrapKind> {
+    if x.is_nan() {
+        return Err(TrapKind::InvalidConversion);
+    }
+    let t = x.trunc();
+    if t < min || t >= end {
+        return Err(TrapKind::IntOverflow);
+    }
+    Ok(t)
+}
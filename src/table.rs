@@ -0,0 +1,86 @@
+use crate::TrapKind;
+
+use std::ops::Range;
+
+// Tables. Elements are references, funcref and externref alike: a function
+// index or a host handle, None for null.
+
+// an implementation limit on growth, which the spec leaves to us
+pub const MAX_TABLE_SIZE: u32 = 10_000_000;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefTable {
+    pub elems: Vec<Option<usize>>,
+    pub max: u32,
+}
+
+impl RefTable {
+    pub fn new(min: u32, max: Option<u32>) -> Self {
+        RefTable { elems: vec![None; min as usize], max: max.unwrap_or(MAX_TABLE_SIZE).min(MAX_TABLE_SIZE) }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.elems.len() as u32
+    }
+
+    // the old size, or -1 if it can't grow that far
+    pub fn grow(&mut self, delta: u32, init: Option<usize>) -> i32 {
+        let old = self.size();
+        match old.checked_add(delta) {
+            Some(new) if new <= self.max => {
+                self.elems.resize(new as usize, init);
+                old as i32
+            }
+            _ => -1,
+        }
+    }
+
+    pub fn get(&self, idx: i32) -> Result<Option<usize>, TrapKind> {
+        self.elems.get(idx as u32 as usize).copied().ok_or(TrapKind::TableOutOfBounds)
+    }
+
+    pub fn set(&mut self, idx: i32, val: Option<usize>) -> Result<(), TrapKind> {
+        let elem = self.elems.get_mut(idx as u32 as usize).ok_or(TrapKind::TableOutOfBounds)?;
+        *elem = val;
+        Ok(())
+    }
+
+    pub fn fill(&mut self, dst: i32, val: Option<usize>, len: i32) -> Result<(), TrapKind> {
+        let dst = range(self.elems.len(), dst, len, TrapKind::TableOutOfBounds)?;
+        self.elems[dst].fill(val);
+        Ok(())
+    }
+
+    // from an element segment's references
+    pub fn init(&mut self, dst: i32, elem: &[Option<usize>], src: i32, len: i32) -> Result<(), TrapKind> {
+        let src = range(elem.len(), src, len, TrapKind::TableOutOfBounds)?;
+        let dst = range(self.elems.len(), dst, len, TrapKind::TableOutOfBounds)?;
+        self.elems[dst].copy_from_slice(&elem[src]);
+        Ok(())
+    }
+}
+
+// both ranges are checked before anything moves, and the tables may be the
+// same one with the ranges overlapping
+pub fn copy(tables: &mut [RefTable], dst_table: usize, src_table: usize, dst: i32, src: i32, len: i32) -> Result<(), TrapKind> {
+    let src = range(tables[src_table].elems.len(), src, len, TrapKind::TableOutOfBounds)?;
+    let dst = range(tables[dst_table].elems.len(), dst, len, TrapKind::TableOutOfBounds)?;
+    if dst_table == src_table {
+        tables[dst_table].elems.copy_within(src, dst.start);
+    } else {
+        let elems = tables[src_table].elems[src].to_vec();
+        tables[dst_table].elems[dst].copy_from_slice(&elems);
+    }
+    Ok(())
+}
+
+// a bulk op's range, for memory's too: start and len are unsigned, and the
+// end is computed without wrapping
+pub fn range(size: usize, start: i32, len: i32, trap: TrapKind) -> Result<Range<usize>, TrapKind> {
+    let start = start as u32 as u64;
+    let end = start + len as u32 as u64;
+    if end > size as u64 {
+        return Err(trap);
+    }
+    Ok(start as usize..end as usize)
+}
@@ -1,93 +1,2048 @@
 use crate::tf::{TypedEval, TypedValidate, CBD};
-use crate::{CodePtr, SidetableMeta, CtlType, CtlEntry, Type, sum_code};
-
-#[cfg(test)]
-impl TypedEval {
-    fn run(&mut self) {
-        self.pushi(5);
-        self.pushi(-15);
-        self.pushi(20);
-        let x_1 = self.stack.pop().unwrap();
-        let x_2 = self.stack.pop().unwrap();
-        let x_3 = x_1 + x_2;
-        self.stack.push(x_3);
-        let x_4 = self.stack.pop().unwrap();
-        let x_5 = self.stack.pop().unwrap();
-        let x_6 = x_4 + x_5;
-        self.stack.push(x_6);
-
-        self.stp += 1;
-        let ste = self.sidetable[self.stp];
-        // stupid casts
-        self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
-        self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
-
-        self.pushi(-999);
-        let x_7 = self.stack.pop().unwrap();
-        self.locals[0 as usize] = x_7;
-        self.pushi(0);
-        let x_8 = self.stack.pop().unwrap();
-        self.locals[1 as usize] = x_8;
-        let x_9 = self.locals[0 as usize];
-        self.stack.push(x_9);
-        let x_10 = self.locals[1 as usize];
-        self.stack.push(x_10);
-        let x_11 = self.stack.pop().unwrap();
-        let x_12 = self.stack.pop().unwrap();
-        let x_13 = x_11 + x_12;
-        self.stack.push(x_13);
-        let x_14 = self.stack.pop().unwrap();
-        self.locals[1 as usize] = x_14;
-        let x_15 = self.locals[0 as usize];
-        self.stack.push(x_15);
-        self.pushi(-1);
-        let x_16 = self.stack.pop().unwrap();
-        let x_17 = self.stack.pop().unwrap();
-        let x_18 = x_16 + x_17;
-        self.stack.push(x_18);
-        let x_19 = self.stack.pop().unwrap();
-        self.locals[0 as usize] = x_19;
-        let x_20 = self.locals[0 as usize];
-        self.stack.push(x_20);
-        let x_21 = self.stack.pop().unwrap();
-        let x_22 = x_21 == 0;
-        self.stp += 1;
-
-        self.stp += 1;
-        let ste = self.sidetable[self.stp];
-        // stupid casts
-        self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
-        self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
-
-        let x_23 = self.locals[1 as usize];
-        self.stack.push(x_23);
-
-    }
-}
-
-#[test]
-fn test_compile() {
-    let nlocals = 2;
-    let code = sum_code();
-    let mut validate = TypedValidate {
+use crate::{CodePtr, CodeEntry, BlockSig, Opcode, Type, Eval, Run, STEntry, Trap, TrapKind, sum_code};
+use crate::frfr::CBD_FR;
+use crate::{ValidationError, ValidationErrorKind};
+use crate::frfr::EvalFR;
+use crate::cps::{WASMFun, CPSEval};
+use crate::decode::{decode, DecodeError, MAX_LOCALS};
+use crate::wat::parse_module;
+use crate::disasm::disassemble;
+use crate::module::{ConstExpr, Data, DataMode, Elem, ElemMode, Export, ExportDesc, FuncType, Global, GlobalType, Import, ImportDesc, LinkError, Memory, Module, Table};
+use crate::instance::{Extern, Instance, Store};
+use crate::embed::{CallError, Value};
+use crate::mem::PAGE_SIZE;
+use crate::mem::LinearMemory;
+use crate::num::Slot;
+
+// sum_code() with a trailing function `end`, as produced by a compiler
+const SUM_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F, 0x03,
+    0x02, 0x01, 0x00, 0x0A, 0x35, 0x01, 0x33, 0x01, 0x02, 0x7F, 0x02, 0x40, 0x41, 0x05, 0x41, 0x71,
+    0x41, 0x14, 0x6A, 0x6A, 0x21, 0x00, 0x0C, 0x00, 0x41, 0x99, 0x78, 0x21, 0x00, 0x0B, 0x41, 0x00,
+    0x21, 0x01, 0x03, 0x40, 0x20, 0x00, 0x20, 0x01, 0x6A, 0x21, 0x01, 0x20, 0x00, 0x41, 0x7F, 0x6A,
+    0x21, 0x00, 0x20, 0x00, 0x0D, 0x00, 0x0B, 0x20, 0x01, 0x0B,
+];
+
+// a block type by type index
+const BLOCK_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0A, 0x02, 0x60, 0x00, 0x01, 0x7F, 0x60,
+    0x01, 0x7F, 0x01, 0x7F, 0x03, 0x02, 0x01, 0x00, 0x0A, 0x0C, 0x01, 0x0A, 0x00, 0x41, 0x03, 0x02,
+    0x01, 0x41, 0x04, 0x6A, 0x0B, 0x0B,
+];
+
+// if and else
+const IF_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F, 0x03,
+    0x02, 0x01, 0x00, 0x0A, 0x0E, 0x01, 0x0C, 0x00, 0x41, 0x01, 0x04, 0x7F, 0x41, 0x0A, 0x05, 0x41,
+    0x14, 0x0B, 0x0B,
+];
+
+// br_table's label vector
+const BR_TABLE_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02,
+    0x01, 0x00, 0x0A, 0x0E, 0x01, 0x0C, 0x00, 0x02, 0x40, 0x41, 0x00, 0x0E, 0x02, 0x00, 0x01, 0x00,
+    0x0B, 0x0B,
+];
+
+// call and return
+const CALL_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7F,
+    0x03, 0x03, 0x02, 0x00, 0x00, 0x0A, 0x0E, 0x02, 0x07, 0x00, 0x20, 0x00, 0x10, 0x01, 0x0F, 0x0B,
+    0x04, 0x00, 0x20, 0x00, 0x0B,
+];
+
+// a table, an active element segment and call_indirect
+const INDIRECT_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7F,
+    0x03, 0x03, 0x02, 0x00, 0x00, 0x04, 0x04, 0x01, 0x70, 0x00, 0x02, 0x09, 0x07, 0x01, 0x00, 0x41,
+    0x00, 0x0B, 0x01, 0x00, 0x0A, 0x10, 0x02, 0x04, 0x00, 0x20, 0x00, 0x0B, 0x09, 0x00, 0x20, 0x00,
+    0x41, 0x00, 0x11, 0x00, 0x00, 0x0B,
+];
+
+// a memory, loads with memargs and memory.grow
+const MEMORY_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7F,
+    0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0A, 0x0E, 0x01, 0x0C, 0x00, 0x20, 0x00,
+    0x2D, 0x00, 0x02, 0x41, 0x00, 0x40, 0x00, 0x6A, 0x0B,
+];
+
+// a mutable global, global.get and global.set
+const GLOBAL_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F, 0x03,
+    0x02, 0x01, 0x00, 0x06, 0x06, 0x01, 0x7F, 0x01, 0x41, 0x2A, 0x0B, 0x0A, 0x0D, 0x01, 0x0B, 0x00,
+    0x23, 0x00, 0x41, 0x01, 0x6A, 0x24, 0x00, 0x23, 0x00, 0x0B,
+];
+
+// i64 types, globals and constants
+const I64_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7E,
+    0x03, 0x02, 0x01, 0x00, 0x06, 0x06, 0x01, 0x7E, 0x00, 0x42, 0x7B, 0x0B, 0x0A, 0x0E, 0x01, 0x0C,
+    0x00, 0x20, 0x00, 0xAC, 0x42, 0x80, 0x80, 0x80, 0x80, 0x10, 0x7E, 0x0B,
+];
+
+// f32 and f64 types, globals and constants
+const FLOAT_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7C, 0x03,
+    0x02, 0x01, 0x00, 0x06, 0x09, 0x01, 0x7D, 0x00, 0x43, 0x00, 0x00, 0xC0, 0xBF, 0x0B, 0x0A, 0x11,
+    0x01, 0x0F, 0x00, 0x23, 0x00, 0xBB, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xD0, 0x3F, 0xA0,
+    0x0B,
+];
+
+// 0xFC prefixed opcodes, the sub-opcode as a padded LEB
+const SAT_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7F,
+    0x03, 0x02, 0x01, 0x00, 0x0A, 0x0B, 0x01, 0x09, 0x00, 0x20, 0x00, 0xB7, 0xFC, 0x82, 0x00, 0xC0,
+    0x0B,
+];
+
+// typed select, local.tee and nop
+const SELECT_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7F,
+    0x03, 0x02, 0x01, 0x00, 0x0A, 0x10, 0x01, 0x0E, 0x00, 0x41, 0x05, 0x22, 0x00, 0x20, 0x00, 0x20,
+    0x00, 0x1C, 0x01, 0x7F, 0x01, 0x0B,
+];
+
+// a passive element segment of expressions and table ops
+const REF_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F, 0x03,
+    0x02, 0x01, 0x00, 0x04, 0x04, 0x01, 0x70, 0x00, 0x01, 0x09, 0x07, 0x01, 0x05, 0x70, 0x01, 0xD2,
+    0x00, 0x0B, 0x0A, 0x13, 0x01, 0x11, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x01, 0xFC, 0x0C, 0x00,
+    0x00, 0x41, 0x00, 0x25, 0x00, 0xD1, 0x0B,
+];
+
+// an imported function, an export and a start function
+const IMPORT_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x02, 0x60, 0x01, 0x7F, 0x01, 0x7F,
+    0x60, 0x00, 0x00, 0x02, 0x09, 0x01, 0x03, 0x65, 0x6E, 0x76, 0x01, 0x66, 0x00, 0x00, 0x03, 0x03,
+    0x02, 0x00, 0x01, 0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x01, 0x08, 0x01, 0x02, 0x0A, 0x0B, 0x02,
+    0x06, 0x00, 0x20, 0x00, 0x10, 0x00, 0x0B, 0x02, 0x00, 0x0B,
+];
+
+// active and passive data segments, a data count and memory.init
+const DATA_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F, 0x03,
+    0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0C, 0x01, 0x02, 0x0A, 0x16, 0x01, 0x14, 0x00,
+    0x41, 0x00, 0x41, 0x01, 0x41, 0x01, 0xFC, 0x08, 0x01, 0x00, 0xFC, 0x09, 0x01, 0x41, 0x00, 0x2D,
+    0x00, 0x00, 0x0B, 0x0B, 0x0B, 0x02, 0x00, 0x41, 0x01, 0x0B, 0x01, 0x2A, 0x01, 0x02, 0x07, 0x08,
+];
+
+// memory.copy and memory.fill, each with its memory indices
+const BULK_WASM: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02,
+    0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0A, 0x17, 0x01, 0x15, 0x00, 0x41, 0x00, 0x41, 0x01,
+    0x41, 0x02, 0xFC, 0x0A, 0x00, 0x00, 0x41, 0x00, 0x41, 0x01, 0x41, 0x02, 0xFC, 0x0B, 0x00, 0x0B,
+];
+
+#[test]
+fn test_decode() {
+    let module = decode(SUM_WASM).unwrap();
+    assert_eq!(module.types, vec![FuncType { params: vec![], results: vec![Type::I32] }]);
+    assert_eq!(module.funcs.len(), 1);
+    assert_eq!(module.local_types(0), vec![Type::I32; 2]);
+
+    let mut expected = sum_code();
+    expected.push(CodeEntry::Op(Opcode::End));
+    assert_eq!(module.funcs[0].code, expected);
+
+    let mut validate = TypedValidate::from_module(&module, 0);
+    validate.dispatch().unwrap();
+    let sidetable = validate.build_sidetable();
+
+    let mut eval = Eval {
         stack: vec![],
-        locals: vec![Type::I32; nlocals],
-        ctl_entries: vec![CtlEntry { tipe: CtlType::Func, cont_ip: code.len(), cont_stp: 0 }],
-        ctl_stack: vec![0],
-        codeptr: CodePtr { code: code.clone(), ip: 0 },
-        sidetable_meta: vec![SidetableMeta { br_ip: 0, target_ctl_idx: 0 } ],
+        locals: vec![Slot::default(); 2],
+        codeptr: module.codeptr(0),
+        sidetable: sidetable.clone(),
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
+    };
+    eval.dispatch().unwrap();
+    assert_eq!(eval.stack, vec![Slot::from(55)]);
+
+    let mut teval = TypedEval {
+        stack: vec![],
+        locals: vec![Slot::default(); 2],
+        codeptr: module.codeptr(0),
+        sidetable: sidetable.clone(),
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
+    };
+    teval.dispatch().unwrap();
+    assert_eq!(teval.stack, vec![Slot::from(55)]);
+
+    let mut fr_eval = EvalFR {
+        stack: vec![],
+        locals: vec![Slot::default(); 2],
+        codeptr: module.codeptr(0),
+        sidetable: sidetable.clone(),
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
+    };
+    fr_eval.run().unwrap();
+    assert_eq!(fr_eval.stack, vec![Slot::from(55)]);
+
+    let mut wasm_fun = WASMFun::new(module.funcs[0].code.clone(), &sidetable);
+    let interpreter = wasm_fun.run(CPSEval { stack: vec![], locals: vec![Slot::default(); 2], globals: vec![], trap: None }).unwrap();
+    assert_eq!(interpreter.stack, vec![Slot::from(55)]);
+
+    // the rest decode to what their text parses to
+    let cases: &[(&[u8], &str)] = &[
+        (BLOCK_WASM, r#"
+            (type (func (result i32))) (type $t (func (param i32) (result i32)))
+            (func (type 0) i32.const 3 (block (type $t) (i32.add (i32.const 4))))"#),
+        (IF_WASM, "(func (result i32) i32.const 1 if (result i32) i32.const 10 else i32.const 20 end)"),
+        (BR_TABLE_WASM, "(func block i32.const 0 br_table 0 1 0 end)"),
+        (CALL_WASM, "(func (param i32) (result i32) (call 1 (local.get 0)) return) (func (param i32) (result i32) (local.get 0))"),
+        (INDIRECT_WASM, r#"
+            (type (func (param i32) (result i32)))
+            (table 2 funcref)
+            (elem (i32.const 0) 0)
+            (func (param i32) (result i32) local.get 0)
+            (func (param i32) (result i32) local.get 0 i32.const 0 call_indirect (type 0))"#),
+        (MEMORY_WASM, r#"
+            (memory 1)
+            (func (param i32) (result i32)
+              (i32.load8_u offset=2 (local.get 0)) (memory.grow (i32.const 0)) i32.add)"#),
+        (GLOBAL_WASM, r#"
+            (global (mut i32) (i32.const 42))
+            (func (result i32) global.get 0 i32.const 1 i32.add global.set 0 global.get 0)"#),
+        (I64_WASM, r#"
+            (global i64 (i64.const -5))
+            (func (param i32) (result i64) (i64.mul (i64.extend_i32_s (local.get 0)) (i64.const 0x100000000)))"#),
+        (FLOAT_WASM, r#"
+            (global f32 (f32.const -1.5))
+            (func (result f64) (f64.add (f64.promote_f32 (global.get 0)) (f64.const 0.25)))"#),
+        (SAT_WASM, "(func (param i32) (result i32) local.get 0 f64.convert_i32_s i32.trunc_sat_f64_s i32.extend8_s)"),
+        (SELECT_WASM, "(func (param i32) (result i32) (select (result i32) (local.tee 0 (i32.const 5)) (local.get 0) (local.get 0)) nop)"),
+        (REF_WASM, r#"
+            (table 1 funcref)
+            (elem funcref (ref.func 0))
+            (func (result i32) (table.init 0 0 (i32.const 0) (i32.const 0) (i32.const 1)) (ref.is_null (table.get 0 (i32.const 0))))"#),
+        (IMPORT_WASM, r#"
+            (import "env" "f" (func (param i32) (result i32)))
+            (func (export "g") (param i32) (result i32) (call 0 (local.get 0)))
+            (func $s)
+            (start $s)"#),
+        (DATA_WASM, r#"
+            (memory 1)
+            (data (i32.const 1) "\2a")
+            (data "\07\08")
+            (func (result i32)
+              (memory.init 1 (i32.const 0) (i32.const 1) (i32.const 1)) (data.drop 1) (i32.load8_u (i32.const 0)))"#),
+        (BULK_WASM, r#"
+            (memory 1)
+            (func (memory.copy (i32.const 0) (i32.const 1) (i32.const 2)) (memory.fill (i32.const 0) (i32.const 1) (i32.const 2)))"#),
+    ];
+    for (wasm, wat) in cases {
+        assert_eq!(decode(wasm).unwrap(), parse_module(wat).unwrap(), "{wat}");
+    }
+    assert_eq!(Opcode::from_prefixed(0xFC, 2), Some(Opcode::I32TruncSatF64S));
+    assert_eq!(Opcode::I32TruncSatF64S.sub_opcode(), Some(2));
+    assert_eq!(Opcode::from_byte(0xFC), None);
+}
+
+#[test]
+fn test_decode_errors() {
+    assert_eq!(decode(b"\0asn\x01\0\0\0").unwrap_err(), DecodeError::BadMagic);
+    assert_eq!(decode(b"\0asm\x02\0\0\0").unwrap_err(), DecodeError::BadVersion(2));
+
+    let mut bad_op = SUM_WASM.to_vec();
+    bad_op[28] = 0xFF; // first i32.const
+    assert_eq!(decode(&bad_op).unwrap_err(), DecodeError::UnknownOpcode { byte: 0xFF, offset: 28 });
+    assert_eq!(decode(&bad_op).unwrap_err().to_string(), "unknown opcode 0xff at 28");
+
+    let truncated = &SUM_WASM[..SUM_WASM.len() - 1];
+    assert!(matches!(decode(truncated).unwrap_err(), DecodeError::UnexpectedEof(_)));
+
+    // empty sections after the header, at offset 8
+    let sections = |bytes: &[u8]| decode(&[b"\0asm\x01\0\0\0".as_slice(), bytes].concat()).map(|_| ());
+    assert_eq!(sections(&[0x0D, 0x00]), Err(DecodeError::UnknownSection { id: 13, offset: 8 }));
+    assert_eq!(sections(&[0x01, 0x01, 0x00, 0x01, 0x01, 0x00]), Err(DecodeError::DuplicateSection { id: 1, offset: 11 }));
+    assert_eq!(sections(&[0x03, 0x01, 0x00, 0x01, 0x01, 0x00]), Err(DecodeError::SectionOutOfOrder { id: 1, offset: 11 }));
+    assert_eq!(sections(&[0x0A, 0x01, 0x00, 0x0C, 0x01, 0x00]), Err(DecodeError::SectionOutOfOrder { id: 12, offset: 11 }));
+    // data count goes between elements and code, custom sections anywhere
+    assert_eq!(sections(&[0x09, 0x01, 0x00, 0x0C, 0x01, 0x00, 0x0A, 0x01, 0x00, 0x0B, 0x01, 0x00]), Ok(()));
+    assert_eq!(sections(&[0x01, 0x01, 0x00, 0x00, 0x02, 0x01, b'x', 0x00, 0x01, 0x00, 0x03, 0x01, 0x00]), Ok(()));
+
+    // one function whose body, at offset 22, is `body` then end
+    let func_body = |body: &[u8]| {
+        let mut wasm = b"\0asm\x01\0\0\0\x01\x04\x01\x60\0\0\x03\x02\x01\0".to_vec();
+        let size = body.len() as u8 + 1;
+        wasm.extend([0x0A, size + 2, 0x01, size]);
+        wasm.extend(body);
+        wasm.push(0x0B);
+        decode(&wasm)
     };
-    validate.dispatch();
-    // dbg!(&validate.ctl_stack);
-    // dbg!(&validate.ctl_entries);
+    assert_eq!(func_body(&[0x01, 0xD0, 0x86, 0x03, 0x7F]).unwrap().funcs[0].locals.len(), MAX_LOCALS as usize);
+    assert_eq!(func_body(&[0x01, 0xD1, 0x86, 0x03, 0x7F]).unwrap_err(), DecodeError::TooManyLocals(22));
+    // the counts are added without wrapping
+    let huge = [0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x7F, 0x01, 0x7F];
+    assert_eq!(func_body(&huge).unwrap_err(), DecodeError::TooManyLocals(22));
+
+    // the last byte of a LEB128 may only hold what's left of the integer
+    assert_eq!(func_body(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 0x7F]).unwrap_err(), DecodeError::LebUnusedBits(23));
+    let i32_const = |leb: &[u8]| func_body(&[&[0x00, 0x41], leb].concat()).map(|m| m.funcs[0].code[1].clone());
+    assert_eq!(i32_const(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F]), Ok(CodeEntry::I32Imm(-1)));
+    assert_eq!(i32_const(&[0x80, 0x80, 0x80, 0x80, 0x78]), Ok(CodeEntry::I32Imm(i32::MIN)));
+    assert_eq!(i32_const(&[0xFF, 0xFF, 0xFF, 0xFF, 0x07]), Ok(CodeEntry::I32Imm(i32::MAX)));
+    assert_eq!(i32_const(&[0x80, 0x80, 0x80, 0x80, 0x08]), Err(DecodeError::LebUnusedBits(24)));
+    assert_eq!(i32_const(&[0xFF, 0xFF, 0xFF, 0xFF, 0x4F]), Err(DecodeError::LebUnusedBits(24)));
+    let i64_const = |leb: &[u8]| func_body(&[&[0x00, 0x42], leb].concat()).map(|m| m.funcs[0].code[1].clone());
+    assert_eq!(i64_const(&[[0xFF; 9].as_slice(), &[0x7F]].concat()), Ok(CodeEntry::I64Imm(-1)));
+    assert_eq!(i64_const(&[[0xFF; 9].as_slice(), &[0x00]].concat()), Ok(CodeEntry::I64Imm(i64::MAX)));
+    assert_eq!(i64_const(&[[0xFF; 9].as_slice(), &[0x01]].concat()), Err(DecodeError::LebUnusedBits(24)));
+
+    // memory.grow's memory index
+    let mut bad_index = MEMORY_WASM.to_vec();
+    bad_index[38] = 0x01;
+    assert_eq!(decode(&bad_index).unwrap_err(), DecodeError::BadMemoryIndex { byte: 1, offset: 38 });
+
+    // a global's mutability flag
+    let mut bad_mut = GLOBAL_WASM.to_vec();
+    bad_mut[23] = 0x02;
+    assert_eq!(decode(&bad_mut).unwrap_err(), DecodeError::BadMutability { byte: 2, offset: 23 });
+
+    let mut bad_sub = SAT_WASM.to_vec();
+    bad_sub[29] = 0xE3; // 99
+    assert_eq!(decode(&bad_sub).unwrap_err(), DecodeError::UnknownPrefixedOpcode { prefix: 0xFC, sub: 99, offset: 28 });
+
+    // a typed select takes exactly one type
+    let mut no_types = SELECT_WASM.to_vec();
+    no_types[34] = 0;
+    assert_eq!(decode(&no_types).unwrap_err(), DecodeError::BadSelectTypes(34));
+
+    // an import's kind
+    let mut bad_kind = IMPORT_WASM.to_vec();
+    bad_kind[28] = 0x04;
+    assert_eq!(decode(&bad_kind).unwrap_err(), DecodeError::BadExternKind { byte: 4, offset: 28 });
+
+    // memory.init's memory index, a data segment's flags and the data count
+    let mut bad_index = DATA_WASM.to_vec();
+    bad_index[41] = 0x01;
+    assert_eq!(decode(&bad_index).unwrap_err(), DecodeError::BadMemoryIndex { byte: 1, offset: 41 });
+    let mut bad_flags = DATA_WASM.to_vec();
+    bad_flags[54] = 0x03;
+    assert_eq!(decode(&bad_flags).unwrap_err(), DecodeError::UnsupportedDataSegment { flags: 3, offset: 54 });
+    let mut bad_count = DATA_WASM.to_vec();
+    bad_count[26] = 0x03;
+    assert_eq!(decode(&bad_count).unwrap_err(), DecodeError::DataCountMismatch { count: 3, datas: 2 });
+
+    // memory.copy's two memory indices and memory.fill's one
+    for offset in [36, 37, 46] {
+        let mut bad_index = BULK_WASM.to_vec();
+        bad_index[offset] = 0x01;
+        assert_eq!(decode(&bad_index).unwrap_err(), DecodeError::BadMemoryIndex { byte: 1, offset });
+    }
+}
+
+const SUM_WAT: &str = r#"
+(module
+  (func (result i32) (local $i i32) (local $acc i32)
+    block $init
+      i32.const 5
+      i32.const -15
+      i32.const 20
+      i32.add
+      i32.add
+      local.set $i
+      br $init
+      i32.const -999
+      local.set $i
+    end
+
+    i32.const 0 ;; accumulator
+    local.set $acc
+
+    loop $top
+      (local.set $acc (i32.add (local.get $i) (local.get $acc)))
+      (local.set $i (i32.add (local.get $i) (i32.const -1)))
+      (br_if $top (local.get $i))
+    end
+    local.get $acc))
+"#;
+
+#[test]
+fn test_wat() {
+    let from_text = parse_module(SUM_WAT).unwrap();
+    let from_binary = decode(SUM_WASM).unwrap();
+    assert_eq!(from_text.types, from_binary.types);
+    assert_eq!(from_text.funcs[0].locals, from_binary.funcs[0].locals);
+    assert_eq!(from_text.funcs[0].code, from_binary.funcs[0].code);
+
+    // folded blocks, numeric labels and a bare func without (module ...)
+    let folded = parse_module("(func (block (br 0 (; unused ;)) (loop $l (br_if 1 (i32.const 0x1)))))").unwrap();
+    let flat = parse_module("(func block br 0 loop $l i32.const 1 br_if 1 end end)").unwrap();
+    assert_eq!(folded.funcs[0].code, flat.funcs[0].code);
+
+    let e = parse_module("(func\n  br $nope)").unwrap_err();
+    assert_eq!((e.line, e.col), (2, 6));
+    assert!(parse_module("(func local.get 0)").is_err());
+    assert!(parse_module("(func block)").is_err());
+}
+
+#[test]
+fn test_disasm() {
+    let module = decode(SUM_WASM).unwrap();
+    let mut validate = TypedValidate::from_module(&module, 0);
+    validate.dispatch().unwrap();
+    let sidetable = validate.build_sidetable();
+    let wasm_fun = WASMFun::new(module.funcs[0].code.clone(), &sidetable);
+
+    let text = disassemble(&module.codeptr(0), &sidetable, &wasm_fun.cont_blocks);
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines.contains(&"    4:     i32.const -15"));
+    assert!(lines.contains(&"   12:     br 0                 ;; st[1] ip_delta=+5 stp_delta=+0 -> ip 19, stp 1"));
+    assert!(lines.contains(&"   41:     br_if 0              ;; st[2] ip_delta=-18 stp_delta=-1 -> ip 25, stp 1"));
+    assert!(lines.contains(&"       ;; ---- cont_block 3 (br_tgt 3) ----"));
+    assert_eq!(lines.last(), Some(&"       ;; ---- cont_block 6 (br_tgt 0) ----"));
+}
+
+// runs a module's first function on every evaluator, checking they agree
+fn run_everywhere<T: From<Slot>>(module: &Module) -> Result<Vec<T>, Trap> {
+    let mut validate = TypedValidate::from_module(module, 0);
+    validate.dispatch().unwrap();
     let sidetable = validate.build_sidetable();
+    run_unvalidated(module.funcs[0].code.clone(), module.local_types(0).len(), sidetable)
+}
+
+fn run_unvalidated<T: From<Slot>>(code: Vec<CodeEntry>, nlocals: usize, sidetable: Vec<STEntry>) -> Result<Vec<T>, Trap> {
+    let res: Result<Vec<Slot>, Trap> = run_sidetable(code.clone(), nlocals, sidetable.clone());
+
+    let mut wasm_fun = WASMFun::new(code, &sidetable);
+    let cps_eval = wasm_fun.run(CPSEval { stack: vec![], locals: vec![Slot::default(); nlocals], globals: vec![], trap: None });
+    assert_eq!(cps_eval.map(|i| i.stack), res);
+
+    typed(res)
+}
+
+// a result stack read as the type the test expects, the slots alone don't say
+fn typed<T: From<Slot>>(res: Result<Vec<Slot>, Trap>) -> Result<Vec<T>, Trap> {
+    res.map(|stack| stack.into_iter().map(T::from).collect())
+}
+
+// just the evaluators driven by the sidetable
+fn run_sidetable<T: From<Slot>>(code: Vec<CodeEntry>, nlocals: usize, sidetable: Vec<STEntry>) -> Result<Vec<T>, Trap> {
+    let mut eval = Eval {
+        stack: vec![],
+        locals: vec![Slot::default(); nlocals],
+        codeptr: CodePtr { code: code.clone(), ip: 0 },
+        sidetable: sidetable.clone(),
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
+    };
+    let res = eval.dispatch().map(|()| eval.stack);
+
     let mut teval = TypedEval {
         stack: vec![],
-        locals: vec![0; nlocals],
-        codeptr: CodePtr { code: vec![], ip: 0 },
+        locals: vec![Slot::default(); nlocals],
+        codeptr: CodePtr { code: code.clone(), ip: 0 },
+        sidetable: sidetable.clone(),
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
+    };
+    assert_eq!(teval.dispatch().map(|()| teval.stack), res);
+
+    let mut fr_eval = EvalFR {
+        stack: vec![],
+        locals: vec![Slot::default(); nlocals],
+        codeptr: CodePtr { code, ip: 0 },
         sidetable,
         stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: vec![],
+        hosts: vec![],
+        tables: vec![],
+        elems: vec![],
+        sigs: vec![],
+        memory: LinearMemory::default(),
+        datas: vec![],
+        globals: vec![],
+    };
+    assert_eq!(fr_eval.run().map(|()| fr_eval.stack), res);
+
+    typed(res)
+}
+
+#[test]
+fn test_i32_ops() {
+    let cases: &[(&str, i32)] = &[
+        ("(i32.sub (i32.const 3) (i32.const 5))", -2),
+        ("(i32.sub (i32.const 0x80000000) (i32.const 1))", i32::MAX),
+        ("(i32.mul (i32.const 0x7fffffff) (i32.const 2))", -2),
+        ("(i32.div_s (i32.const -7) (i32.const 2))", -3),
+        ("(i32.div_u (i32.const -1) (i32.const 2))", i32::MAX),
+        ("(i32.rem_s (i32.const -7) (i32.const 2))", -1),
+        ("(i32.rem_s (i32.const 0x80000000) (i32.const -1))", 0),
+        ("(i32.rem_u (i32.const -1) (i32.const 10))", 5),
+        ("(i32.and (i32.const 0xff00) (i32.const 0x0ff0))", 0x0f00),
+        ("(i32.or (i32.const 0xff00) (i32.const 0x0ff0))", 0xfff0),
+        ("(i32.xor (i32.const 0xff00) (i32.const 0x0ff0))", 0xf0f0),
+        ("(i32.shl (i32.const 1) (i32.const 33))", 2),
+        ("(i32.shr_s (i32.const -8) (i32.const 1))", -4),
+        ("(i32.shr_u (i32.const -8) (i32.const 28))", 0xf),
+        ("(i32.rotl (i32.const 0x80000001) (i32.const 1))", 3),
+        ("(i32.rotr (i32.const 3) (i32.const 1))", 0x80000001u32 as i32),
+        ("(i32.clz (i32.const 1))", 31),
+        ("(i32.ctz (i32.const 0))", 32),
+        ("(i32.popcnt (i32.const -1))", 32),
+        ("(i32.eqz (i32.const 0))", 1),
+        ("(i32.eq (i32.const 4) (i32.const 4))", 1),
+        ("(i32.ne (i32.const 4) (i32.const 4))", 0),
+        ("(i32.lt_s (i32.const -1) (i32.const 0))", 1),
+        ("(i32.lt_u (i32.const -1) (i32.const 0))", 0),
+        ("(i32.gt_s (i32.const -1) (i32.const 0))", 0),
+        ("(i32.gt_u (i32.const -1) (i32.const 0))", 1),
+        ("(i32.le_s (i32.const 2) (i32.const 2))", 1),
+        ("(i32.le_u (i32.const 3) (i32.const 2))", 0),
+        ("(i32.ge_s (i32.const -3) (i32.const 2))", 0),
+        ("(i32.ge_u (i32.const -3) (i32.const 2))", 1),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i32) {expr})")).unwrap();
+        let stack: Vec<i32> = run_everywhere(&module).unwrap();
+        assert_eq!(stack, vec![*expected], "{expr}");
+    }
+
+    // decoder and disassembler see the new opcodes too
+    let module = parse_module("(func (result i32) (i32.rotr (i32.const 1) (i32.const 2)))").unwrap();
+    assert_eq!(Opcode::from_byte(Opcode::I32Rotr.byte()), Some(Opcode::I32Rotr));
+    let text = disassemble(&module.codeptr(0), &[], &[]);
+    assert!(text.contains("i32.rotr"));
+}
+
+#[test]
+fn test_traps() {
+    let trap = |kind, ip| Err::<Vec<i32>, _>(Trap { kind, ip });
+
+    let module = parse_module("(func (result i32) (i32.div_s (i32.const 1) (i32.const 0)))").unwrap();
+    assert_eq!(run_everywhere(&module), trap(TrapKind::IntDivByZero, 4));
+
+    let module = parse_module("(func (result i32) (i32.div_s (i32.const 0x80000000) (i32.const -1)))").unwrap();
+    assert_eq!(run_everywhere(&module), trap(TrapKind::IntOverflow, 4));
+
+    // trap in the loop body after a few iterations, 10 / (3 - i)
+    let module = parse_module(r#"
+        (func (local $i i32) (local $x i32)
+          (loop $l
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (local.set $x (i32.div_u (i32.const 10) (i32.sub (i32.const 3) (local.get $i))))
+            (br_if $l (i32.const 1))))"#).unwrap();
+    assert_eq!(run_everywhere(&module), trap(TrapKind::IntDivByZero, 16));
+
+    // the validator would reject these, but the evaluators mustn't panic either
+    use CodeEntry::*;
+    use Opcode::*;
+    let underflow = vec![Op(I32Const), I32Imm(1), Op(I32Add)];
+    assert_eq!(run_unvalidated(underflow, 0, vec![]), trap(TrapKind::StackUnderflow, 2));
+    let bad_local = vec![Op(I32Const), I32Imm(1), Op(LocalSet), I32Imm(-1)];
+    assert_eq!(run_unvalidated(bad_local, 1, vec![]), trap(TrapKind::LocalOutOfRange, 2));
+}
+
+#[test]
+fn test_validation_errors() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str, locals: Vec<Type>| {
+        let module = parse_module(wat).unwrap();
+        TypedValidate::new(module.funcs[0].code.clone(), locals, vec![]).dispatch()
+    };
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+
+    assert_eq!(validate("(func (block i32.const 1 i32.add))", vec![]), err(StackUnderflow, 4, 2));
+    // can't reach past the block for operands
+    assert_eq!(validate("(func i32.const 1 (block i32.eqz))", vec![]), err(StackUnderflow, 4, 2));
+    assert_eq!(validate("(func (local i32) (local.set 0 (i32.const 0)))", vec![]), err(UnknownLocal(0), 2, 1));
+    assert_eq!(validate("(func (block i32.const 1 i32.const 2))", vec![]), err(BlockArity { expected: 0, found: 2 }, 6, 2));
+
+    use CodeEntry::*;
+    use Opcode::*;
+    let bad_label = vec![Op(Block), BlockType(BlockSig::Empty), Op(Loop), BlockType(BlockSig::Empty), Op(Br), I32Imm(3), Op(End), Op(End)];
+    assert_eq!(TypedValidate::new(bad_label, vec![], vec![]).dispatch(), err(UnknownLabel(3), 4, 3));
+    let extra_end = vec![Op(Block), BlockType(BlockSig::Empty), Op(End), Op(End), Op(End)];
+    assert_eq!(TypedValidate::new(extra_end, vec![], vec![]).dispatch(), err(UnbalancedEnd, 4, 0));
+    let missing_end = vec![Op(Block), BlockType(BlockSig::Empty), Op(Loop), BlockType(BlockSig::Empty), Op(End)];
+    assert_eq!(TypedValidate::new(missing_end, vec![], vec![]).dispatch(), err(UnbalancedEnd, 5, 2));
+}
+
+#[test]
+fn test_unreachable_validation() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str| {
+        let module = parse_module(wat).unwrap();
+        TypedValidate::new(module.funcs[0].code.clone(), vec![Type::I32], vec![]).dispatch()
+    };
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+
+    // operands of dead code come from the polymorphic stack
+    assert_eq!(validate("(func (local i32) (block br 0 i32.add i32.eqz local.set 0))"), Ok(()));
+    assert_eq!(validate("(func (local i32) (block (br 0 (i32.const 7)) i32.add local.set 0))"), Ok(()));
+    // but the block still can't leave anything behind
+    assert_eq!(validate("(func (block br 0 i32.const 1))"), err(BlockArity { expected: 0, found: 1 }, 6, 2));
+    // and the enclosing block is unaffected
+    assert_eq!(validate("(func (block br 0) i32.eqz)"), err(StackUnderflow, 5, 1));
+    // br_if may fall through, so nothing is dead after it
+    assert_eq!(validate("(func (block (br_if 0 (i32.const 1)) i32.eqz))"), err(StackUnderflow, 6, 2));
+}
+
+#[test]
+fn test_block_types() {
+    // CPSEval doesn't adjust the stack on branches yet, so only the sidetable evaluators
+    let run = |wat: &str| {
+        let module = parse_module(wat).unwrap();
+        let mut validate = TypedValidate::from_module(&module, 0);
+        validate.dispatch().unwrap();
+        run_sidetable(module.funcs[0].code.clone(), module.local_types(0).len(), validate.build_sidetable())
+    };
+
+    // br drops what's under the block's result
+    assert_eq!(run("(func (result i32) (block (result i32) i32.const 1 i32.const 2 i32.const 42 br 0))"), Ok(vec![42]));
+    assert_eq!(run("(func (result i32) (block $b (result i32) i32.const 7 i32.const 9 (br_if $b (i32.const 1)) i32.add))"), Ok(vec![9]));
+    assert_eq!(run("(func (result i32) (block $b (result i32) i32.const 7 i32.const 9 (br_if $b (i32.const 0)) i32.add))"), Ok(vec![16]));
+    // params stay on the stack, and a loop's label carries them
+    assert_eq!(run("(func (result i32) i32.const 3 (block (param i32) (result i32) i32.const 4 i32.add))"), Ok(vec![7]));
+    assert_eq!(run("(type $t (func (param i32) (result i32))) (func (result i32) i32.const 3 (block (type $t) (i32.add (i32.const 4))))"), Ok(vec![7]));
+    assert_eq!(run(r#"
+        (func (result i32) (local $n i32)
+          (local.set $n (i32.const 4))
+          i32.const 0
+          (loop $l (param i32) (result i32)
+            (i32.add (local.get $n))
+            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+            (br_if $l (local.get $n))))"#), Ok(vec![10]));
+
+    let module = parse_module("(func (result i32) (block (result i32) i32.const 1 i32.const 2 br 0))").unwrap();
+    let text = {
+        let mut validate = TypedValidate::from_module(&module, 0);
+        validate.dispatch().unwrap();
+        disassemble(&module.codeptr(0), &validate.build_sidetable(), &[])
+    };
+    assert!(text.contains("block (result i32)"));
+    assert!(text.contains("-> ip 9, stp 1, keep 1 pop 1"));
+}
+
+#[test]
+fn test_block_type_errors() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str| {
+        let module = parse_module(wat).unwrap();
+        TypedValidate::from_module(&module, 0).dispatch()
+    };
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+
+    assert_eq!(validate("(func (block (result i32)))"), err(BlockArity { expected: 1, found: 0 }, 2, 2));
+    assert_eq!(validate("(func (block (result i32) i32.const 1 i32.const 2))"),
+        err(BlockArity { expected: 1, found: 2 }, 6, 2));
+    // the function's own results are checked at its end
+    assert_eq!(validate("(func (result i32))"), err(BlockArity { expected: 1, found: 0 }, 0, 1));
+    // a branch needs the label's values
+    assert_eq!(validate("(func (block (result i32) br 0))"), err(StackUnderflow, 2, 2));
+    // params come from outside the block
+    assert_eq!(validate("(func (block (param i32)))"), err(StackUnderflow, 0, 1));
+
+    use CodeEntry::*;
+    use Opcode::*;
+    let bad_type = vec![Op(Block), BlockType(BlockSig::Index(5)), Op(End)];
+    assert_eq!(TypedValidate::new(bad_type, vec![], vec![]).dispatch(), err(UnknownType(5), 0, 1));
+}
+
+#[test]
+fn test_if_else() {
+    let run = |wat: &str| run_everywhere(&parse_module(wat).unwrap());
+
+    for (cond, res) in [(1, 10), (0, 20)] {
+        let wat = format!("(func (result i32) (if (result i32) (i32.const {cond}) (then (i32.const 10)) (else (i32.const 20))))");
+        assert_eq!(run(&wat), Ok(vec![res]));
+    }
+    // without an else the false edge goes straight to the end
+    for (cond, res) in [(1, 5), (0, 0)] {
+        let wat = format!("(func (result i32) (local $x i32) (if (i32.const {cond}) (then (local.set $x (i32.const 5)))) local.get $x)");
+        assert_eq!(run(&wat), Ok(vec![res]));
+    }
+    // params are passed to whichever arm runs
+    for (cond, res) in [(1, 4), (0, 6)] {
+        let wat = format!(r#"
+            (func (result i32)
+              i32.const 3 i32.const {cond}
+              if (param i32) (result i32) i32.const 1 i32.add else i32.const 2 i32.mul end)"#);
+        assert_eq!(run(&wat), Ok(vec![res]));
+    }
+    // br 0 in an arm leaves the if
+    assert_eq!(run("(func (result i32) (if (result i32) (i32.const 1) (then (br 0 (i32.const 7)) i32.const 8) (else i32.const 9)))"), Ok(vec![7]));
+    // sum of the even numbers below 10, nested in a loop
+    assert_eq!(run(r#"
+        (func (result i32) (local $n i32) (local $sum i32)
+          (local.set $n (i32.const 10))
+          (loop $l
+            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+            (if (i32.rem_u (local.get $n) (i32.const 2))
+              (then)
+              (else (local.set $sum (i32.add (local.get $sum) (local.get $n)))))
+            (br_if $l (local.get $n)))
+          local.get $sum)"#), Ok(vec![20]));
+
+    let module = parse_module("(func (result i32) i32.const 1 if (result i32) i32.const 10 else i32.const 20 end)").unwrap();
+    let mut validate = TypedValidate::from_module(&module, 0);
+    validate.dispatch().unwrap();
+    let text = disassemble(&module.codeptr(0), &validate.build_sidetable(), &[]);
+    let lines: Vec<_> = text.lines().collect();
+    // the false edge lands after the else, the then arm skips to the end
+    assert!(lines[1].starts_with("    2:   if (result i32)"));
+    assert!(lines[1].ends_with("st[1] ip_delta=+3 stp_delta=+1 -> ip 7, stp 2"));
+    assert!(lines[3].starts_with("    6:   else"));
+    assert!(lines[3].ends_with("st[2] ip_delta=+3 stp_delta=+0 -> ip 10, stp 2, keep 1 pop 0"));
+    assert!(lines[4].starts_with("    7:     i32.const 20"));
+}
+
+#[test]
+fn test_if_else_errors() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str| {
+        let module = parse_module(wat).unwrap();
+        TypedValidate::from_module(&module, 0).dispatch()
+    };
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+
+    // the missing else arm would have to produce the result
+    assert_eq!(validate("(func (result i32) (if (result i32) (i32.const 1) (then (i32.const 1))))"),
+        err(BlockArity { expected: 1, found: 0 }, 6, 2));
+    assert_eq!(validate("(func (if (result i32) (i32.const 1) (then) (else (i32.const 1))))"), err(StackUnderflow, 4, 2));
+    assert_eq!(validate("(func (if (i32.const 1) (then (i32.const 1))))"), err(BlockArity { expected: 0, found: 1 }, 6, 2));
+    assert_eq!(validate("(func block else end)"), err(ElseWithoutIf, 2, 2));
+    assert_eq!(validate("(func (if (i32.const 1) (then)))"), Ok(()));
+}
+
+#[test]
+fn test_br_table() {
+    let run = |wat: &str| run_everywhere(&parse_module(wat).unwrap());
+
+    // out of range selectors, negative ones included, take the default
+    for (sel, res) in [(0, 10), (1, 20), (2, 30), (5, 30), (-1, 30)] {
+        let wat = format!(r#"
+            (func (result i32)
+              (block $out (result i32)
+                (block $c
+                  (block $b
+                    (block $a (br_table $a $b $c (i32.const {sel})))
+                    (br $out (i32.const 10)))
+                  (br $out (i32.const 20)))
+                i32.const 30))"#);
+        assert_eq!(run(&wat), Ok(vec![res]), "selector {sel}");
+    }
+    // the targets' values are carried along
+    for (sel, res) in [(0, 107), (1, 7)] {
+        let wat = format!("(func (result i32) (block $a (result i32) (block $b (result i32) (br_table $b $a (i32.const 7) (i32.const {sel}))) (i32.add (i32.const 100))))");
+        assert_eq!(run(&wat), Ok(vec![res]));
+    }
+    // a loop as the default target
+    assert_eq!(run(r#"
+        (func (result i32) (local $n i32) (local $sum i32)
+          (local.set $n (i32.const 5))
+          (block $done
+            (loop $l
+              (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+              (local.set $sum (i32.add (local.get $sum) (local.get $n)))
+              (br_table $done $l (local.get $n))))
+          local.get $sum)"#), Ok(vec![10]));
+
+    // values under the carried ones are dropped, which CPSEval doesn't do yet
+    let module = parse_module("(func (result i32) (block (result i32) i32.const 1 i32.const 2 (br_table 0 0 (i32.const 9) (i32.const 1))))").unwrap();
+    let mut validate = TypedValidate::from_module(&module, 0);
+    validate.dispatch().unwrap();
+    let sidetable = validate.build_sidetable();
+    assert_eq!(run_sidetable(module.funcs[0].code.clone(), 0, sidetable.clone()), Ok(vec![9]));
+    let text = disassemble(&module.codeptr(0), &sidetable, &[]);
+    let lines: Vec<_> = text.lines().collect();
+    assert!(lines[5].starts_with("   10:     br_table 0 0"));
+    assert!(lines[5].ends_with("st[1] ip_delta=+1 stp_delta=+1 -> ip 13, stp 2, keep 1 pop 2"));
+    assert!(lines[6].ends_with("st[2] ip_delta=+1 stp_delta=+0 -> ip 13, stp 2, keep 1 pop 2"));
+
+    // a cont block per target
+    let module = parse_module("(func block i32.const 0 br_table 0 1 0 end)").unwrap();
+    let wasm_fun = WASMFun::new(module.funcs[0].code.clone(), &[]);
+    assert_eq!(wasm_fun.cont_blocks[0].br_tgts.iter().map(|t| t.stp).collect::<Vec<_>>(), vec![2, 3, 2]);
+}
+
+#[test]
+fn test_br_table_errors() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str| {
+        let module = parse_module(wat).unwrap();
+        TypedValidate::from_module(&module, 0).dispatch()
+    };
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+
+    assert_eq!(validate("(func (result i32) (block $a (result i32) (block $b (br_table $a $b (i32.const 1) (i32.const 0))) i32.const 2))"),
+        err(BrTableArity { expected: 0, found: 1 }, 8, 3));
+    // the selector comes off the stack first
+    assert_eq!(validate("(func (result i32) (block (result i32) (br_table 0 1 (i32.const 1))))"), err(StackUnderflow, 4, 2));
+    assert_eq!(validate("(func (result i32) (block $a (result i32) (br_table $a $a 1 (i32.const 7) (i32.const 0))))"), Ok(()));
+
+    use CodeEntry::*;
+    use Opcode::*;
+    let unknown = vec![Op(I32Const), I32Imm(0), Op(BrTable), Labels(vec![0, 3]), Op(End)];
+    assert_eq!(TypedValidate::new(unknown, vec![], vec![]).dispatch(), err(UnknownLabel(3), 2, 1));
+}
+
+// calls a module function on every evaluator that supports calls, checking
+// they agree
+fn run_module<T: From<Slot>>(module: &Module, func_idx: usize, args: &[i32]) -> Result<Vec<T>, Trap> {
+    let linked = module.link().unwrap();
+
+    let mut eval = Eval {
+        stack: args.iter().map(|&x| Slot::from(x)).collect(),
+        locals: vec![],
+        codeptr: linked.codeptr(),
+        sidetable: linked.sidetable.clone(),
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: linked.funcs.clone(),
+        hosts: linked.hosts.clone(),
+        tables: linked.tables.clone(),
+        elems: linked.elems.clone(),
+        sigs: linked.sigs.clone(),
+        memory: linked.memory.clone(),
+        datas: linked.datas.clone(),
+        globals: linked.globals.clone(),
+    };
+    eval.call(func_idx);
+    let res = eval.dispatch().map(|()| eval.stack);
+
+    let mut teval = TypedEval {
+        stack: args.iter().map(|&x| Slot::from(x)).collect(),
+        locals: vec![],
+        codeptr: linked.codeptr(),
+        sidetable: linked.sidetable.clone(),
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: linked.funcs.clone(),
+        hosts: linked.hosts.clone(),
+        tables: linked.tables.clone(),
+        elems: linked.elems.clone(),
+        sigs: linked.sigs.clone(),
+        memory: linked.memory.clone(),
+        datas: linked.datas.clone(),
+        globals: linked.globals.clone(),
     };
-    teval.run();
-    dbg!(teval.stack);
+    teval.call(func_idx);
+    assert_eq!(teval.dispatch().map(|()| teval.stack), res);
+
+    let mut fr_eval = EvalFR {
+        stack: args.iter().map(|&x| Slot::from(x)).collect(),
+        locals: vec![],
+        codeptr: linked.codeptr(),
+        sidetable: linked.sidetable,
+        stp: 0,
+        trap: None,
+        frames: vec![],
+        funcs: linked.funcs,
+        hosts: linked.hosts,
+        tables: linked.tables,
+        elems: linked.elems,
+        sigs: linked.sigs,
+        memory: linked.memory,
+        datas: linked.datas,
+        globals: linked.globals,
+    };
+    CBD_FR::call(&mut fr_eval, func_idx);
+    assert_eq!(fr_eval.run().map(|()| fr_eval.stack), res);
+
+    typed(res)
+}
+
+const FIB_WAT: &str = r#"
+    (func $fib (param $n i32) (result i32)
+      (if (result i32) (i32.lt_u (local.get $n) (i32.const 2))
+        (then (local.get $n))
+        (else
+          (i32.add
+            (call $fib (i32.sub (local.get $n) (i32.const 1)))
+            (call $fib (i32.sub (local.get $n) (i32.const 2)))))))
+"#;
+
+#[test]
+fn test_calls() {
+    let fib = parse_module(FIB_WAT).unwrap();
+    for (n, res) in [(0, 0), (1, 1), (2, 1), (10, 55), (20, 6765)] {
+        assert_eq!(run_module(&fib, 0, &[n]), Ok(vec![res]), "fib {n}");
+    }
+
+    // callees can come later, params are the callee's first locals
+    let module = parse_module(r#"
+        (func $is_even (param $n i32) (result i32)
+          (if (result i32) (local.get $n)
+            (then (call $is_odd (i32.sub (local.get $n) (i32.const 1))))
+            (else (i32.const 1))))
+        (func $is_odd (param $n i32) (result i32)
+          (if (result i32) (local.get $n)
+            (then (call $is_even (i32.sub (local.get $n) (i32.const 1))))
+            (else (i32.const 0))))
+        (func $sub (param i32 i32) (result i32) (local i32)
+          (local.set 2 (i32.sub (local.get 0) (local.get 1)))
+          local.get 2)
+        (func (result i32)
+          (call $sub (i32.const 10) (call $is_even (i32.const 7))))"#).unwrap();
+    assert_eq!(run_module(&module, 0, &[10]), Ok(vec![1]));
+    assert_eq!(run_module(&module, 1, &[10]), Ok(vec![0]));
+    assert_eq!(run_module(&module, 2, &[10, 3]), Ok(vec![7]));
+    assert_eq!(run_module(&module, 3, &[]), Ok(vec![10]));
+
+    // return leaves from inside blocks, dropping whatever is under the results
+    let module = parse_module(r#"
+        (func $f (param i32) (result i32)
+          i32.const 1 i32.const 2
+          (block (block (return (i32.const 42))))
+          i32.add)
+        (func (result i32) i32.const 7 (call $f (i32.const 0)) i32.add)"#).unwrap();
+    assert_eq!(run_module(&module, 1, &[]), Ok(vec![49]));
+    assert_eq!(run_everywhere(&parse_module("(func (result i32) (block (return (i32.const 3))) i32.const 4)").unwrap()), Ok(vec![3]));
+
+    let module = parse_module("(func $loop (call $loop))").unwrap();
+    assert_eq!(run_module::<i32>(&module, 0, &[]).map_err(|trap| trap.kind), Err(TrapKind::CallStackExhausted));
+    // traps in the callee unwind everything
+    let module = parse_module(r#"
+        (func $div (param i32 i32) (result i32) (i32.div_s (local.get 0) (local.get 1)))
+        (func (result i32) (call $div (i32.const 1) (i32.const 0)))"#).unwrap();
+    assert_eq!(run_module::<i32>(&module, 1, &[]), Err(Trap { kind: TrapKind::IntDivByZero, ip: 4 }));
+
+    // a lone WASMFun has nothing to call, nor any memory
+    for (wat, ip) in [("(func $f (call $f))", 0), ("(memory 1) (func (drop (i32.load (i32.const 0))))", 2)] {
+        let mut wasm_fun = WASMFun::new(parse_module(wat).unwrap().funcs[0].code.clone(), &[]);
+        let cps_eval = wasm_fun.run(CPSEval { stack: vec![], locals: vec![], globals: vec![], trap: None });
+        assert_eq!(cps_eval.map(|_| ()), Err(Trap { kind: TrapKind::Unsupported, ip }), "{wat}");
+    }
+}
+
+#[test]
+fn test_call_errors() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str| parse_module(wat).unwrap().link().map(|_| ());
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+
+    let validate = |wat: &str| validate(wat).map_err(|err| match err {
+        LinkError::Invalid(err) => err,
+        err => panic!("{err}"),
+    });
+    assert_eq!(validate("(func $f (param i32) (result i32) (call $f))"), err(StackUnderflow, 0, 1));
+    assert_eq!(validate("(func $f (param i32) (call $f (i32.const 1)) i32.add)"), err(StackUnderflow, 4, 1));
+    // the results of return come from the function type
+    assert_eq!(validate("(func (result i32) (block (return)))"), err(StackUnderflow, 2, 2));
+    assert!(parse_module("(func (call 1))").is_err());
+
+    use CodeEntry::*;
+    use Opcode::*;
+    let unknown = vec![Op(Call), I32Imm(3), Op(End)];
+    assert_eq!(TypedValidate::new(unknown, vec![], vec![]).dispatch(), err(UnknownFunc(3), 0, 1));
+}
+
+const INDIRECT_WAT: &str = r#"
+    (type $unop (func (param i32) (result i32)))
+    (type $dup (func (param i32) (result i32)))
+    (table $t 5 funcref)
+    (elem (table $t) (i32.const 0) $double $square $add)
+    (func $double (param i32) (result i32) (i32.add (local.get 0) (local.get 0)))
+    (func $square (param i32) (result i32) (i32.mul (local.get 0) (local.get 0)))
+    (func $add (param i32 i32) (result i32) (i32.add (local.get 0) (local.get 1)))
+    (func $apply (param $f i32) (param $x i32) (result i32)
+      (call_indirect $t (type $unop) (local.get $x) (local.get $f)))
+    (func $apply_dup (param $f i32) (param $x i32) (result i32)
+      local.get $x
+      local.get $f
+      call_indirect (type $dup))
+    (func $apply_inline (param $f i32) (param $x i32) (result i32)
+      (call_indirect (param i32) (result i32) (local.get $x) (local.get $f)))
+"#;
+
+#[test]
+fn test_call_indirect() {
+    let module = parse_module(INDIRECT_WAT).unwrap();
+    let trap = |f, x| run_module(&module, 3, &[f, x]).map_err(|trap| trap.kind);
+    assert_eq!(trap(0, 5), Ok(vec![10]));
+    assert_eq!(trap(1, 5), Ok(vec![25]));
+    // signatures are compared structurally, whichever type index names them
+    assert_eq!(run_module(&module, 4, &[1, 6]), Ok(vec![36]));
+    assert_eq!(run_module(&module, 5, &[0, 6]), Ok(vec![12]));
+
+    assert_eq!(trap(2, 5), Err(TrapKind::IndirectCallTypeMismatch));
+    assert_eq!(trap(3, 5), Err(TrapKind::UninitializedElement));
+    assert_eq!(trap(5, 5), Err(TrapKind::UndefinedElement));
+    assert_eq!(trap(-1, 5), Err(TrapKind::UndefinedElement));
+
+    // an inline (elem ...) sizes the table to fit
+    let module = parse_module(r#"
+        (table funcref (elem $one $two))
+        (func $one (result i32) i32.const 1)
+        (func $two (result i32) i32.const 2)
+        (func (param i32) (result i32) (call_indirect (result i32) (local.get 0)))"#).unwrap();
+    assert_eq!(module.tables, [Table { elem_type: Type::FuncRef, min: 2, max: Some(2) }]);
+    assert_eq!(run_module(&module, 2, &[1]), Ok(vec![2]));
+    assert_eq!(run_module::<i32>(&module, 2, &[2]).map_err(|trap| trap.kind), Err(TrapKind::UndefinedElement));
+}
+
+#[test]
+fn test_call_indirect_errors() {
+    use ValidationErrorKind::*;
+    let link = |wat: &str| parse_module(wat).unwrap().link().map(|_| ());
+    let err = |kind, ip, depth| Err(LinkError::Invalid(ValidationError { kind, ip, depth }));
+
+    assert_eq!(link("(type (func)) (func (call_indirect (type 0) (i32.const 0)))"), err(UnknownTable(0), 2, 1));
+    assert_eq!(link("(table 1 funcref) (func (call_indirect (param i32) (i32.const 0)))"), err(StackUnderflow, 2, 1));
+    assert_eq!(link("(table 1 funcref) (func (result i32) (call_indirect (result i32)))"), err(StackUnderflow, 0, 1));
+    assert!(parse_module("(func (call_indirect $nope (i32.const 0)))").is_err());
+
+    // segments must fit their table, but may end right at its end
+    assert_eq!(link("(table 1 funcref) (func) (elem (i32.const 1) 0)"), Err(LinkError::ElemOutOfBounds(0)));
+    assert_eq!(link("(table 1 funcref) (func) (elem (i32.const -1) 0)"), Err(LinkError::ElemOutOfBounds(0)));
+    assert_eq!(link("(table 1 funcref) (func) (elem (i32.const 1))"), Ok(()));
+    let mut module = parse_module("(table 1 funcref) (func)").unwrap();
+    let mode = ElemMode::Active { table: 0, offset: ConstExpr::I32Const(0) };
+    module.elems.push(Elem { ty: Type::FuncRef, mode, init: vec![ConstExpr::RefFunc(3)] });
+    assert_eq!(module.link().map(|_| ()), Err(LinkError::InvalidElem { elem: 0, kind: UnknownFunc(3) }));
+
+    use CodeEntry::*;
+    use Opcode::*;
+    let unknown = vec![Op(I32Const), I32Imm(0), Op(CallIndirect), I32Imm(7), I32Imm(0), Op(End)];
+    let mut validate = TypedValidate::new(unknown, vec![], vec![]);
+    validate.tables = vec![Type::FuncRef];
+    assert_eq!(validate.dispatch(), Err(ValidationError { kind: UnknownType(7), ip: 2, depth: 1 }));
+}
+
+const MEMORY_WAT: &str = r#"
+    (memory 1 2)
+    (func $load8_s (param $addr i32) (param $v i32) (result i32)
+      (i32.store (local.get $addr) (local.get $v))
+      (i32.load8_s (local.get $addr)))
+    (func $load8_u (param $addr i32) (param $v i32) (result i32)
+      (i32.store (local.get $addr) (local.get $v))
+      (i32.load8_u (local.get $addr)))
+    (func $load16_s (param $addr i32) (param $v i32) (result i32)
+      (i32.store (local.get $addr) (local.get $v))
+      (i32.load16_s (local.get $addr)))
+    (func $load16_u (param $addr i32) (param $v i32) (result i32)
+      (i32.store (local.get $addr) (local.get $v))
+      (i32.load16_u align=1 (local.get $addr)))
+    (func $offset (param $addr i32) (param $v i32) (result i32)
+      local.get $addr
+      local.get $v
+      i32.store offset=4
+      (i32.load (i32.add (local.get $addr) (i32.const 4))))
+    (func $narrow (param $addr i32) (param $v i32) (result i32)
+      (i32.store (local.get $addr) (i32.const -1))
+      (i32.store16 (local.get $addr) (local.get $v))
+      (i32.store8 offset=3 (local.get $addr) (local.get $v))
+      (i32.load (local.get $addr)))
+    (func $grow (result i32)
+      (i32.add
+        (i32.mul (memory.grow (i32.const 1)) (i32.const 100))
+        (i32.add (i32.mul (memory.grow (i32.const 1)) (i32.const 10)) (memory.size))))
+    (func $grown (result i32) (local i32)
+      (local.set 0 (memory.grow (i32.const 1)))
+      (i32.store8 (i32.const 65536) (i32.const 7))
+      (i32.load8_u (i32.const 65536)))
+    (func $read (param $addr i32) (result i32) (i32.load8_u (local.get $addr)))
+    (func $wrap (result i32) (i32.load offset=0xFFFFFFFF (i32.const 1)))
+"#;
+
+#[test]
+fn test_memory() {
+    let module = parse_module(MEMORY_WAT).unwrap();
+    let run = |func_idx, args: &[i32]| run_module(&module, func_idx, args).map_err(|trap| trap.kind);
+    let v = 0xFFFF8081u32 as i32;
+    assert_eq!(run(0, &[8, v]), Ok(vec![-127]));
+    assert_eq!(run(1, &[8, v]), Ok(vec![0x81]));
+    assert_eq!(run(2, &[8, v]), Ok(vec![-32639]));
+    assert_eq!(run(3, &[8, v]), Ok(vec![0x8081]));
+    assert_eq!(run(4, &[8, v]), Ok(vec![v]));
+    // narrow stores only write the low bytes, little endian
+    assert_eq!(run(5, &[8, 0x12345678]), Ok(vec![0x78FF5678]));
+
+    // 1 page to start, up to 2
+    assert_eq!(run(6, &[]), Ok(vec![100 - 10 + 2]));
+    assert_eq!(run(7, &[]), Ok(vec![7]));
+
+    assert_eq!(run(8, &[65535]), Ok(vec![0]));
+    assert_eq!(run(8, &[65536]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(8, &[-1]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(0, &[65533, 0]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(0, &[65532, 0]), Ok(vec![0]));
+    // address plus offset doesn't wrap
+    assert_eq!(run(9, &[]), Err(TrapKind::MemoryOutOfBounds));
+
+    // memargs other than the defaults are shown
+    let text = disassemble(&module.codeptr(4), &[], &[]);
+    assert!(text.contains("i32.store offset=4\n"), "{text}");
+    let text = disassemble(&module.codeptr(3), &[], &[]);
+    assert!(text.contains("i32.load16_u align=1\n"), "{text}");
+    // an exponent too large to shift is shown as one
+    let mut huge = module.clone();
+    for entry in &mut huge.funcs[3].code {
+        if let CodeEntry::MemArg(memarg) = entry {
+            memarg.align = 64;
+        }
+    }
+    let text = disassemble(&huge.codeptr(3), &[], &[]);
+    assert!(text.contains("i32.load16_u align=2^64\n"), "{text}");
+}
+
+#[test]
+fn test_memory_errors() {
+    use ValidationErrorKind::*;
+    let link = |wat: &str| parse_module(wat).unwrap().link().map(|_| ());
+    let err = |kind, ip, depth| Err(LinkError::Invalid(ValidationError { kind, ip, depth }));
+
+    assert_eq!(link("(func (result i32) (i32.load (i32.const 0)))"), err(UnknownMemory(0), 2, 1));
+    assert_eq!(link("(func (result i32) (memory.size))"), err(UnknownMemory(0), 0, 1));
+    assert_eq!(link("(memory 1) (func (result i32) (i32.load align=8 (i32.const 0)))"),
+        err(AlignmentTooLarge { align: 3, natural: 2 }, 2, 1));
+    assert_eq!(link("(memory 1) (func (i32.store8 align=2 (i32.const 0) (i32.const 0)))"),
+        err(AlignmentTooLarge { align: 1, natural: 0 }, 4, 1));
+    assert_eq!(link("(memory 1) (func (i32.store (i32.const 0)))"), err(StackUnderflow, 2, 1));
+    assert!(parse_module("(memory 1) (func (result i32) (i32.load align=3 (i32.const 0)))").is_err());
+
+    assert_eq!(link("(memory 2 1)"), Err(LinkError::MemoryLimits { min: 2, max: Some(1) }));
+    assert_eq!(link("(memory 1 65537)"), Err(LinkError::MemoryLimits { min: 1, max: Some(65537) }));
+    assert_eq!(link("(memory 0) (memory 0)"), Err(LinkError::MultipleMemories));
+}
+
+#[test]
+fn test_globals() {
+    let module = parse_module(r#"
+        (global $counter (mut i32) (i32.const 10))
+        (global $base i32 (i32.const 100))
+        (global $copy i32 (global.get $base))
+        (func $bump (param i32) (result i32)
+          (global.set $counter (i32.add (global.get $counter) (local.get 0)))
+          global.get $counter)
+        (func $twice (param i32) (result i32)
+          (i32.add (call $bump (local.get 0)) (call $bump (local.get 0))))
+        (func $read (result i32) (i32.add (global.get $base) (global.get $copy)))"#).unwrap();
+    assert_eq!(module.globals[0], Global { ty: GlobalType { ty: Type::I32, mutable: true }, init: ConstExpr::I32Const(10) });
+    assert_eq!(module.globals[2].init, ConstExpr::GlobalGet(1));
+    assert_eq!(module.link().unwrap().globals, [10, 100, 100].map(Slot::from));
+    // sets are seen by later calls
+    assert_eq!(run_module(&module, 1, &[5]), Ok(vec![15 + 20]));
+    assert_eq!(run_module(&module, 2, &[]), Ok(vec![200]));
+
+    // segment offsets can read immutable globals too
+    let module = parse_module(r#"
+        (global $at i32 (i32.const 1))
+        (table 2 funcref)
+        (elem (global.get $at) $f)
+        (func $f)"#).unwrap();
+    assert_eq!(module.link().unwrap().tables[0].elems, [None, Some(0)]);
+
+    // a WASMFun has no module, its globals are whatever the CPSEval is given
+    use CodeEntry::*;
+    use Opcode::*;
+    let code = vec![
+        Op(GlobalGet), I32Imm(0), Op(I32Const), I32Imm(1), Op(I32Add), Op(GlobalSet), I32Imm(0),
+        Op(GlobalGet), I32Imm(0), Op(End),
+    ];
+    let mut wasm_fun = WASMFun::new(code, &[]);
+    let interpreter = wasm_fun.run(CPSEval { stack: vec![], locals: vec![], globals: vec![Slot::from(5)], trap: None }).unwrap();
+    assert_eq!((interpreter.stack, interpreter.globals), (vec![Slot::from(6)], vec![Slot::from(6)]));
+}
+
+#[test]
+fn test_global_errors() {
+    use ValidationErrorKind::*;
+    let link = |wat: &str| parse_module(wat).unwrap().link().map(|_| ());
+    let err = |kind, ip, depth| Err(LinkError::Invalid(ValidationError { kind, ip, depth }));
+
+    assert_eq!(link("(global i32 (i32.const 0)) (func (global.set 0 (i32.const 1)))"), err(ImmutableGlobal(0), 2, 1));
+    assert_eq!(link("(global (mut i32) (i32.const 0)) (func (global.set 0))"), err(StackUnderflow, 0, 1));
+    assert_eq!(link("(global (mut i32) (i32.const 0)) (func (global.get 0))"), err(BlockArity { expected: 0, found: 1 }, 2, 1));
+    assert!(parse_module("(func (global.get 0))").is_err());
+    let unknown = vec![CodeEntry::Op(Opcode::GlobalGet), CodeEntry::I32Imm(3), CodeEntry::Op(Opcode::End)];
+    assert_eq!(TypedValidate::new(unknown, vec![], vec![Type::I32]).dispatch(),
+        Err(ValidationError { kind: UnknownGlobal(3), ip: 0, depth: 1 }));
+
+    // initializers may only read earlier immutable globals
+    assert_eq!(link("(global (mut i32) (i32.const 0)) (global i32 (global.get 0))"),
+        Err(LinkError::InvalidGlobal { global: 1, kind: ConstExprRequired }));
+    assert_eq!(link("(global i32 (global.get 1)) (global i32 (i32.const 0))"),
+        Err(LinkError::InvalidGlobal { global: 0, kind: UnknownGlobal(1) }));
+    assert_eq!(link("(global (mut i32) (i32.const 0)) (table 1 funcref) (elem (global.get 0))"),
+        Err(LinkError::InvalidElem { elem: 0, kind: ConstExprRequired }));
+}
+
+#[test]
+fn test_i64() {
+    let cases: &[(&str, i64)] = &[
+        ("(i64.add (i64.const 0x7fffffffffffffff) (i64.const 1))", i64::MIN),
+        ("(i64.sub (i64.const 3) (i64.const 5))", -2),
+        ("(i64.mul (i64.const 0x100000000) (i64.const 0x100000000))", 0),
+        ("(i64.div_s (i64.const -7) (i64.const 2))", -3),
+        ("(i64.div_u (i64.const -1) (i64.const 2))", i64::MAX),
+        ("(i64.rem_s (i64.const 0x8000000000000000) (i64.const -1))", 0),
+        ("(i64.rem_u (i64.const -1) (i64.const 10))", 5),
+        ("(i64.and (i64.const 0xff00) (i64.const 0x0ff0))", 0x0f00),
+        ("(i64.or (i64.const 0xff00) (i64.const 0x0ff0))", 0xfff0),
+        ("(i64.xor (i64.const 0xff00) (i64.const 0x0ff0))", 0xf0f0),
+        ("(i64.shl (i64.const 1) (i64.const 65))", 2),
+        ("(i64.shr_s (i64.const -8) (i64.const 1))", -4),
+        ("(i64.shr_u (i64.const -8) (i64.const 60))", 0xf),
+        ("(i64.rotl (i64.const 0x8000000000000001) (i64.const 1))", 3),
+        ("(i64.rotr (i64.const 3) (i64.const 1))", 0x8000000000000001u64 as i64),
+        ("(i64.clz (i64.const 1))", 63),
+        ("(i64.ctz (i64.const 0))", 64),
+        ("(i64.popcnt (i64.const -1))", 64),
+        ("(i64.extend_i32_s (i32.const -1))", -1),
+        ("(i64.extend_i32_u (i32.const -1))", 0xffffffff),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i64) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let cases: &[(&str, i32)] = &[
+        ("(i64.eqz (i64.const 0x100000000))", 0),
+        ("(i64.eq (i64.const 0x100000001) (i64.const 1))", 0),
+        ("(i64.ne (i64.const 4) (i64.const 4))", 0),
+        ("(i64.lt_s (i64.const -1) (i64.const 0))", 1),
+        ("(i64.lt_u (i64.const -1) (i64.const 0))", 0),
+        ("(i64.gt_s (i64.const -1) (i64.const 0))", 0),
+        ("(i64.gt_u (i64.const -1) (i64.const 0))", 1),
+        ("(i64.le_s (i64.const 2) (i64.const 2))", 1),
+        ("(i64.le_u (i64.const 3) (i64.const 2))", 0),
+        ("(i64.ge_s (i64.const -3) (i64.const 2))", 0),
+        ("(i64.ge_u (i64.const -3) (i64.const 2))", 1),
+        ("(i32.wrap_i64 (i64.const 0x1_ffff_fffe))", -2),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i32) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let trap = |kind, ip| Err::<Vec<i64>, _>(Trap { kind, ip });
+    let module = parse_module("(func (result i64) (i64.div_u (i64.const 1) (i64.const 0)))").unwrap();
+    assert_eq!(run_everywhere(&module), trap(TrapKind::IntDivByZero, 4));
+    let module = parse_module("(func (result i64) (i64.div_s (i64.const 0x8000000000000000) (i64.const -1)))").unwrap();
+    assert_eq!(run_everywhere(&module), trap(TrapKind::IntOverflow, 4));
+
+    // i64 locals, globals and blocks, across calls
+    let module = parse_module(r#"
+        (global $total (mut i64) (i64.const 0))
+        (func $fact (param $n i32) (result i64) (local $acc i64)
+          (local.set $acc (i64.const 1))
+          (block $done
+            (loop $l
+              (br_if $done (i32.eqz (local.get $n)))
+              (local.set $acc (i64.mul (local.get $acc) (i64.extend_i32_u (local.get $n))))
+              (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+              (br $l)))
+          (global.set $total (i64.add (global.get $total) (local.get $acc)))
+          local.get $acc)
+        (func (param i32) (result i64) (local i64)
+          (local.set 1 (call $fact (local.get 0)))
+          (i64.add (call $fact (local.get 0)) (global.get $total)))"#).unwrap();
+    let fact20 = 2432902008176640000i64;
+    assert_eq!(run_module(&module, 0, &[20]), Ok(vec![fact20]));
+    assert_eq!(run_module(&module, 0, &[21]), Ok(vec![fact20.wrapping_mul(21)]));
+    // the second call's result plus both calls' total
+    assert_eq!(run_module(&module, 1, &[20]), Ok(vec![fact20.wrapping_mul(3)]));
+
+    let module = parse_module("(global i64 (i64.const -5)) (func (result i64) (i64.const 0x100000000))").unwrap();
+    assert_eq!(module.link().unwrap().globals, [Slot::from(-5i64)]);
+    assert!(disassemble(&module.codeptr(0), &[], &[]).contains("i64.const 4294967296"));
+}
+
+#[test]
+fn test_i64_errors() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str| {
+        let module = parse_module(wat).unwrap();
+        TypedValidate::from_module(&module, 0).dispatch()
+    };
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+    let mismatch = |expected, found| TypeMismatch { expected, found };
+
+    assert_eq!(validate("(func (result i64) (i32.const 1))"), err(mismatch(Type::I64, Type::I32), 2, 1));
+    assert_eq!(validate("(func (result i64) (i64.add (i32.const 1) (i64.const 2)))"), err(mismatch(Type::I64, Type::I32), 4, 1));
+    assert_eq!(validate("(func (result i32) (i32.wrap_i64 (i32.const 1)))"), err(mismatch(Type::I64, Type::I32), 2, 1));
+    assert_eq!(validate("(func (result i64) (i64.extend_i32_u (i64.const 1)))"), err(mismatch(Type::I32, Type::I64), 2, 1));
+    assert_eq!(validate("(func (result i32) (i64.eq (i64.const 1) (i64.const 2)) i64.eqz)"), err(mismatch(Type::I64, Type::I32), 5, 1));
+    assert_eq!(validate("(func (local i64) (local.set 0 (i32.const 1)))"), err(mismatch(Type::I64, Type::I32), 2, 1));
+
+    assert!(parse_module("(func (result i64) (i64.const 0x1_0000_0000_0000_0000))").is_err());
+    let link = |wat: &str| parse_module(wat).unwrap().link().map(|_| ());
+    assert_eq!(link("(global i64 (i32.const 1))"),
+        Err(LinkError::InvalidGlobal { global: 0, kind: mismatch(Type::I64, Type::I32) }));
+    assert_eq!(link("(global i64 (i64.const 1)) (table 1 funcref) (elem (global.get 0))"),
+        Err(LinkError::InvalidElem { elem: 0, kind: mismatch(Type::I32, Type::I64) }));
+}
+
+#[test]
+fn test_floats() {
+    let cases: &[(&str, f32)] = &[
+        ("(f32.add (f32.const 1.5) (f32.const 2.25))", 3.75),
+        ("(f32.sub (f32.const 0x1p-149) (f32.const 0x1p-149))", 0.0),
+        ("(f32.mul (f32.const -3) (f32.const 0.5))", -1.5),
+        ("(f32.div (f32.const 1) (f32.const -0))", f32::NEG_INFINITY),
+        ("(f32.min (f32.const -inf) (f32.const 1))", f32::NEG_INFINITY),
+        ("(f32.max (f32.const 0x1.8p1) (f32.const 2))", 3.0),
+        ("(f32.copysign (f32.const 2) (f32.const -0))", -2.0),
+        ("(f32.abs (f32.const -inf))", f32::INFINITY),
+        ("(f32.neg (f32.const 1e10))", -1e10),
+        ("(f32.sqrt (f32.const 2.25))", 1.5),
+        ("(f32.ceil (f32.const 1.1))", 2.0),
+        ("(f32.floor (f32.const -0.5))", -1.0),
+        ("(f32.trunc (f32.const -1.7))", -1.0),
+        ("(f32.nearest (f32.const 2.5))", 2.0),
+        ("(f32.nearest (f32.const 3.5))", 4.0),
+        ("(f32.convert_i32_u (i32.const -1))", 4294967296.0),
+        ("(f32.convert_i64_s (i64.const -3))", -3.0),
+        ("(f32.convert_i64_u (i64.const -1))", 18446744073709551616.0),
+        // halfway between two f32s, ties to even
+        ("(f32.demote_f64 (f64.const 0x1.000001p0))", 1.0),
+        ("(f32.reinterpret_i32 (i32.const 0x3fc00000))", 1.5),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result f32) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let cases: &[(&str, f64)] = &[
+        ("(f64.add (f64.const 0.1) (f64.const 0.2))", 0.1 + 0.2),
+        ("(f64.div (f64.const 1) (f64.const 3))", 1.0 / 3.0),
+        ("(f64.min (f64.const 1) (f64.const 2))", 1.0),
+        ("(f64.max (f64.const -inf) (f64.const -1e300))", -1e300),
+        ("(f64.nearest (f64.const -1.5))", -2.0),
+        ("(f64.sqrt (f64.const 0x1p-1074))", 2f64.powi(-537)),
+        ("(f64.convert_i64_u (i64.const -1))", 18446744073709551616.0),
+        ("(f64.convert_i32_s (i32.const -5))", -5.0),
+        ("(f64.promote_f32 (f32.const 0x1p-149))", 2f64.powi(-149)),
+        ("(f64.reinterpret_i64 (i64.const 0x3ff0000000000000))", 1.0),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result f64) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    // signed zeros and NaNs, compared as bits
+    let cases: &[(&str, i32)] = &[
+        ("(i32.reinterpret_f32 (f32.min (f32.const 0) (f32.const -0)))", i32::MIN),
+        ("(i32.reinterpret_f32 (f32.max (f32.const -0) (f32.const 0)))", 0),
+        ("(i32.reinterpret_f32 (f32.nearest (f32.const -0.5)))", i32::MIN),
+        ("(i32.reinterpret_f32 (f32.ceil (f32.const -0.5)))", i32::MIN),
+        ("(i32.reinterpret_f32 (f32.const -nan:0x1))", 0xff800001u32 as i32),
+        // sign bit ops keep payloads
+        ("(i32.reinterpret_f32 (f32.neg (f32.const nan:0x200000)))", 0xffa00000u32 as i32),
+        ("(i32.reinterpret_f32 (f32.abs (f32.const -nan)))", 0x7fc00000),
+        // min and max propagate a NaN, quieted, rather than return the number
+        ("(i32.and (i32.reinterpret_f32 (f32.min (f32.const 1) (f32.const nan:0x200000))) (i32.const 0x7fc00000))", 0x7fc00000),
+        ("(i32.and (i32.reinterpret_f32 (f32.max (f32.const nan) (f32.const 1))) (i32.const 0x7fc00000))", 0x7fc00000),
+        ("(i32.and (i32.reinterpret_f32 (f32.sqrt (f32.const -1))) (i32.const 0x7fc00000))", 0x7fc00000),
+        ("(f32.eq (f32.const nan) (f32.const nan))", 0),
+        ("(f32.ne (f32.const nan) (f32.const nan))", 1),
+        ("(f32.eq (f32.const 0) (f32.const -0))", 1),
+        ("(f32.lt (f32.const -0) (f32.const 0))", 0),
+        ("(f32.gt (f32.const inf) (f32.const 1e38))", 1),
+        ("(f32.le (f32.const nan) (f32.const 1))", 0),
+        ("(f32.ge (f32.const 2) (f32.const 2))", 1),
+        ("(f64.lt (f64.const -inf) (f64.const 0))", 1),
+        ("(f64.ge (f64.const 1) (f64.const nan))", 0),
+        ("(i32.trunc_f32_s (f32.const -1.9))", -1),
+        ("(i32.trunc_f32_u (f32.const 4294967040))", -256),
+        ("(i32.trunc_f64_s (f64.const -2147483648.9))", i32::MIN),
+        ("(i32.trunc_f64_u (f64.const -0.9))", 0),
+        ("(i32.trunc_f64_u (f64.const 4294967295.9))", -1),
+        ("(i32.reinterpret_f32 (f32.const -0))", i32::MIN),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i32) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let cases: &[(&str, i64)] = &[
+        ("(i64.trunc_f32_s (f32.const -0x1p63))", i64::MIN),
+        ("(i64.trunc_f32_u (f32.const 0x1.fffffep63))", 0xffffff0000000000u64 as i64),
+        ("(i64.trunc_f64_s (f64.const 1e18))", 1_000_000_000_000_000_000),
+        ("(i64.trunc_f64_u (f64.const 0x1.fffffffffffffp63))", -2048),
+        ("(i64.reinterpret_f64 (f64.const -0))", i64::MIN),
+        ("(i64.reinterpret_f64 (f64.const nan:0x4))", 0x7ff0000000000004),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i64) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let trap = |kind, ip| Err::<Vec<i32>, _>(Trap { kind, ip });
+    let run = |wat: &str| run_everywhere(&parse_module(wat).unwrap());
+    assert_eq!(run("(func (result i32) (i32.trunc_f32_s (f32.const nan)))"), trap(TrapKind::InvalidConversion, 2));
+    assert_eq!(run("(func (result i32) (i32.trunc_f64_s (f64.const 2147483648)))"), trap(TrapKind::IntOverflow, 2));
+    assert_eq!(run("(func (result i32) (i32.trunc_f64_u (f64.const -1)))"), trap(TrapKind::IntOverflow, 2));
+    assert_eq!(run("(func (result i32) (i32.trunc_f32_u (f32.const inf)))"), trap(TrapKind::IntOverflow, 2));
+    assert_eq!(run("(func (result i64) (i64.trunc_f64_s (f64.const 0x1p63)))"), trap(TrapKind::IntOverflow, 2));
+    assert_eq!(run("(func (result i64) (i64.trunc_f32_u (f32.const -nan)))"), trap(TrapKind::InvalidConversion, 2));
+
+    // f64 locals and globals, across calls
+    let module = parse_module(r#"
+        (global $scale f64 (f64.const 0.5))
+        (func $harmonic (param $n i32) (result f64) (local $sum f64)
+          (block $done
+            (loop $l
+              (br_if $done (i32.eqz (local.get $n)))
+              (local.set $sum (f64.add (local.get $sum) (f64.div (f64.const 1) (f64.convert_i32_u (local.get $n)))))
+              (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+              (br $l)))
+          (f64.mul (local.get $sum) (global.get $scale)))
+        (func (param i32) (result f32) (f32.demote_f64 (call $harmonic (local.get 0))))"#).unwrap();
+    let harmonic = |n: i32| (1..=n).rev().fold(0.0, |sum, k| sum + 1.0 / k as f64) * 0.5;
+    assert_eq!(run_module(&module, 0, &[100]), Ok(vec![harmonic(100)]));
+    assert_eq!(run_module(&module, 1, &[100]), Ok(vec![harmonic(100) as f32]));
+
+    let module = parse_module("(func (result f64) (f64.const 0.25))").unwrap();
+    assert!(disassemble(&module.codeptr(0), &[], &[]).contains("f64.const 0.25"));
+    let module = parse_module("(func (result f32) (f32.add (f32.const -nan:0x1) (f32.const -inf)))").unwrap();
+    let text = disassemble(&module.codeptr(0), &[], &[]);
+    assert!(text.contains("f32.const -nan:0x1") && text.contains("f32.const -inf"), "{text}");
+}
+
+#[test]
+fn test_float_errors() {
+    use ValidationErrorKind::*;
+    let validate = |wat: &str| {
+        let module = parse_module(wat).unwrap();
+        TypedValidate::from_module(&module, 0).dispatch()
+    };
+    let err = |kind, ip, depth| Err(ValidationError { kind, ip, depth });
+    let mismatch = |expected, found| TypeMismatch { expected, found };
+
+    assert_eq!(validate("(func (result f32) (f64.const 1))"), err(mismatch(Type::F32, Type::F64), 2, 1));
+    assert_eq!(validate("(func (result f32) (f32.add (f64.const 1) (f32.const 2)))"), err(mismatch(Type::F32, Type::F64), 4, 1));
+    assert_eq!(validate("(func (result i32) (f64.lt (f64.const 1) (i64.const 2)))"), err(mismatch(Type::F64, Type::I64), 4, 1));
+    assert_eq!(validate("(func (result i32) (i32.trunc_f32_s (f64.const 1)))"), err(mismatch(Type::F32, Type::F64), 2, 1));
+    assert_eq!(validate("(func (result f64) (f64.reinterpret_i64 (i32.const 1)))"), err(mismatch(Type::I64, Type::I32), 2, 1));
+    assert_eq!(validate("(func (result i32) (f32.demote_f64 (f64.const 1)))"), err(mismatch(Type::I32, Type::F32), 3, 1));
+    assert_eq!(validate("(func (local f64) (local.set 0 (f32.const 1)))"), err(mismatch(Type::F64, Type::F32), 2, 1));
+
+    for bad in ["1e39", "0x1p128", "nan:0x0", "nan:0x800000", "0x", "infinity", ".5", "1.5x"] {
+        assert!(parse_module(&format!("(func (result f32) (f32.const {bad}))")).is_err(), "{bad}");
+    }
+    assert!(parse_module("(func (result f64) (f64.const 1e309))").is_err());
+    let link = |wat: &str| parse_module(wat).unwrap().link().map(|_| ());
+    assert_eq!(link("(global f32 (f64.const 1))"),
+        Err(LinkError::InvalidGlobal { global: 0, kind: mismatch(Type::F32, Type::F64) }));
+}
+
+#[test]
+fn test_sat_trunc_and_sign_ext() {
+    let cases: &[(&str, i32)] = &[
+        ("(i32.trunc_sat_f32_s (f32.const nan))", 0),
+        ("(i32.trunc_sat_f32_s (f32.const -inf))", i32::MIN),
+        ("(i32.trunc_sat_f32_u (f32.const -1))", 0),
+        ("(i32.trunc_sat_f32_u (f32.const 1e10))", -1),
+        ("(i32.trunc_sat_f64_s (f64.const 2147483648))", i32::MAX),
+        ("(i32.trunc_sat_f64_s (f64.const -7.9))", -7),
+        ("(i32.trunc_sat_f64_u (f64.const 4294967295.9))", -1),
+        ("(i32.extend8_s (i32.const 0x80))", -128),
+        ("(i32.extend8_s (i32.const 0x17f))", 127),
+        ("(i32.extend16_s (i32.const 0x8000))", -32768),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i32) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let cases: &[(&str, i64)] = &[
+        ("(i64.trunc_sat_f32_s (f32.const inf))", i64::MAX),
+        ("(i64.trunc_sat_f32_u (f32.const -nan))", 0),
+        ("(i64.trunc_sat_f64_s (f64.const -1e19))", i64::MIN),
+        ("(i64.trunc_sat_f64_u (f64.const 1e20))", -1),
+        ("(i64.extend8_s (i64.const 0xff))", -1),
+        ("(i64.extend16_s (i64.const 0x1_7fff))", 0x7fff),
+        ("(i64.extend32_s (i64.const 0x8000_0000))", i32::MIN as i64),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i64) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let module = parse_module("(func (result i32) (i32.trunc_sat_f64_s (f64.const 1)))").unwrap();
+    assert!(disassemble(&module.codeptr(0), &[], &[]).contains("i32.trunc_sat_f64_s"));
+
+    let module = parse_module("(func (result i32) (i32.trunc_sat_f32_s (f64.const 1)))").unwrap();
+    let err = TypedValidate::from_module(&module, 0).dispatch();
+    let kind = ValidationErrorKind::TypeMismatch { expected: Type::F32, found: Type::F64 };
+    assert_eq!(err, Err(ValidationError { kind, ip: 2, depth: 1 }));
+}
+
+#[test]
+fn test_parametric() {
+    let cases: &[(&str, i32)] = &[
+        ("(select (i32.const 1) (i32.const 2) (i32.const 0))", 2),
+        ("(select (i32.const 1) (i32.const 2) (i32.const -5))", 1),
+        ("(i32.wrap_i64 (select (i64.const 1) (i64.const 2) (i32.const 1)))", 1),
+        ("(i32.trunc_f64_s (select (result f64) (f64.const 3.5) (f64.const 4.5) (i32.const 0)))", 4),
+        ("(drop (i32.const 7)) nop (i32.const 8)", 8),
+        ("(i32.add (local.tee 0 (i32.const 20)) (local.get 0))", 40),
+        ("(block (result i32) (br 0 (i32.const 3)) unreachable)", 3),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i32) (local i32) {expr})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let module = parse_module("(func (result i32) (drop (i32.const 1)) unreachable)").unwrap();
+    assert_eq!(run_everywhere::<i32>(&module), Err(Trap { kind: TrapKind::Unreachable, ip: 3 }));
+
+    // the stack is polymorphic after unreachable, select's operands unknown
+    for body in ["unreachable i32.add", "unreachable select", "unreachable (i32.const 1) select", "unreachable (select (result i64))"] {
+        let module = parse_module(&format!("(func (result i32) {body} drop (i32.const 0))")).unwrap();
+        assert_eq!(TypedValidate::from_module(&module, 0).dispatch(), Ok(()), "{body}");
+    }
+    // but one known operand types the result
+    let module = parse_module("(func (result i64) unreachable (select (i32.const 1) (i32.const 1)))").unwrap();
+    let kind = ValidationErrorKind::TypeMismatch { expected: Type::I64, found: Type::I32 };
+    assert_eq!(TypedValidate::from_module(&module, 0).dispatch(), Err(ValidationError { kind, ip: 6, depth: 1 }));
+
+    let module = parse_module("(func (result i32) (select (i32.const 1) (i64.const 2) (i32.const 0)))").unwrap();
+    let kind = ValidationErrorKind::TypeMismatch { expected: Type::I32, found: Type::I64 };
+    assert_eq!(TypedValidate::from_module(&module, 0).dispatch(), Err(ValidationError { kind, ip: 6, depth: 1 }));
+    let module = parse_module("(func (result f32) (select (result f32) (f32.const 1) (f64.const 2) (i32.const 0)))").unwrap();
+    let kind = ValidationErrorKind::TypeMismatch { expected: Type::F32, found: Type::F64 };
+    assert_eq!(TypedValidate::from_module(&module, 0).dispatch(), Err(ValidationError { kind, ip: 6, depth: 1 }));
+    assert!(parse_module("(func (select (result i32 i32)))").is_err());
+
+    let module = parse_module("(func (result i32) (select (result i32) (i32.const 1) (i32.const 2) (i32.const 0)))").unwrap();
+    assert!(disassemble(&module.codeptr(0), &[], &[]).contains("select (result i32)"));
+}
+
+#[test]
+fn test_multi_value() {
+    // branches carry every value the label takes, dropping what's under them
+    let cases: &[(&str, &str, &[i32])] = &[
+        ("(result i32 i32)", "(i32.const 1) (i32.const 2)", &[1, 2]),
+        ("(result i32 i32)", "(i32.const 9) (block (param i32) (result i32 i32) (i32.const 2))", &[9, 2]),
+        ("(result i32 i32)", "(block (result i32 i32) (i32.const 7) (i32.const 1) (i32.const 2) (br 0))", &[1, 2]),
+        ("(result i32 i32)", "(block (result i32 i32) (i32.const 7) (i32.const 1) (i32.const 2) (br_if 0 (i32.const 1)) drop drop drop (i32.const 0) (i32.const 0))", &[1, 2]),
+        ("(result i32 i32)", "(block (result i32 i32) (i32.const 8) (i32.const 1) (i32.const 2) (br_table 1 0 (i32.const 0)))", &[1, 2]),
+        ("(result i32 i32)", "(i32.const 8) (i32.const 1) (i32.const 2) (return)", &[1, 2]),
+        ("(result i32 i32)", "(i32.const 1) (i32.const 2) (if (param i32 i32) (result i32 i32) (i32.const 0) (then) (else drop (i32.const 5)))", &[1, 5]),
+        // sums 5..1 with the accumulator and counter as the loop's params
+        ("(result i32) (local i32 i32)", "(i32.const 0) (i32.const 5)
+            (loop (param i32 i32) (result i32 i32)
+                (local.set 1) (local.set 0)
+                (i32.add (local.get 0) (local.get 1))
+                (i32.sub (local.get 1) (i32.const 1))
+                (br_if 0 (i32.ne (local.get 1) (i32.const 1))))
+            drop", &[15]),
+    ];
+    for (sig, body, expected) in cases {
+        let module = parse_module(&format!("(func {sig} {body})")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(expected.to_vec()), "{body}");
+    }
+
+    let module = parse_module("
+        (func $swap (param i32 i32) (result i32 i32) (local.get 1) (local.get 0))
+        (func (result i32) (i32.sub (call $swap (i32.const 1) (i32.const 3))))").unwrap();
+    assert_eq!(run_module(&module, 1, &[]), Ok(vec![2]));
+
+    let errors: &[(&str, ValidationErrorKind, usize)] = &[
+        ("(block (result i32 i32) (i32.const 1))", ValidationErrorKind::BlockArity { expected: 2, found: 1 }, 4),
+        ("(block (result i32 i64) (i32.const 1) (i32.const 2) (br 0))", ValidationErrorKind::TypeMismatch { expected: Type::I64, found: Type::I32 }, 6),
+        ("(block (result i64) (i64.const 1) (i32.const 2) (br_if 0 (i32.const 1)) drop)", ValidationErrorKind::TypeMismatch { expected: Type::I64, found: Type::I32 }, 8),
+    ];
+    for (body, kind, ip) in errors {
+        let module = parse_module(&format!("(func {body} drop drop)")).unwrap();
+        let err = TypedValidate::from_module(&module, 0).dispatch();
+        assert_eq!(err, Err(ValidationError { kind: *kind, ip: *ip, depth: 2 }), "{body}");
+    }
+}
+
+#[test]
+fn test_reference_types() {
+    let cases: &[(&str, i32)] = &[
+        ("(ref.is_null (ref.null func))", 1),
+        ("(ref.is_null (ref.null extern))", 1),
+        ("(ref.is_null (ref.func 0))", 0),
+        ("(ref.is_null (select (result funcref) (ref.null func) (ref.func 0) (i32.const 0)))", 0),
+        // reference locals start out null
+        ("(ref.is_null (local.get 0))", 1),
+    ];
+    for (expr, expected) in cases {
+        let module = parse_module(&format!("(func (result i32) (local funcref) {expr}) (elem declare func 0)")).unwrap();
+        assert_eq!(run_everywhere(&module), Ok(vec![*expected]), "{expr}");
+    }
+
+    let module = parse_module(r#"
+        (table $t 2 10 funcref)
+        (elem $e funcref (ref.func $one) (ref.null func) (item ref.func $two))
+        (elem $act (i32.const 0) $one)
+        (func $one (result i32) i32.const 1)
+        (func $two (result i32) i32.const 2)
+        (func (param i32) (result i32) (table.grow $t (ref.null func) (local.get 0)))
+        (func (param i32) (result i32)
+            (table.init $t $e (i32.const 0) (i32.const 1) (i32.const 2))
+            (call_indirect (result i32) (local.get 0)))
+        (func (param i32) (result i32) (ref.is_null (table.get $t (local.get 0))))
+        (func (param i32) (result i32)
+            (table.set $t (local.get 0) (ref.func $one))
+            (call_indirect (result i32) (local.get 0)))
+        (func (param i32) (result i32)
+            (table.fill $t (i32.const 1) (ref.func $two) (local.get 0))
+            (table.copy (i32.const 0) (i32.const 1) (i32.const 1))
+            (i32.add (table.size) (call_indirect (result i32) (i32.const 0))))
+        (func (param i32) (result i32) (table.init $t $act (i32.const 0) (i32.const 0) (local.get 0)) (i32.const 0))"#).unwrap();
+    let trap = |func_idx, arg| run_module::<i32>(&module, func_idx, &[arg]).map_err(|trap| trap.kind);
+    assert_eq!(run_module(&module, 2, &[8]), Ok(vec![2]));
+    assert_eq!(run_module(&module, 2, &[9]), Ok(vec![-1]));
+    assert_eq!(run_module(&module, 3, &[1]), Ok(vec![2]));
+    assert_eq!(trap(3, 0), Err(TrapKind::UninitializedElement));
+    // the active segment filled slot 0 when linking
+    assert_eq!(run_module(&module, 4, &[0]), Ok(vec![0]));
+    assert_eq!(run_module(&module, 4, &[1]), Ok(vec![1]));
+    assert_eq!(trap(4, 2), Err(TrapKind::TableOutOfBounds));
+    assert_eq!(run_module(&module, 5, &[1]), Ok(vec![1]));
+    assert_eq!(trap(5, 2), Err(TrapKind::TableOutOfBounds));
+    assert_eq!(run_module(&module, 6, &[1]), Ok(vec![2 + 2]));
+    assert_eq!(trap(6, 2), Err(TrapKind::TableOutOfBounds));
+    // active segments are dropped once linked, so only an empty init fits
+    assert_eq!(run_module(&module, 7, &[0]), Ok(vec![0]));
+    assert_eq!(trap(7, 1), Err(TrapKind::TableOutOfBounds));
+    let linked = module.link().unwrap();
+    assert_eq!(linked.elems, [vec![Some(0), None, Some(1)], vec![]]);
+
+    use ValidationErrorKind::*;
+    let errors: &[(&str, ValidationErrorKind, usize)] = &[
+        ("(func (drop (ref.func 0)))", UndeclaredFuncRef(0), 0),
+        ("(func (result i32) (ref.is_null (i32.const 0)))", ExpectedRef(Type::I32), 2),
+        ("(func (drop (select (ref.null func) (ref.null func) (i32.const 1))))", ExpectedNum(Type::FuncRef), 6),
+        ("(table 1 externref) (func (call_indirect (i32.const 0)))", TypeMismatch { expected: Type::FuncRef, found: Type::ExternRef }, 2),
+        ("(table 1 externref) (func (table.set (i32.const 0) (ref.null func)))", TypeMismatch { expected: Type::ExternRef, found: Type::FuncRef }, 4),
+        ("(table 1 funcref) (table 1 externref) (func (table.copy 0 1 (i32.const 0) (i32.const 0) (i32.const 0)))",
+            TypeMismatch { expected: Type::FuncRef, found: Type::ExternRef }, 6),
+        ("(table 1 externref) (elem funcref) (func (table.init 0 (i32.const 0) (i32.const 0) (i32.const 0)))",
+            TypeMismatch { expected: Type::ExternRef, found: Type::FuncRef }, 6),
+        ("(func (result i32) (table.size))", UnknownTable(0), 0),
+    ];
+    for (wat, kind, ip) in errors {
+        let module = parse_module(wat).unwrap();
+        let err = TypedValidate::from_module(&module, 0).dispatch();
+        assert_eq!(err, Err(ValidationError { kind: *kind, ip: *ip, depth: 1 }), "{wat}");
+    }
+    let module = parse_module("(table 1 externref) (func $f) (elem (i32.const 0) $f)").unwrap();
+    let kind = TypeMismatch { expected: Type::ExternRef, found: Type::FuncRef };
+    assert_eq!(module.link().map(|_| ()), Err(LinkError::InvalidElem { elem: 0, kind }));
+
+    use CodeEntry::*;
+    use Opcode::*;
+    let unknown = vec![Op(I32Const), I32Imm(0), Op(I32Const), I32Imm(0), Op(I32Const), I32Imm(0), Op(TableInit), I32Imm(3), I32Imm(0), Op(End)];
+    let mut validate = TypedValidate::new(unknown, vec![], vec![]);
+    validate.tables = vec![Type::FuncRef];
+    assert_eq!(validate.dispatch(), Err(ValidationError { kind: UnknownElem(3), ip: 6, depth: 1 }));
+
+    let module = parse_module("(func (result i32) (ref.is_null (ref.null extern)))").unwrap();
+    assert!(disassemble(&module.codeptr(0), &[], &[]).contains("ref.null extern"));
+}
+
+// the same call with each evaluator, on copies of the store
+fn invoke_everywhere(store: &Store, func_addr: usize, args: &[i32]) -> Result<Vec<i32>, Trap> {
+    let args: Vec<Slot> = args.iter().map(|&x| Slot::from(x)).collect();
+    let res = store.clone().invoke::<Eval>(func_addr, &args);
+    assert_eq!(store.clone().invoke::<TypedEval>(func_addr, &args), res);
+    assert_eq!(store.clone().invoke::<EvalFR>(func_addr, &args), res);
+    typed(res)
+}
+
+#[test]
+fn test_instances() {
+    let lib = parse_module(r#"
+        (table (export "table") 1 funcref)
+        (elem (i32.const 0) $add)
+        (memory (export "mem") 1)
+        (global $count (export "count") (mut i32) (i32.const 0))
+        (func $add (export "add") (param i32 i32) (result i32) (i32.add (local.get 0) (local.get 1)))
+        (func $init (global.set $count (i32.const 10)) (i32.store (i32.const 0) (i32.const 7)))
+        (start $init)"#).unwrap();
+    let main = parse_module(r#"
+        (import "lib" "add" (func $add (param i32 i32) (result i32)))
+        (import "lib" "count" (global $count (mut i32)))
+        (import "lib" "table" (table 1 funcref))
+        (memory (import "lib" "mem") 1)
+        (func (export "bump") (result i32)
+            (global.set $count (call $add (global.get $count) (i32.const 1)))
+            (global.get $count))
+        (func (export "indirect") (param i32) (result i32)
+            (call_indirect (param i32 i32) (result i32) (i32.const 2) (i32.const 3) (local.get 0)))
+        (func (export "load") (result i32) (i32.load (i32.const 0)))"#).unwrap();
+    assert_eq!(main.imports[1], Import {
+        module: "lib".to_string(),
+        name: "count".to_string(),
+        desc: ImportDesc::Global(GlobalType { ty: Type::I32, mutable: true }),
+    });
+    // imports come first in the function index space
+    assert_eq!(main.exports[0], Export { name: "bump".to_string(), desc: ExportDesc::Func(1) });
+
+    let mut store = Store::default();
+    let lib_instance = Instance::new::<Eval>(&mut store, &lib).unwrap();
+    let Some(Extern::Global(count)) = lib_instance.export("count") else { panic!("count isn't a global") };
+    // the start function ran
+    assert_eq!(store.global(count).i32(), 10);
+    store.register("lib", &lib_instance);
+    let instance = Instance::new::<Eval>(&mut store, &main).unwrap();
+    let (bump, indirect, load) = (instance.func("bump").unwrap(), instance.func("indirect").unwrap(), instance.func("load").unwrap());
+    assert_eq!(invoke_everywhere(&store, bump, &[]), Ok(vec![11]));
+    assert_eq!(invoke_everywhere(&store, indirect, &[0]), Ok(vec![5]));
+    assert_eq!(invoke_everywhere(&store, indirect, &[1]).map_err(|trap| trap.kind), Err(TrapKind::UndefinedElement));
+    assert_eq!(invoke_everywhere(&store, load, &[]), Ok(vec![7]));
+    // the global is shared, and stays changed between calls
+    store.invoke::<EvalFR>(bump, &[]).unwrap();
+    store.invoke::<TypedEval>(bump, &[]).unwrap();
+    assert_eq!(store.global(count).i32(), 12);
+    assert_eq!(store.invoke::<Eval>(lib_instance.func("add").unwrap(), &[1.into(), 2.into()]), Ok(vec![3.into()]));
+
+    // instances of one module have their own state
+    let counter = parse_module(r#"
+        (global $g (mut i32) (i32.const 0))
+        (func (export "inc") (result i32) (global.set $g (i32.add (global.get $g) (i32.const 1))) (global.get $g))"#).unwrap();
+    let a = Instance::new::<Eval>(&mut store, &counter).unwrap();
+    let b = Instance::new::<Eval>(&mut store, &counter).unwrap();
+    store.invoke::<Eval>(a.func("inc").unwrap(), &[]).unwrap();
+    assert_eq!(invoke_everywhere(&store, a.func("inc").unwrap(), &[]), Ok(vec![2]));
+    assert_eq!(invoke_everywhere(&store, b.func("inc").unwrap(), &[]), Ok(vec![1]));
+
+    let unknown = LinkError::UnknownImport { module: "lib".to_string(), name: "add".to_string() };
+    assert_eq!(Instance::new::<Eval>(&mut Store::default(), &main).map(|_| ()), Err(unknown));
+    let incompatible = [
+        r#"(import "lib" "add" (func (param i32)))"#,
+        r#"(import "lib" "count" (global i32))"#,
+        r#"(import "lib" "table" (table 2 funcref))"#,
+        r#"(import "lib" "table" (table 1 externref))"#,
+        r#"(import "lib" "mem" (memory 1 1))"#,
+        r#"(import "lib" "add" (global i32))"#,
+    ];
+    for wat in incompatible {
+        let module = parse_module(wat).unwrap();
+        let import = &module.imports[0];
+        let err = LinkError::IncompatibleImport { module: import.module.clone(), name: import.name.clone() };
+        assert_eq!(Instance::new::<Eval>(&mut store, &module).map(|_| ()), Err(err), "{wat}");
+    }
+    let errors = [
+        ("(memory 1)", LinkError::MultipleMemories),
+        (r#"(func (export "f")) (func (export "f"))"#, LinkError::DuplicateExport("f".to_string())),
+        ("(func (param i32)) (start 0)", LinkError::InvalidStart(0)),
+        ("(table 5 2 funcref)", LinkError::TableLimits { min: 5, max: Some(2) }),
+        ("(table 4294967295 funcref)", LinkError::TableLimits { min: u32::MAX, max: None }),
+    ];
+    for (wat, err) in errors {
+        let module = parse_module(wat).unwrap();
+        assert_eq!(Instance::new::<Eval>(&mut store, &module).map(|_| ()), Err(err), "{wat}");
+    }
+    let mut module = parse_module("(func)").unwrap();
+    module.exports.push(Export { name: "g".to_string(), desc: ExportDesc::Func(1) });
+    let kind = ValidationErrorKind::UnknownFunc(1);
+    assert_eq!(module.link().map(|_| ()), Err(LinkError::InvalidExport { name: "g".to_string(), kind }));
+    let module = Module { tables: vec![Table { elem_type: Type::I32, min: 0, max: None }], ..Module::default() };
+    let kind = ValidationErrorKind::ExpectedRef(Type::I32);
+    assert_eq!(module.link().map(|_| ()), Err(LinkError::InvalidTable { table: 0, kind }));
+    let mut module = parse_module("(func)").unwrap();
+    module.funcs[0].ty = 5;
+    let err = ValidationError { kind: ValidationErrorKind::UnknownType(5), ip: 0, depth: 0 };
+    assert_eq!(module.link().map(|_| ()), Err(LinkError::Invalid(err)));
+    let mut module = parse_module(r#"(import "lib" "add" (func (param i32 i32) (result i32))) (func)"#).unwrap();
+    module.imports[0].desc = ImportDesc::Func(5);
+    let kind = ValidationErrorKind::UnknownType(5);
+    assert_eq!(Instance::new::<Eval>(&mut store, &module).map(|_| ()), Err(LinkError::InvalidImport { import: 0, kind }));
+    let module = parse_module("(func unreachable) (start 0)").unwrap();
+    let err = Instance::new::<TypedEval>(&mut Store::default(), &module).map(|_| ()).unwrap_err();
+    assert!(matches!(err, LinkError::StartTrapped(Trap { kind: TrapKind::Unreachable, .. })));
+}
+
+#[test]
+fn test_host_funcs() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let logged = Rc::new(RefCell::new(vec![]));
+    let mut store = Store::default();
+    let log = logged.clone();
+    let ty = |params: &[Type], results: &[Type]| FuncType { params: params.to_vec(), results: results.to_vec() };
+    store.define_func("env", "log", ty(&[Type::I32], &[]), move |_, args| {
+        log.borrow_mut().push(args[0].i32());
+        Ok(vec![])
+    });
+    let clock = store.define_func("env", "clock", ty(&[], &[Type::I64]), |_, _| Ok(vec![1234i64.into()]));
+    store.define_func("env", "fail", ty(&[Type::I32], &[]), |_, args| Err(TrapKind::Host(args[0].i32() as u32)));
+    // the guest's memory, as the host sees it
+    store.define_func("env", "sum", ty(&[Type::I32, Type::I32], &[Type::I32]), |memory, args| {
+        let (start, len) = (args[0].i32() as usize, args[1].i32() as usize);
+        let bytes = memory.bytes.get(start..start + len).ok_or(TrapKind::MemoryOutOfBounds)?;
+        Ok(vec![bytes.iter().map(|&b| b as i32).sum::<i32>().into()])
+    });
+    store.define_func("env", "bad", ty(&[], &[Type::I32]), |_, _| Ok(vec![]));
+
+    let module = parse_module(r#"
+        (import "env" "log" (func $log (param i32)))
+        (import "env" "clock" (func $clock (result i64)))
+        (import "env" "fail" (func $fail (param i32)))
+        (import "env" "sum" (func $sum (param i32 i32) (result i32)))
+        (import "env" "bad" (func $bad (result i32)))
+        (memory 1)
+        (table 1 funcref)
+        (elem (i32.const 0) $clock)
+        (func (export "logs") (param i32) (call $log (local.get 0)) (call $log (i32.add (local.get 0) (i32.const 1))))
+        (func (export "time") (result i64) (i64.add (call $clock) (call_indirect (result i64) (i32.const 0))))
+        (func (export "fail") (param i32) (result i32) (call $fail (local.get 0)) (i32.const 0))
+        (func (export "sum") (result i32)
+            (i32.store (i32.const 0) (i32.const 0x01020304))
+            (call $sum (i32.const 0) (i32.const 4)))
+        (func (export "sum_oob") (result i32) (call $sum (i32.const 65535) (i32.const 2)))
+        (func (export "bad") (result i32) (call $bad))"#).unwrap();
+    let instance = Instance::new::<EvalFR>(&mut store, &module).unwrap();
+    let func = |name| instance.func(name).unwrap();
+
+    assert_eq!(invoke_everywhere(&store, func("logs"), &[5]), Ok(vec![]));
+    // once per evaluator
+    assert_eq!(*logged.borrow(), [5, 6, 5, 6, 5, 6]);
+    assert_eq!(store.invoke::<TypedEval>(func("time"), &[]), Ok(vec![2468i64.into()]));
+    assert_eq!(store.invoke::<EvalFR>(clock, &[]), Ok(vec![1234i64.into()]));
+    assert_eq!(invoke_everywhere(&store, func("sum"), &[]), Ok(vec![10]));
+    assert_eq!(store.linked.memory.bytes.len(), PAGE_SIZE);
+    let trap = |name, args: &[i32]| invoke_everywhere(&store, func(name), args).map_err(|trap| trap.kind);
+    assert_eq!(trap("fail", &[7]), Err(TrapKind::Host(7)));
+    assert_eq!(trap("sum_oob", &[]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(trap("bad", &[]), Err(TrapKind::HostResultArity));
+    assert_eq!(TrapKind::Host(7).to_string(), "host trap 7");
+
+    let module = parse_module(r#"(import "env" "clock" (func (result i32)))"#).unwrap();
+    let err = LinkError::IncompatibleImport { module: "env".to_string(), name: "clock".to_string() };
+    assert_eq!(Instance::new::<Eval>(&mut store, &module).map(|_| ()), Err(err));
+}
+
+#[test]
+fn test_typed_funcs() {
+    let module = parse_module(r#"
+        (memory (export "mem") 1)
+        (func $add (export "add") (param i32 i32) (result i32) (i32.add (local.get 0) (local.get 1)))
+        (func (export "neg") (param i32) (result i32) (i32.sub (i32.const 0) (local.get 0)))
+        (func (export "split") (param i64) (result i32 i64 f64)
+            (i32.wrap_i64 (local.get 0)) (local.get 0) (f64.convert_i64_s (local.get 0)))
+        (func (export "nothing"))
+        (func (export "div") (param i32 i32) (result i32) (i32.div_s (local.get 0) (local.get 1)))
+        (func (export "ref") (result funcref) (ref.func $add))"#).unwrap();
+    let mut store = Store::default();
+    let instance = Instance::new::<EvalFR>(&mut store, &module).unwrap();
+
+    let add = instance.get_typed_func::<(i32, i32), i32>("add").unwrap();
+    assert_eq!(add.call::<EvalFR>(&mut store, (1, 2)), Ok(3));
+    assert_eq!(add.call::<TypedEval>(&mut store, (-4, 2)), Ok(-2));
+    assert_eq!(add.call::<Eval>(&mut store, (i32::MAX, 1)), Ok(i32::MIN));
+    let neg = instance.get_typed_func::<i32, i32>("neg").unwrap();
+    assert_eq!(neg.call::<EvalFR>(&mut store, 5), Ok(-5));
+    let split = instance.get_typed_func::<i64, (i32, i64, f64)>("split").unwrap();
+    assert_eq!(split.call::<EvalFR>(&mut store, 1 << 32 | 7), Ok((7, 1 << 32 | 7, 4294967303.0)));
+    let nothing = instance.get_typed_func::<(), ()>("nothing").unwrap();
+    assert_eq!(nothing.call::<Eval>(&mut store, ()), Ok(()));
+    let div = instance.get_typed_func::<(i32, i32), i32>("div").unwrap();
+    assert_eq!(div.call::<EvalFR>(&mut store, (1, 0)).map_err(|trap| trap.kind), Err(TrapKind::IntDivByZero));
+
+    let err = instance.get_typed_func::<(i64, i32), i32>("add").map(|_| ()).unwrap_err();
+    assert_eq!(err, CallError::ParamTypes { expected: vec![Type::I32, Type::I32], found: vec![Type::I64, Type::I32] });
+    let err = instance.get_typed_func::<(i32, i32), ()>("add").map(|_| ()).unwrap_err();
+    assert_eq!(err, CallError::ResultTypes { expected: vec![Type::I32], found: vec![] });
+    let err = instance.get_typed_func::<(), ()>("mem").map(|_| ()).unwrap_err();
+    assert_eq!(err, CallError::NotAFunc("mem".to_string()));
+    let err = instance.get_typed_func::<(), ()>("sub").map(|_| ()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown export sub");
+
+    // dynamic calls
+    assert_eq!(instance.call::<EvalFR>(&mut store, "add", &[Value::I32(1), Value::I32(2)]), Ok(vec![Value::I32(3)]));
+    assert_eq!(instance.call::<TypedEval>(&mut store, "split", &[Value::I64(-1)]), Ok(vec![Value::I32(-1), Value::I64(-1), Value::F64(-1.0)]));
+    let add_addr = instance.func("add").unwrap();
+    assert_eq!(instance.call::<Eval>(&mut store, "ref", &[]), Ok(vec![Value::FuncRef(Some(add_addr))]));
+    let err = instance.call::<EvalFR>(&mut store, "add", &[Value::I32(1), Value::F32(2.0)]).unwrap_err();
+    assert_eq!(err, CallError::ParamTypes { expected: vec![Type::I32, Type::I32], found: vec![Type::I32, Type::F32] });
+    let err = instance.call::<EvalFR>(&mut store, "div", &[Value::I32(1), Value::I32(0)]).unwrap_err();
+    assert!(matches!(err, CallError::Trap(Trap { kind: TrapKind::IntDivByZero, .. })));
+}
+
+#[test]
+fn test_data_segments() {
+    let module = parse_module(r#"
+        (memory 1)
+        (table 1 funcref)
+        (global $base i32 (i32.const 16))
+        (data (i32.const 0) "\01\02" "\03")
+        (data (memory 0) (offset (global.get $base)) "hi")
+        (data $p "\aa\bb\cc\dd")
+        (elem $e func $f)
+        (func $f)
+        (func (param i32) (result i32) (i32.load8_u (local.get 0)))
+        (func (param i32 i32 i32) (result i32)
+            (memory.init $p (local.get 0) (local.get 1) (local.get 2))
+            (i32.load (local.get 0)))
+        (func (param i32) (result i32) (data.drop $p) (memory.init $p (i32.const 0) (i32.const 0) (local.get 0)) (i32.const 0))
+        (func (param i32) (result i32) (memory.init 0 (i32.const 0) (i32.const 0) (local.get 0)) (i32.const 0))
+        (func (param i32) (result i32) (elem.drop $e) (table.init $e (i32.const 0) (i32.const 0) (local.get 0)) (i32.const 0))"#).unwrap();
+    let run = |func_idx, args: &[i32]| run_module::<i32>(&module, func_idx, args).map_err(|trap| trap.kind);
+    // active segments were copied in when linking
+    assert_eq!(run(1, &[2]), Ok(vec![3]));
+    assert_eq!(run(1, &[17]), Ok(vec![b'i' as i32]));
+    assert_eq!(run(2, &[100, 1, 2]), Ok(vec![0xCCBB]));
+    assert_eq!(run(2, &[0, 4, 0]), Ok(vec![0x030201]));
+    assert_eq!(run(2, &[100, 2, 3]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(2, &[65535, 0, 2]), Err(TrapKind::MemoryOutOfBounds));
+    // dropped segments, and active ones once linked, are empty
+    assert_eq!(run(3, &[0]), Ok(vec![0]));
+    assert_eq!(run(3, &[1]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(4, &[0]), Ok(vec![0]));
+    assert_eq!(run(4, &[1]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(5, &[0]), Ok(vec![0]));
+    assert_eq!(run(5, &[1]), Err(TrapKind::TableOutOfBounds));
+    let linked = module.link().unwrap();
+    assert_eq!(linked.datas, [vec![], vec![], vec![0xAA, 0xBB, 0xCC, 0xDD]]);
+
+    let module = parse_module(r#"(memory (data "ab" "c")) (func (result i32) (i32.load8_u (i32.const 2)))"#).unwrap();
+    assert_eq!(module.memories, [Memory { min: 1, max: Some(1) }]);
+    assert_eq!(module.datas, [Data { mode: DataMode::Active { memory: 0, offset: ConstExpr::I32Const(0) }, init: b"abc".to_vec() }]);
+    assert_eq!(run_module(&module, 0, &[]), Ok(vec![b'c' as i32]));
+
+    // bounds and types are checked when instantiating
+    let link = |wat: &str| parse_module(wat).unwrap().link().map(|_| ());
+    assert_eq!(link(r#"(memory 1) (data (i32.const 65535) "ab")"#), Err(LinkError::DataOutOfBounds(0)));
+    assert_eq!(link(r#"(memory 1) (data (i32.const -1) "a")"#), Err(LinkError::DataOutOfBounds(0)));
+    assert_eq!(link(r#"(memory 0) (data (i32.const 0) "")"#), Ok(()));
+    assert_eq!(link(r#"(memory 0) (data (i32.const 1) "")"#), Err(LinkError::DataOutOfBounds(0)));
+    assert_eq!(link(r#"(data "passive needs no memory")"#), Ok(()));
+    let module = Module { datas: vec![Data { mode: DataMode::Active { memory: 0, offset: ConstExpr::I32Const(0) }, init: vec![] }], ..Module::default() };
+    assert_eq!(module.link().map(|_| ()), Err(LinkError::InvalidData { data: 0, kind: ValidationErrorKind::UnknownMemory(0) }));
+    let kind = ValidationErrorKind::TypeMismatch { expected: Type::I32, found: Type::I64 };
+    assert_eq!(link(r#"(memory 1) (data (offset (i64.const 0)) "a")"#), Err(LinkError::InvalidData { data: 0, kind }));
+
+    // a failed instantiation leaves nothing behind, so the store's one
+    // memory is still free
+    let mut store = Store::default();
+    let bad_data = parse_module(r#"(memory 1) (global i32 (i32.const 0)) (func) (data (i32.const 65535) "ab")"#).unwrap();
+    assert_eq!(Instance::new::<Eval>(&mut store, &bad_data).map(|_| ()), Err(LinkError::DataOutOfBounds(0)));
+    let bad_elem = parse_module("(memory 1) (table 1 funcref) (func) (elem (i32.const 1) 0)").unwrap();
+    assert_eq!(Instance::new::<Eval>(&mut store, &bad_elem).map(|_| ()), Err(LinkError::ElemOutOfBounds(0)));
+    assert!(store.memory.is_none() && store.tables.is_empty() && store.globals.is_empty() && store.types.is_empty());
+    assert!(store.linked.funcs.is_empty() && store.linked.code.is_empty() && store.linked.elems.is_empty() && store.linked.datas.is_empty());
+    let good = parse_module(r#"(memory 1) (data (i32.const 0) "\2a") (func (export "f") (result i32) (i32.load8_u (i32.const 0)))"#).unwrap();
+    let instance = Instance::new::<Eval>(&mut store, &good).unwrap();
+    assert_eq!(invoke_everywhere(&store, instance.func("f").unwrap(), &[]), Ok(vec![42]));
+
+    use ValidationErrorKind::*;
+    let errors: &[(&str, ValidationErrorKind, usize)] = &[
+        (r#"(data "") (func (memory.init 0 (i32.const 0) (i32.const 0) (i32.const 0)))"#, UnknownMemory(0), 6),
+        (r#"(memory 1) (data "") (func (memory.init 0 (i32.const 0) (i32.const 0)))"#, StackUnderflow, 4),
+    ];
+    for (wat, kind, ip) in errors {
+        let module = parse_module(wat).unwrap();
+        let err = TypedValidate::from_module(&module, 0).dispatch();
+        assert_eq!(err, Err(ValidationError { kind: *kind, ip: *ip, depth: 1 }), "{wat}");
+    }
+    assert!(parse_module("(memory 1) (func (data.drop 0))").is_err());
+    assert!(parse_module("(func (elem.drop $e))").is_err());
+    assert!(parse_module(r#"(func (export "\ff"))"#).is_err());
+    use CodeEntry::*;
+    use Opcode::*;
+    let mut validate = TypedValidate::new(vec![Op(DataDrop), I32Imm(1), Op(End)], vec![], vec![]);
+    validate.datas = 1;
+    assert_eq!(validate.dispatch(), Err(ValidationError { kind: UnknownData(1), ip: 0, depth: 1 }));
+    let mut validate = TypedValidate::new(vec![Op(ElemDrop), I32Imm(0), Op(End)], vec![], vec![]);
+    assert_eq!(validate.dispatch(), Err(ValidationError { kind: UnknownElem(0), ip: 0, depth: 1 }));
+
+    let module = parse_module(r#"(memory 1) (data "") (data "") (func (memory.init 1 (i32.const 0) (i32.const 0) (i32.const 0)) (data.drop 1))"#).unwrap();
+    let text = disassemble(&module.codeptr(0), &[], &[]);
+    assert!(text.contains("memory.init 1\n") && text.contains("data.drop 1\n"), "{text}");
+}
+
+#[test]
+fn test_bulk_memory() {
+    let module = parse_module(r#"
+        (memory 1 2)
+        (data (i32.const 0) "\01\02\03\04\05")
+        (func (param i32 i32 i32) (result i32 i32)
+            (memory.copy (local.get 0) (local.get 1) (local.get 2))
+            (i32.load (i32.const 0)) (i32.load (i32.const 4)))
+        (func (param i32 i32 i32) (result i32 i32)
+            (memory.fill (local.get 0) (local.get 1) (local.get 2))
+            (i32.load (i32.const 0)) (i32.load (i32.const 4)))
+        (func (result i32)
+            (drop (memory.grow (i32.const 1)))
+            (memory.copy (i32.const 65536) (i32.const 0) (i32.const 4))
+            (i32.load (i32.const 65536)))"#).unwrap();
+    let run = |func_idx, args: &[i32]| run_module::<i32>(&module, func_idx, args).map_err(|trap| trap.kind);
+    // overlapping either way round reads the source as it was
+    assert_eq!(run(0, &[1, 0, 4]), Ok(vec![0x03020101, 0x04]));
+    assert_eq!(run(0, &[0, 1, 4]), Ok(vec![0x05040302, 0x05]));
+    assert_eq!(run(0, &[65536, 0, 0]), Ok(vec![0x04030201, 0x05]));
+    assert_eq!(run(0, &[65535, 0, 2]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(0, &[0, 65535, 2]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(0, &[65537, 0, 0]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(0, &[0, 0, -1]), Err(TrapKind::MemoryOutOfBounds));
+    // only the value's low byte is written
+    assert_eq!(run(1, &[1, 0x1FF, 2]), Ok(vec![0x04FFFF01, 0x05]));
+    assert_eq!(run(1, &[65536, 0, 0]), Ok(vec![0x04030201, 0x05]));
+    assert_eq!(run(1, &[65535, 0, 2]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(1, &[-1, 0, 0]), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(run(2, &[]), Ok(vec![0x04030201]));
+
+    // nothing is written when a range is out of bounds
+    let mut memory = LinearMemory::new(1, None);
+    assert_eq!(memory.fill(65534, 7, 4), Err(TrapKind::MemoryOutOfBounds));
+    assert_eq!(memory.copy(0, 65534, 4), Err(TrapKind::MemoryOutOfBounds));
+    assert!(memory.bytes.iter().all(|&b| b == 0));
+
+    use ValidationErrorKind::*;
+    let errors: &[(&str, ValidationErrorKind, usize)] = &[
+        ("(func (memory.fill (i32.const 0) (i32.const 0) (i32.const 0)))", UnknownMemory(0), 6),
+        ("(func (memory.copy (i32.const 0) (i32.const 0) (i32.const 0)))", UnknownMemory(0), 6),
+        ("(memory 1) (func (memory.copy (i32.const 0) (i64.const 0) (i32.const 0)))", TypeMismatch { expected: Type::I32, found: Type::I64 }, 6),
+        ("(memory 1) (func (memory.fill (i32.const 0) (i32.const 0)))", StackUnderflow, 4),
+    ];
+    for (wat, kind, ip) in errors {
+        let module = parse_module(wat).unwrap();
+        let err = TypedValidate::from_module(&module, 0).dispatch();
+        assert_eq!(err, Err(ValidationError { kind: *kind, ip: *ip, depth: 1 }), "{wat}");
+    }
 }
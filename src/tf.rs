@@ -1,5 +1,12 @@
-use crate::{CodePtr, Balloon, cbdif, STEntry, Type, SidetableMeta, CtlEntry, Idk, CtlType};
-
+use crate::{CodePtr, CodeEntry, Balloon, cbdif, STEntry, Type, SidetableMeta, CtlEntry, Idk, CtlType, TrapKind};
+use crate::{ValidationError, ValidationErrorKind, BlockSig, table_index, Frame, FuncEntry, MAX_CALL_DEPTH, resolve_indirect};
+use crate::module::{FuncType, GlobalType, Module};
+use crate::num::{I32Binop, I32Unop, I32Relop, I64Binop, I64Unop, I64Relop, FBinop, FUnop, FRelop, Cvtop, Slot};
+use crate::mem::{I32LoadOp, I32StoreOp, LinearMemory, MemArg};
+use crate::table::{self, RefTable};
+use crate::instance::{call_host, HostFunc};
+
+use std::collections::HashSet;
 use std::fmt::Write;
 
 // I don't think Virgil really has the type system
@@ -8,6 +15,9 @@ use std::fmt::Write;
 // makes sure these are consistent
 pub trait CBD {
     type I32Val;
+    type I64Val;
+    type F32Val;
+    type F64Val;
     type StackVal: Clone + Into<Self::LocalVal>;
     type LocalVal: Clone + Into<Self::StackVal>;
     type CondVal: Balloon;
@@ -18,21 +28,92 @@ pub trait CBD {
     fn pushi_imm(&mut self, x: i32);
     fn pushi(&mut self, x: Self::I32Val);
 
+    fn popl(&mut self) -> Self::I64Val;
+    fn pushl_imm(&mut self, x: i64);
+    fn pushl(&mut self, x: Self::I64Val);
+
+    fn popf(&mut self) -> Self::F32Val;
+    fn pushf_imm(&mut self, x: f32);
+    fn pushf(&mut self, x: Self::F32Val);
+
+    fn popd(&mut self) -> Self::F64Val;
+    fn pushd_imm(&mut self, x: f64);
+    fn pushd(&mut self, x: Self::F64Val);
+
     fn push(&mut self, x: Self::StackVal);
     fn pop(&mut self) -> Self::StackVal;
 
     fn set_local(&mut self, idx: i32, val: Self::LocalVal);
     fn get_local(&mut self, idx: i32) -> Self::LocalVal;
 
-    fn start_block(&mut self, ty_index: usize);
-    fn start_loop(&mut self, ty_index: usize);
+    fn set_global(&mut self, idx: i32, val: Self::StackVal);
+    fn get_global(&mut self, idx: i32) -> Self::StackVal;
+
+    fn start_block(&mut self, ty: BlockSig);
+    fn start_loop(&mut self, ty: BlockSig);
+    fn start_if(&mut self, ty: BlockSig);
+    fn start_else(&mut self);
     fn end(&mut self);
 
     fn i32_add(&mut self, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
     fn i32_eqz(&mut self, x: Self::I32Val) -> Self::CondVal;
+    fn i32_binop(&mut self, op: I32Binop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
+    fn i32_unop(&mut self, op: I32Unop, x: Self::I32Val) -> Self::I32Val;
+    fn i32_relop(&mut self, op: I32Relop, x: Self::I32Val, y: Self::I32Val) -> Self::I32Val;
+    fn i64_add(&mut self, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val;
+    fn i64_binop(&mut self, op: I64Binop, x: Self::I64Val, y: Self::I64Val) -> Self::I64Val;
+    fn i64_unop(&mut self, op: I64Unop, x: Self::I64Val) -> Self::I64Val;
+    fn i64_relop(&mut self, op: I64Relop, x: Self::I64Val, y: Self::I64Val) -> Self::I32Val;
+    fn i64_eqz(&mut self, x: Self::I64Val) -> Self::I32Val;
+    fn f32_binop(&mut self, op: FBinop, x: Self::F32Val, y: Self::F32Val) -> Self::F32Val;
+    fn f32_unop(&mut self, op: FUnop, x: Self::F32Val) -> Self::F32Val;
+    fn f32_relop(&mut self, op: FRelop, x: Self::F32Val, y: Self::F32Val) -> Self::I32Val;
+    fn f64_binop(&mut self, op: FBinop, x: Self::F64Val, y: Self::F64Val) -> Self::F64Val;
+    fn f64_unop(&mut self, op: FUnop, x: Self::F64Val) -> Self::F64Val;
+    fn f64_relop(&mut self, op: FRelop, x: Self::F64Val, y: Self::F64Val) -> Self::I32Val;
+    fn cvtop(&mut self, op: Cvtop, x: Self::StackVal) -> Self::StackVal;
+    fn select(&mut self, ty: Option<Type>, cond: Self::I32Val, x: Self::StackVal, y: Self::StackVal) -> Self::StackVal;
+    fn unreachable(&mut self);
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, addr: Self::I32Val) -> Self::I32Val;
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, addr: Self::I32Val, val: Self::I32Val);
+    fn memory_size(&mut self) -> Self::I32Val;
+    fn memory_grow(&mut self, delta: Self::I32Val) -> Self::I32Val;
+    fn memory_init(&mut self, data_idx: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn data_drop(&mut self, data_idx: usize);
+    fn memory_copy(&mut self, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn memory_fill(&mut self, dst: Self::I32Val, val: Self::I32Val, len: Self::I32Val);
+    fn ref_null(&mut self, t: Type) -> Self::StackVal;
+    fn ref_is_null(&mut self, x: Self::StackVal) -> Self::I32Val;
+    fn ref_func(&mut self, func_idx: usize) -> Self::StackVal;
+    fn table_get(&mut self, table_idx: usize, idx: Self::I32Val) -> Self::StackVal;
+    fn table_set(&mut self, table_idx: usize, idx: Self::I32Val, val: Self::StackVal);
+    fn table_size(&mut self, table_idx: usize) -> Self::I32Val;
+    fn table_grow(&mut self, table_idx: usize, init: Self::StackVal, delta: Self::I32Val) -> Self::I32Val;
+    fn table_fill(&mut self, table_idx: usize, dst: Self::I32Val, val: Self::StackVal, len: Self::I32Val);
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, dst: Self::I32Val, src: Self::I32Val, len: Self::I32Val);
+    fn elem_drop(&mut self, elem_idx: usize);
 
     fn branch(&mut self, label_idx: usize);
+    fn branch_table(&mut self, labels: &[usize], idx: Self::I32Val);
     fn fallthru(&mut self);
+    fn call(&mut self, func_idx: usize);
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, idx: Self::I32Val);
+
+    // evaluators just follow the sidetable, so this is any other branch,
+    // the validator points it at the else arm
+    fn branch_if_false(&mut self) {
+        self.branch(0);
+    }
+
+    // return is a branch to the function's label, which evaluators find in
+    // the sidetable like any other
+    fn branch_return(&mut self) {
+        self.branch(0);
+    }
+
+    // code after an unconditional branch is dead, only the validator cares
+    fn set_unreachable(&mut self) { }
 
     fn cbd_i32_const(&mut self) {
         let x = self.codeptr_mut().read_imm_i32();
@@ -46,6 +127,272 @@ pub trait CBD {
         self.pushi(z);
     }
 
+    fn cbd_i32_binop(&mut self, op: I32Binop) {
+        let y = self.popi();
+        let x = self.popi();
+        let z = self.i32_binop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i32_unop(&mut self, op: I32Unop) {
+        let x = self.popi();
+        let z = self.i32_unop(op, x);
+        self.pushi(z);
+    }
+
+    fn cbd_i32_relop(&mut self, op: I32Relop) {
+        let y = self.popi();
+        let x = self.popi();
+        let z = self.i32_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i64_const(&mut self) {
+        let x = self.codeptr_mut().read_imm_i64();
+        self.pushl_imm(x);
+    }
+
+    fn cbd_i64_add(&mut self) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_add(x, y);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_binop(&mut self, op: I64Binop) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_binop(op, x, y);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_unop(&mut self, op: I64Unop) {
+        let x = self.popl();
+        let z = self.i64_unop(op, x);
+        self.pushl(z);
+    }
+
+    fn cbd_i64_relop(&mut self, op: I64Relop) {
+        let y = self.popl();
+        let x = self.popl();
+        let z = self.i64_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_i64_eqz(&mut self) {
+        let x = self.popl();
+        let z = self.i64_eqz(x);
+        self.pushi(z);
+    }
+
+    fn cbd_f32_const(&mut self) {
+        let x = self.codeptr_mut().read_imm_f32();
+        self.pushf_imm(x);
+    }
+
+    fn cbd_f32_binop(&mut self, op: FBinop) {
+        let y = self.popf();
+        let x = self.popf();
+        let z = self.f32_binop(op, x, y);
+        self.pushf(z);
+    }
+
+    fn cbd_f32_unop(&mut self, op: FUnop) {
+        let x = self.popf();
+        let z = self.f32_unop(op, x);
+        self.pushf(z);
+    }
+
+    fn cbd_f32_relop(&mut self, op: FRelop) {
+        let y = self.popf();
+        let x = self.popf();
+        let z = self.f32_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_f64_const(&mut self) {
+        let x = self.codeptr_mut().read_imm_f64();
+        self.pushd_imm(x);
+    }
+
+    fn cbd_f64_binop(&mut self, op: FBinop) {
+        let y = self.popd();
+        let x = self.popd();
+        let z = self.f64_binop(op, x, y);
+        self.pushd(z);
+    }
+
+    fn cbd_f64_unop(&mut self, op: FUnop) {
+        let x = self.popd();
+        let z = self.f64_unop(op, x);
+        self.pushd(z);
+    }
+
+    fn cbd_f64_relop(&mut self, op: FRelop) {
+        let y = self.popd();
+        let x = self.popd();
+        let z = self.f64_relop(op, x, y);
+        self.pushi(z);
+    }
+
+    fn cbd_cvtop(&mut self, op: Cvtop) {
+        let x = self.pop();
+        let z = self.cvtop(op, x);
+        self.push(z);
+    }
+
+    fn cbd_i32_load(&mut self, op: I32LoadOp) {
+        let memarg = self.codeptr_mut().read_memarg();
+        let addr = self.popi();
+        let x = self.i32_load(op, memarg, addr);
+        self.pushi(x);
+    }
+
+    fn cbd_i32_store(&mut self, op: I32StoreOp) {
+        let memarg = self.codeptr_mut().read_memarg();
+        let val = self.popi();
+        let addr = self.popi();
+        self.i32_store(op, memarg, addr, val);
+    }
+
+    fn cbd_memory_size(&mut self) {
+        let x = self.memory_size();
+        self.pushi(x);
+    }
+
+    fn cbd_memory_grow(&mut self) {
+        let delta = self.popi();
+        let x = self.memory_grow(delta);
+        self.pushi(x);
+    }
+
+    fn cbd_memory_init(&mut self) {
+        let data_idx = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.memory_init(data_idx as usize, dst, src, len);
+    }
+
+    fn cbd_data_drop(&mut self) {
+        let data_idx = self.codeptr_mut().read_imm_i32();
+        self.data_drop(data_idx as usize);
+    }
+
+    fn cbd_memory_copy(&mut self) {
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.memory_copy(dst, src, len);
+    }
+
+    fn cbd_memory_fill(&mut self) {
+        let len = self.popi();
+        let val = self.popi();
+        let dst = self.popi();
+        self.memory_fill(dst, val, len);
+    }
+
+    // references are popped and pushed untyped, like cvtop operands
+    fn cbd_ref_null(&mut self) {
+        let t = self.codeptr_mut().read_val_type();
+        let x = self.ref_null(t);
+        self.push(x);
+    }
+
+    fn cbd_ref_is_null(&mut self) {
+        let x = self.pop();
+        let z = self.ref_is_null(x);
+        self.pushi(z);
+    }
+
+    fn cbd_ref_func(&mut self) {
+        let func_idx = self.codeptr_mut().read_imm_i32();
+        let x = self.ref_func(func_idx as usize);
+        self.push(x);
+    }
+
+    fn cbd_table_get(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let idx = self.popi();
+        let x = self.table_get(table_idx as usize, idx);
+        self.push(x);
+    }
+
+    fn cbd_table_set(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let val = self.pop();
+        let idx = self.popi();
+        self.table_set(table_idx as usize, idx, val);
+    }
+
+    fn cbd_table_size(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let x = self.table_size(table_idx as usize);
+        self.pushi(x);
+    }
+
+    fn cbd_table_grow(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let delta = self.popi();
+        let init = self.pop();
+        let x = self.table_grow(table_idx as usize, init, delta);
+        self.pushi(x);
+    }
+
+    fn cbd_table_fill(&mut self) {
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let val = self.pop();
+        let dst = self.popi();
+        self.table_fill(table_idx as usize, dst, val, len);
+    }
+
+    fn cbd_table_copy(&mut self) {
+        let dst_table = self.codeptr_mut().read_imm_i32();
+        let src_table = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.table_copy(dst_table as usize, src_table as usize, dst, src, len);
+    }
+
+    fn cbd_table_init(&mut self) {
+        let elem_idx = self.codeptr_mut().read_imm_i32();
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let len = self.popi();
+        let src = self.popi();
+        let dst = self.popi();
+        self.table_init(table_idx as usize, elem_idx as usize, dst, src, len);
+    }
+
+    fn cbd_elem_drop(&mut self) {
+        let elem_idx = self.codeptr_mut().read_imm_i32();
+        self.elem_drop(elem_idx as usize);
+    }
+
+    fn cbd_nop(&mut self) { }
+
+    fn cbd_unreachable(&mut self) {
+        self.unreachable();
+        self.set_unreachable();
+    }
+
+    fn cbd_drop(&mut self) {
+        self.pop();
+    }
+
+    // the typed form names the operands' type, the untyped one leaves it to
+    // validation to check they match
+    fn cbd_select(&mut self, typed: bool) {
+        let ty = typed.then(|| self.codeptr_mut().read_val_type());
+        let cond = self.popi();
+        let y = self.pop();
+        let x = self.pop();
+        let z = self.select(ty, cond, x, y);
+        self.push(z);
+    }
+
     fn cbd_local_set(&mut self) {
         let idx = self.codeptr_mut().read_imm_i32();
         let val = self.pop();
@@ -65,6 +412,18 @@ pub trait CBD {
         self.set_local(idx, val.into());
     }
 
+    fn cbd_global_get(&mut self) {
+        let idx = self.codeptr_mut().read_imm_i32();
+        let val = self.get_global(idx);
+        self.push(val);
+    }
+
+    fn cbd_global_set(&mut self) {
+        let idx = self.codeptr_mut().read_imm_i32();
+        let val = self.pop();
+        self.set_global(idx, val);
+    }
+
     fn cbd_block(&mut self) {
         let ty = self.codeptr_mut().read_block_type();
         self.start_block(ty);
@@ -75,9 +434,30 @@ pub trait CBD {
         self.start_loop(ty);
     }
 
+    fn cbd_if(&mut self) {
+        let ty = self.codeptr_mut().read_block_type();
+        let condv = self.popi();
+        let condb = self.i32_eqz(condv);
+        self.start_if(ty);
+        cbdif! {
+            if (condb) then {
+                self.branch_if_false();
+            }, else {
+                self.fallthru();
+            }
+        }
+    }
+
+    // only reached by falling out of the then arm
+    fn cbd_else(&mut self) {
+        self.branch(0);
+        self.start_else();
+    }
+
     fn cbd_br(&mut self) {
         let label_idx = self.codeptr_mut().read_imm_i32();
         self.branch(label_idx as usize);
+        self.set_unreachable();
     }
 
     fn cbd_br_if(&mut self) {
@@ -93,6 +473,30 @@ pub trait CBD {
             }
         }
     }
+
+    fn cbd_return(&mut self) {
+        self.branch_return();
+        self.set_unreachable();
+    }
+
+    fn cbd_call(&mut self) {
+        let func_idx = self.codeptr_mut().read_imm_i32();
+        self.call(func_idx as usize);
+    }
+
+    fn cbd_call_indirect(&mut self) {
+        let type_idx = self.codeptr_mut().read_imm_i32();
+        let table_idx = self.codeptr_mut().read_imm_i32();
+        let idx = self.popi();
+        self.call_indirect(type_idx as usize, table_idx as usize, idx);
+    }
+
+    fn cbd_br_table(&mut self) {
+        let labels = self.codeptr_mut().read_labels();
+        let idx = self.popi();
+        self.branch_table(&labels, idx);
+        self.set_unreachable();
+    }
     
     fn cbd_end(&mut self) {
         self.end();
@@ -100,17 +504,41 @@ pub trait CBD {
 }
 
 pub struct TypedEval {
-    pub stack: Vec<i32>,
-    pub locals: Vec<i32>,
+    pub stack: Vec<Slot>,
+    pub locals: Vec<Slot>,
     pub codeptr: CodePtr,
     pub sidetable: Vec<STEntry>,
     pub stp: usize,
+    pub trap: Option<TrapKind>,
+    pub frames: Vec<Frame>,
+    pub funcs: Vec<FuncEntry>, // the module's function index space, see module::Linked
+    pub hosts: Vec<HostFunc>, // see FuncEntry::host
+    pub tables: Vec<RefTable>,
+    pub elems: Vec<Vec<Option<usize>>>, // each segment's references, empty once dropped
+    pub sigs: Vec<usize>, // canonical id of each type index
+    pub memory: LinearMemory,
+    pub datas: Vec<Vec<u8>>, // each segment's bytes, empty once dropped
+    pub globals: Vec<Slot>,
+}
+
+impl TypedEval {
+    fn set_trap(&mut self, kind: TrapKind) {
+        self.trap.get_or_insert(kind);
+    }
+
+    // the params and locals of every active call are laid end to end
+    fn local_slot(&self, idx: i32) -> usize {
+        self.frames.last().map_or(0, |f| f.locals_base) + idx as u32 as usize
+    }
 }
 
 impl CBD for TypedEval {
     type I32Val = i32;
-    type StackVal = i32;
-    type LocalVal = i32;
+    type I64Val = i64;
+    type F32Val = f32;
+    type F64Val = f64;
+    type StackVal = Slot;
+    type LocalVal = Slot;
     type CondVal = bool;
 
     fn codeptr_mut(&mut self) -> &mut CodePtr {
@@ -118,188 +546,1011 @@ impl CBD for TypedEval {
     }
 
     fn popi(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+        self.pop().i32()
     }
 
     fn pushi_imm(&mut self, x: i32) {
         self.pushi(x)
     }
     fn pushi(&mut self, x: i32) {
-        self.stack.push(x)
+        self.stack.push(x.into())
     }
 
-    fn push(&mut self, x: i32) {
-        self.stack.push(x)
+    fn popl(&mut self) -> i64 {
+        self.pop().i64()
     }
-    fn pop(&mut self) -> i32 {
-        self.stack.pop().unwrap()
+
+    fn pushl_imm(&mut self, x: i64) {
+        self.pushl(x)
+    }
+    fn pushl(&mut self, x: i64) {
+        self.stack.push(x.into())
     }
 
-    fn set_local(&mut self, idx: i32, val: i32) {
-        self.locals[idx as usize] = val;
+    fn popf(&mut self) -> f32 {
+        self.pop().f32()
     }
 
-    fn get_local(&mut self, idx: i32) -> i32 {
-        self.locals[idx as usize]
+    fn pushf_imm(&mut self, x: f32) {
+        self.pushf(x)
+    }
+    fn pushf(&mut self, x: f32) {
+        self.stack.push(x.into())
     }
 
-    fn start_block(&mut self, _ty_index: usize) { }
-    fn start_loop(&mut self, _ty_index: usize) { }
-    fn end(&mut self) { }
+    fn popd(&mut self) -> f64 {
+        self.pop().f64()
+    }
 
-    fn i32_add(&mut self, x: i32, y: i32) -> i32 {
-        x + y
+    fn pushd_imm(&mut self, x: f64) {
+        self.pushd(x)
+    }
+    fn pushd(&mut self, x: f64) {
+        self.stack.push(x.into())
     }
 
-    fn i32_eqz(&mut self, x: i32) -> bool {
-        x == 0
+    fn push(&mut self, x: Slot) {
+        self.stack.push(x)
+    }
+    fn pop(&mut self) -> Slot {
+        self.stack.pop().unwrap_or_else(|| {
+            self.set_trap(TrapKind::StackUnderflow);
+            Slot::default()
+        })
     }
 
-    fn branch(&mut self, _label_idx: usize) {
-        self.stp += 1;
-        let ste = self.sidetable[self.stp];
-        // stupid casts
-        self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
-        self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
+    fn set_local(&mut self, idx: i32, val: Slot) {
+        let slot = self.local_slot(idx);
+        match self.locals.get_mut(slot) {
+            Some(local) => *local = val,
+            None => self.set_trap(TrapKind::LocalOutOfRange),
+        }
     }
 
-    fn fallthru(&mut self) {
-        self.stp += 1; // we know that only one of branch or fallthru will run in this case,
-                       // but seems iffy
+    fn get_local(&mut self, idx: i32) -> Slot {
+        match self.locals.get(self.local_slot(idx)) {
+            Some(&local) => local,
+            None => {
+                self.set_trap(TrapKind::LocalOutOfRange);
+                Slot::default()
+            }
+        }
     }
-}
 
-pub struct TypedValidate {
-    pub stack: Vec<Type>,
-    pub locals: Vec<Type>,
-    pub ctl_entries: Vec<CtlEntry>,
-    pub ctl_stack: Vec<usize>,
-    pub codeptr: CodePtr,
-    pub sidetable_meta: Vec<SidetableMeta>, // idx = br_index
-}
+    fn set_global(&mut self, idx: i32, val: Slot) {
+        match self.globals.get_mut(idx as u32 as usize) {
+            Some(global) => *global = val,
+            None => self.set_trap(TrapKind::GlobalOutOfRange),
+        }
+    }
 
-impl CBD for TypedValidate {
-    type I32Val = Type;
-    type StackVal = Type;
-    type LocalVal = Type;
-    type CondVal = Idk;
+    fn get_global(&mut self, idx: i32) -> Slot {
+        match self.globals.get(idx as u32 as usize) {
+            Some(&global) => global,
+            None => {
+                self.set_trap(TrapKind::GlobalOutOfRange);
+                Slot::default()
+            }
+        }
+    }
 
-    fn codeptr_mut(&mut self) -> &mut CodePtr {
-        &mut self.codeptr
+    fn start_block(&mut self, _ty: BlockSig) { }
+    fn start_loop(&mut self, _ty: BlockSig) { }
+    fn start_if(&mut self, _ty: BlockSig) { }
+    fn start_else(&mut self) { }
+    // only a function's own End returns, its results are already on top
+    fn end(&mut self) {
+        let Some(&frame) = self.frames.last() else { return };
+        if self.codeptr.ip == self.funcs[frame.func].end_ip {
+            self.frames.pop();
+            self.locals.truncate(frame.locals_base);
+            self.codeptr.ip = frame.ret_ip;
+            self.stp = frame.ret_stp;
+        }
     }
 
-    fn popi(&mut self) -> Type {
-        assert!(self.stack.pop().is_some_and(|t| t == Type::I32));
-        Type::I32
+    fn call(&mut self, func_idx: usize) {
+        let Some(&f) = self.funcs.get(func_idx) else {
+            self.set_trap(TrapKind::FuncOutOfRange);
+            return;
+        };
+        if self.frames.len() == MAX_CALL_DEPTH {
+            self.set_trap(TrapKind::CallStackExhausted);
+            return;
+        }
+        // a host function returns before the next op, no frame needed
+        if let Some(host) = f.host {
+            if let Err(kind) = call_host(&self.hosts[host], &mut self.memory, &mut self.stack) {
+                self.set_trap(kind);
+            }
+            return;
+        }
+        let Some(args) = self.stack.len().checked_sub(f.params) else {
+            self.set_trap(TrapKind::StackUnderflow);
+            return;
+        };
+        // the args become the callee's first locals
+        let locals_base = self.locals.len();
+        self.locals.extend(self.stack.drain(args..));
+        self.locals.resize(locals_base + f.params + f.locals, Slot::default());
+        self.frames.push(Frame { func: func_idx, locals_base, ret_ip: self.codeptr.ip, ret_stp: self.stp });
+        self.codeptr.ip = f.ip;
+        self.stp = f.stp;
     }
 
-    fn pushi_imm(&mut self, _: i32) {
-        self.stack.push(Type::I32)
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, idx: i32) {
+        match resolve_indirect(&self.tables[table_idx].elems, &self.funcs, self.sigs[type_idx], idx) {
+            Ok(func_idx) => self.call(func_idx),
+            Err(kind) => self.set_trap(kind),
+        }
     }
 
-    fn pushi(&mut self, t: Type) {
-        assert!(t == Type::I32);
-        self.stack.push(Type::I32)
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, addr: i32) -> i32 {
+        self.memory.load(op, memarg, addr).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
     }
 
-    fn push(&mut self, t: Type) {
-        self.stack.push(t)
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, addr: i32, val: i32) {
+        if let Err(kind) = self.memory.store(op, memarg, addr, val) {
+            self.set_trap(kind);
+        }
     }
 
-    fn pop(&mut self) -> Type {
-        self.stack.pop().unwrap()
+    fn memory_size(&mut self) -> i32 {
+        self.memory.pages() as i32
     }
 
-    fn set_local(&mut self, idx: i32, val: Type) {
-        self.locals[idx as usize] = val;
+    fn memory_grow(&mut self, delta: i32) -> i32 {
+        self.memory.grow(delta as u32)
     }
 
-    fn get_local(&mut self, idx: i32) -> Type {
-        self.locals[idx as usize]
+    fn memory_init(&mut self, data_idx: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.memory.init(dst, &self.datas[data_idx], src, len) {
+            self.set_trap(kind);
+        }
     }
 
-    fn start_block(&mut self, _ty_index: usize) {
-        self.ctl_stack.push(self.ctl_entries.len());
-        self.ctl_entries.push(CtlEntry {
-            tipe: CtlType::Block,
-            cont_ip: 0, // filled in later
-            cont_stp: self.sidetable_meta.len() - 1,
-        });
+    fn data_drop(&mut self, data_idx: usize) {
+        self.datas[data_idx] = vec![];
     }
 
-    fn start_loop(&mut self, _ty_index: usize) { 
-        self.ctl_stack.push(self.ctl_entries.len());
-        self.ctl_entries.push(CtlEntry {
-            tipe: CtlType::Loop,
-            cont_ip: self.codeptr.ip,
-            cont_stp: self.sidetable_meta.len() - 1,
-        });
+    fn memory_copy(&mut self, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.memory.copy(dst, src, len) {
+            self.set_trap(kind);
+        }
     }
 
-    fn i32_add(&mut self, _: Type, _: Type) -> Type {
-        Type::I32
+    fn memory_fill(&mut self, dst: i32, val: i32, len: i32) {
+        if let Err(kind) = self.memory.fill(dst, val, len) {
+            self.set_trap(kind);
+        }
     }
 
-    fn i32_eqz(&mut self, t: Type) -> Idk {
-        assert!(t == Type::I32);
-        Idk
+    fn i32_add(&mut self, x: i32, y: i32) -> i32 {
+        x.wrapping_add(y)
     }
 
-    fn branch(&mut self, label_idx: usize) {
-        let ctl_idx = self.ctl_stack.last().unwrap() - label_idx;
-        self.sidetable_meta.push(SidetableMeta {
-            br_ip: self.codeptr.ip,
-            target_ctl_idx: ctl_idx,
-        });
-        // validate
+    fn i32_eqz(&mut self, x: i32) -> bool {
+        x == 0
     }
 
-    fn fallthru(&mut self) {
-        // validate
+    fn i32_binop(&mut self, op: I32Binop, x: i32, y: i32) -> i32 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
     }
 
-    fn end(&mut self) {
-        let ctl_idx = self.ctl_stack.pop().unwrap();
-        let ctl = &mut self.ctl_entries[ctl_idx];
-        if ctl.tipe == CtlType::Block {
-            ctl.cont_ip = self.codeptr.ip;
-            ctl.cont_stp = self.sidetable_meta.len() - 1;
-        }
+    fn i32_unop(&mut self, op: I32Unop, x: i32) -> i32 {
+        op.eval(x)
     }
-}
 
-impl TypedValidate {
-    fn is_loop(&self) -> bool {
-        self.ctl_entries.last().unwrap().tipe == CtlType::Loop
+    fn i32_relop(&mut self, op: I32Relop, x: i32, y: i32) -> i32 {
+        op.eval(x, y)
     }
 
-    pub fn build_sidetable(&self) -> Vec<STEntry> {
-        self.sidetable_meta.iter().enumerate().map(|(stp, br_meta)| {
-            let target_ctl_idx = br_meta.target_ctl_idx;
-            let target_ctl = &self.ctl_entries[target_ctl_idx];
-            STEntry {
-                ip_delta: (target_ctl.cont_ip as isize) - (br_meta.br_ip as isize),
-                stp_delta: (target_ctl.cont_stp as isize) - (stp as isize),
-            }
-        }).collect()
+    fn i64_add(&mut self, x: i64, y: i64) -> i64 {
+        x.wrapping_add(y)
     }
-}
 
-pub struct TypedCompiler {
-    pub gen: String,
-    pub codeptr: CodePtr,
-    pub ic: usize,
-}
+    fn i64_binop(&mut self, op: I64Binop, x: i64, y: i64) -> i64 {
+        op.eval(x, y).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            0
+        })
+    }
 
-impl TypedCompiler {
-    pub fn fv(&mut self) -> usize {
-        self.ic += 1;
+    fn i64_unop(&mut self, op: I64Unop, x: i64) -> i64 {
+        op.eval(x)
+    }
+
+    fn i64_relop(&mut self, op: I64Relop, x: i64, y: i64) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn i64_eqz(&mut self, x: i64) -> i32 {
+        (x == 0) as i32
+    }
+
+    fn f32_binop(&mut self, op: FBinop, x: f32, y: f32) -> f32 {
+        op.eval(x, y)
+    }
+
+    fn f32_unop(&mut self, op: FUnop, x: f32) -> f32 {
+        op.eval(x)
+    }
+
+    fn f32_relop(&mut self, op: FRelop, x: f32, y: f32) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn f64_binop(&mut self, op: FBinop, x: f64, y: f64) -> f64 {
+        op.eval(x, y)
+    }
+
+    fn f64_unop(&mut self, op: FUnop, x: f64) -> f64 {
+        op.eval(x)
+    }
+
+    fn f64_relop(&mut self, op: FRelop, x: f64, y: f64) -> i32 {
+        op.eval(x, y)
+    }
+
+    fn cvtop(&mut self, op: Cvtop, x: Slot) -> Slot {
+        op.eval(x).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            Slot::default()
+        })
+    }
+
+    fn select(&mut self, _ty: Option<Type>, cond: i32, x: Slot, y: Slot) -> Slot {
+        if cond != 0 { x } else { y }
+    }
+
+    fn unreachable(&mut self) {
+        self.set_trap(TrapKind::Unreachable);
+    }
+
+    fn ref_null(&mut self, _t: Type) -> Slot {
+        Slot::from(None)
+    }
+
+    fn ref_is_null(&mut self, x: Slot) -> i32 {
+        x.reference().is_none() as i32
+    }
+
+    fn ref_func(&mut self, func_idx: usize) -> Slot {
+        Slot::from(Some(func_idx))
+    }
+
+    fn table_get(&mut self, table_idx: usize, idx: i32) -> Slot {
+        self.tables[table_idx].get(idx).map(Slot::from).unwrap_or_else(|kind| {
+            self.set_trap(kind);
+            Slot::default()
+        })
+    }
+
+    fn table_set(&mut self, table_idx: usize, idx: i32, val: Slot) {
+        if let Err(kind) = self.tables[table_idx].set(idx, val.reference()) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn table_size(&mut self, table_idx: usize) -> i32 {
+        self.tables[table_idx].size() as i32
+    }
+
+    fn table_grow(&mut self, table_idx: usize, init: Slot, delta: i32) -> i32 {
+        self.tables[table_idx].grow(delta as u32, init.reference())
+    }
+
+    fn table_fill(&mut self, table_idx: usize, dst: i32, val: Slot, len: i32) {
+        if let Err(kind) = self.tables[table_idx].fill(dst, val.reference(), len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = table::copy(&mut self.tables, dst_table, src_table, dst, src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, dst: i32, src: i32, len: i32) {
+        if let Err(kind) = self.tables[table_idx].init(dst, &self.elems[elem_idx], src, len) {
+            self.set_trap(kind);
+        }
+    }
+
+    fn elem_drop(&mut self, elem_idx: usize) {
+        self.elems[elem_idx] = vec![];
+    }
+
+    fn branch(&mut self, _label_idx: usize) {
+        self.stp += 1;
+        let ste = self.sidetable[self.stp];
+        // stupid casts
+        self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
+        self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
+        ste.unwind(&mut self.stack);
+    }
+
+    // a br_table's entries follow each other, skip to the one we take
+    fn branch_table(&mut self, labels: &[usize], idx: i32) {
+        self.stp += table_index(idx, labels.len());
+        self.branch(0);
+    }
+
+    fn fallthru(&mut self) {
+        self.stp += 1; // we know that only one of branch or fallthru will run in this case,
+                       // but seems iffy
+    }
+}
+
+pub struct TypedValidate {
+    pub stack: Vec<Type>,
+    pub locals: Vec<Type>,
+    pub ctl_entries: Vec<CtlEntry>,
+    pub ctl_stack: Vec<usize>,
+    pub codeptr: CodePtr,
+    pub sidetable_meta: Vec<SidetableMeta>, // idx = br_index
+    pub types: Vec<FuncType>, // for type index block types
+    pub funcs: Vec<FuncType>, // the module's function index space, for calls
+    pub tables: Vec<Type>, // element types
+    pub elems: Vec<Type>, // element segment types
+    pub refs: HashSet<usize>, // functions ref.func may name
+    pub memories: usize,
+    pub datas: usize, // data segment count
+    pub globals: Vec<GlobalType>,
+    pub op_ip: usize, // ip of the op being validated, for errors
+    pub error: Option<ValidationError>,
+}
+
+impl CBD for TypedValidate {
+    type I32Val = Type;
+    type I64Val = Type;
+    type F32Val = Type;
+    type F64Val = Type;
+    type StackVal = Type;
+    type LocalVal = Type;
+    type CondVal = Idk;
+
+    fn codeptr_mut(&mut self) -> &mut CodePtr {
+        &mut self.codeptr
+    }
+
+    fn popi(&mut self) -> Type {
+        let t = self.pop();
+        self.expect_type(Type::I32, t)
+    }
+
+    fn pushi_imm(&mut self, _: i32) {
+        self.stack.push(Type::I32)
+    }
+
+    fn pushi(&mut self, t: Type) {
+        let t = self.expect_type(Type::I32, t);
+        self.stack.push(t)
+    }
+
+    fn popl(&mut self) -> Type {
+        let t = self.pop();
+        self.expect_type(Type::I64, t)
+    }
+
+    fn pushl_imm(&mut self, _: i64) {
+        self.stack.push(Type::I64)
+    }
+
+    fn pushl(&mut self, t: Type) {
+        let t = self.expect_type(Type::I64, t);
+        self.stack.push(t)
+    }
+
+    fn popf(&mut self) -> Type {
+        let t = self.pop();
+        self.expect_type(Type::F32, t)
+    }
+
+    fn pushf_imm(&mut self, _: f32) {
+        self.stack.push(Type::F32)
+    }
+
+    fn pushf(&mut self, t: Type) {
+        let t = self.expect_type(Type::F32, t);
+        self.stack.push(t)
+    }
+
+    fn popd(&mut self) -> Type {
+        let t = self.pop();
+        self.expect_type(Type::F64, t)
+    }
+
+    fn pushd_imm(&mut self, _: f64) {
+        self.stack.push(Type::F64)
+    }
+
+    fn pushd(&mut self, t: Type) {
+        let t = self.expect_type(Type::F64, t);
+        self.stack.push(t)
+    }
+
+    fn push(&mut self, t: Type) {
+        self.stack.push(t)
+    }
+
+    fn pop(&mut self) -> Type {
+        // can't pop values belonging to an enclosing block, but once the
+        // block is unreachable its stack has anything we need
+        let ctl = &self.ctl_entries[*self.ctl_stack.last().unwrap()];
+        if self.stack.len() > ctl.height {
+            return self.stack.pop().unwrap();
+        }
+        if !ctl.unreachable {
+            self.fail(ValidationErrorKind::StackUnderflow);
+        }
+        Type::Unknown
+    }
+
+    fn set_local(&mut self, idx: i32, val: Type) {
+        let t = self.get_local(idx);
+        self.expect_type(t, val);
+    }
+
+    fn get_local(&mut self, idx: i32) -> Type {
+        match self.locals.get(idx as usize) {
+            Some(&t) => t,
+            None => {
+                self.fail(ValidationErrorKind::UnknownLocal(idx));
+                Type::I32
+            }
+        }
+    }
+
+    fn set_global(&mut self, idx: i32, val: Type) {
+        let Some(&global) = self.globals.get(idx as u32 as usize) else {
+            self.fail(ValidationErrorKind::UnknownGlobal(idx as u32 as usize));
+            return;
+        };
+        if !global.mutable {
+            self.fail(ValidationErrorKind::ImmutableGlobal(idx as u32 as usize));
+        }
+        self.expect_type(global.ty, val);
+    }
+
+    fn get_global(&mut self, idx: i32) -> Type {
+        match self.globals.get(idx as u32 as usize) {
+            Some(global) => global.ty,
+            None => {
+                self.fail(ValidationErrorKind::UnknownGlobal(idx as u32 as usize));
+                Type::I32
+            }
+        }
+    }
+
+    fn start_block(&mut self, ty: BlockSig) {
+        self.push_ctl(CtlType::Block, 0, ty); // cont_ip filled in later
+    }
+
+    fn start_loop(&mut self, ty: BlockSig) { 
+        self.push_ctl(CtlType::Loop, self.codeptr.ip, ty);
+    }
+
+    fn start_if(&mut self, ty: BlockSig) {
+        self.push_ctl(CtlType::If, 0, ty);
+        let ctl = &self.ctl_entries[self.ctl_entries.len() - 1];
+        let if_false = CtlEntry { tipe: CtlType::IfFalse, params: vec![], results: vec![], ..*ctl };
+        self.ctl_entries.push(if_false);
+    }
+
+    fn start_else(&mut self) {
+        let ctl_idx = *self.ctl_stack.last().unwrap();
+        if self.ctl_entries[ctl_idx].tipe != CtlType::If {
+            self.fail(ValidationErrorKind::ElseWithoutIf);
+            return;
+        }
+        self.check_results(ctl_idx);
+
+        let ctl = &mut self.ctl_entries[ctl_idx];
+        self.stack.truncate(ctl.height);
+        self.stack.extend(ctl.params.clone());
+        ctl.tipe = CtlType::Else;
+        ctl.unreachable = false;
+
+        let if_false = &mut self.ctl_entries[ctl_idx + 1];
+        if_false.cont_ip = self.codeptr.ip;
+        if_false.cont_stp = self.sidetable_meta.len() - 1;
+    }
+
+    fn i32_add(&mut self, _: Type, _: Type) -> Type {
+        Type::I32
+    }
+
+    fn i32_eqz(&mut self, t: Type) -> Idk {
+        self.expect_type(Type::I32, t);
+        Idk
+    }
+
+    fn i32_binop(&mut self, _: I32Binop, _: Type, _: Type) -> Type {
+        Type::I32
+    }
+
+    fn i32_unop(&mut self, _: I32Unop, _: Type) -> Type {
+        Type::I32
+    }
+
+    fn i32_relop(&mut self, _: I32Relop, _: Type, _: Type) -> Type {
+        Type::I32
+    }
+
+    fn i64_add(&mut self, _: Type, _: Type) -> Type {
+        Type::I64
+    }
+
+    fn i64_binop(&mut self, _: I64Binop, _: Type, _: Type) -> Type {
+        Type::I64
+    }
+
+    fn i64_unop(&mut self, _: I64Unop, _: Type) -> Type {
+        Type::I64
+    }
+
+    fn i64_relop(&mut self, _: I64Relop, _: Type, _: Type) -> Type {
+        Type::I32
+    }
+
+    fn i64_eqz(&mut self, _: Type) -> Type {
+        Type::I32
+    }
+
+    fn f32_binop(&mut self, _: FBinop, _: Type, _: Type) -> Type {
+        Type::F32
+    }
+
+    fn f32_unop(&mut self, _: FUnop, _: Type) -> Type {
+        Type::F32
+    }
+
+    fn f32_relop(&mut self, _: FRelop, _: Type, _: Type) -> Type {
+        Type::I32
+    }
+
+    fn f64_binop(&mut self, _: FBinop, _: Type, _: Type) -> Type {
+        Type::F64
+    }
+
+    fn f64_unop(&mut self, _: FUnop, _: Type) -> Type {
+        Type::F64
+    }
+
+    fn f64_relop(&mut self, _: FRelop, _: Type, _: Type) -> Type {
+        Type::I32
+    }
+
+    // popped untyped, so the operand is checked here
+    fn cvtop(&mut self, op: Cvtop, t: Type) -> Type {
+        let (from, to) = op.types();
+        self.expect_type(from, t);
+        to
+    }
+
+    // untyped, the operands just have to agree, though either may be
+    // unknown once the stack is polymorphic
+    fn select(&mut self, ty: Option<Type>, _cond: Type, x: Type, y: Type) -> Type {
+        match ty {
+            Some(t) => {
+                self.expect_type(t, x);
+                self.expect_type(t, y)
+            }
+            None => {
+                let t = if x == Type::Unknown { y } else { self.expect_type(x, y) };
+                if t.is_ref() {
+                    self.fail(ValidationErrorKind::ExpectedNum(t));
+                }
+                t
+            }
+        }
+    }
+
+    // set_unreachable does the work
+    fn unreachable(&mut self) { }
+
+    fn ref_null(&mut self, t: Type) -> Type {
+        t
+    }
+
+    fn ref_is_null(&mut self, t: Type) -> Type {
+        if !t.is_ref() && t != Type::Unknown {
+            self.fail(ValidationErrorKind::ExpectedRef(t));
+        }
+        Type::I32
+    }
+
+    fn ref_func(&mut self, func_idx: usize) -> Type {
+        if func_idx >= self.funcs.len() {
+            self.fail(ValidationErrorKind::UnknownFunc(func_idx));
+        } else if !self.refs.contains(&func_idx) {
+            self.fail(ValidationErrorKind::UndeclaredFuncRef(func_idx));
+        }
+        Type::FuncRef
+    }
+
+    fn table_get(&mut self, table_idx: usize, _idx: Type) -> Type {
+        self.table_type(table_idx)
+    }
+
+    fn table_set(&mut self, table_idx: usize, _idx: Type, val: Type) {
+        let t = self.table_type(table_idx);
+        self.expect_type(t, val);
+    }
+
+    fn table_size(&mut self, table_idx: usize) -> Type {
+        self.table_type(table_idx);
+        Type::I32
+    }
+
+    fn table_grow(&mut self, table_idx: usize, init: Type, _delta: Type) -> Type {
+        let t = self.table_type(table_idx);
+        self.expect_type(t, init);
+        Type::I32
+    }
+
+    fn table_fill(&mut self, table_idx: usize, _dst: Type, val: Type, _len: Type) {
+        let t = self.table_type(table_idx);
+        self.expect_type(t, val);
+    }
+
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, _dst: Type, _src: Type, _len: Type) {
+        let dst = self.table_type(dst_table);
+        let src = self.table_type(src_table);
+        self.expect_type(dst, src);
+    }
+
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, _dst: Type, _src: Type, _len: Type) {
+        let t = self.table_type(table_idx);
+        match self.elems.get(elem_idx) {
+            Some(&elem) => { self.expect_type(t, elem); }
+            None => self.fail(ValidationErrorKind::UnknownElem(elem_idx)),
+        }
+    }
+
+    fn elem_drop(&mut self, elem_idx: usize) {
+        if elem_idx >= self.elems.len() {
+            self.fail(ValidationErrorKind::UnknownElem(elem_idx));
+        }
+    }
+
+    fn branch(&mut self, label_idx: usize) {
+        if label_idx >= self.ctl_stack.len() {
+            self.fail(ValidationErrorKind::UnknownLabel(label_idx));
+            return;
+        }
+        let ctl_idx = self.ctl_stack[self.ctl_stack.len() - 1 - label_idx];
+        let labels = self.ctl_entries[ctl_idx].label_types().to_vec();
+        self.pop_types(&labels);
+        self.sidetable_meta.push(SidetableMeta {
+            br_ip: self.codeptr.ip,
+            target_ctl_idx: ctl_idx,
+            val_count: labels.len(),
+            pop_count: self.stack.len().saturating_sub(self.ctl_entries[ctl_idx].height),
+        });
+        self.stack.extend(labels);
+    }
+
+    // an entry for every target, all of which must take the same values
+    fn branch_table(&mut self, labels: &[usize], _idx: Type) {
+        let Some(arities) = labels.iter().map(|&l| self.label_arity(l)).collect::<Option<Vec<_>>>() else {
+            return;
+        };
+        let expected = *arities.last().unwrap();
+        if let Some(&found) = arities.iter().find(|&&n| n != expected) {
+            self.fail(ValidationErrorKind::BrTableArity { expected, found });
+            return;
+        }
+        for &label in labels {
+            self.branch(label);
+        }
+    }
+
+    fn branch_return(&mut self) {
+        self.branch(self.ctl_stack.len() - 1);
+    }
+
+    fn call(&mut self, func_idx: usize) {
+        let Some(ty) = self.funcs.get(func_idx).cloned() else {
+            self.fail(ValidationErrorKind::UnknownFunc(func_idx));
+            return;
+        };
+        self.pop_types(&ty.params);
+        self.stack.extend(ty.results);
+    }
+
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, _addr: Type) -> Type {
+        self.check_memarg(memarg, op.width());
+        Type::I32
+    }
+
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, _addr: Type, _val: Type) {
+        self.check_memarg(memarg, op.width());
+    }
+
+    fn memory_size(&mut self) -> Type {
+        self.check_memory();
+        Type::I32
+    }
+
+    fn memory_grow(&mut self, _delta: Type) -> Type {
+        self.check_memory();
+        Type::I32
+    }
+
+    fn memory_init(&mut self, data_idx: usize, _dst: Type, _src: Type, _len: Type) {
+        self.check_memory();
+        self.check_data(data_idx);
+    }
+
+    fn data_drop(&mut self, data_idx: usize) {
+        self.check_data(data_idx);
+    }
+
+    fn memory_copy(&mut self, _dst: Type, _src: Type, _len: Type) {
+        self.check_memory();
+    }
+
+    fn memory_fill(&mut self, _dst: Type, _val: Type, _len: Type) {
+        self.check_memory();
+    }
+
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, _idx: Type) {
+        match self.table_type(table_idx) {
+            Type::FuncRef => {}
+            Type::Unknown => return,
+            found => {
+                self.fail(ValidationErrorKind::TypeMismatch { expected: Type::FuncRef, found });
+                return;
+            }
+        }
+        let Some(ty) = self.types.get(type_idx).cloned() else {
+            self.fail(ValidationErrorKind::UnknownType(type_idx));
+            return;
+        };
+        self.pop_types(&ty.params);
+        self.stack.extend(ty.results);
+    }
+
+    fn branch_if_false(&mut self) {
+        let ctl_idx = *self.ctl_stack.last().unwrap();
+        let params = self.ctl_entries[ctl_idx].params.len();
+        self.sidetable_meta.push(SidetableMeta {
+            br_ip: self.codeptr.ip,
+            target_ctl_idx: ctl_idx + 1,
+            val_count: params,
+            pop_count: 0,
+        });
+    }
+
+    fn fallthru(&mut self) {
+        // validate
+    }
+
+    fn set_unreachable(&mut self) {
+        let ctl_idx = *self.ctl_stack.last().unwrap();
+        let ctl = &mut self.ctl_entries[ctl_idx];
+        self.stack.truncate(ctl.height);
+        ctl.unreachable = true;
+    }
+
+    fn end(&mut self) {
+        let Some(&ctl_idx) = self.ctl_stack.last() else {
+            self.fail(ValidationErrorKind::UnbalancedEnd);
+            return;
+        };
+        self.check_results(ctl_idx);
+        let ctl = &self.ctl_entries[ctl_idx];
+        // without an else, the false edge passes the params through
+        if ctl.tipe == CtlType::If && ctl.params != ctl.results {
+            let (expected, found) = (ctl.results.len(), ctl.params.len());
+            self.fail(ValidationErrorKind::BlockArity { expected, found });
+        }
+
+        self.ctl_stack.pop();
+        let ctl = &mut self.ctl_entries[ctl_idx];
+        self.stack.truncate(ctl.height);
+        self.stack.extend(ctl.results.clone());
+        if matches!(ctl.tipe, CtlType::Block | CtlType::If | CtlType::Else) {
+            ctl.cont_ip = self.codeptr.ip;
+            ctl.cont_stp = self.sidetable_meta.len() - 1;
+        }
+        // branches to the function's label land on its End, which returns
+        if ctl.tipe == CtlType::Func {
+            ctl.cont_ip = self.codeptr.ip - 1;
+            ctl.cont_stp = self.sidetable_meta.len() - 1;
+        }
+        if ctl.tipe == CtlType::If {
+            let (cont_ip, cont_stp) = (ctl.cont_ip, ctl.cont_stp);
+            let if_false = &mut self.ctl_entries[ctl_idx + 1];
+            if_false.cont_ip = cont_ip;
+            if_false.cont_stp = cont_stp;
+        }
+    }
+}
+
+impl TypedValidate {
+    pub fn new(code: Vec<CodeEntry>, locals: Vec<Type>, results: Vec<Type>) -> Self {
+        let func = CtlEntry {
+            tipe: CtlType::Func,
+            cont_ip: code.len(),
+            cont_stp: 0,
+            height: 0,
+            unreachable: false,
+            params: vec![],
+            results,
+        };
+        TypedValidate {
+            stack: vec![],
+            locals,
+            ctl_entries: vec![func],
+            ctl_stack: vec![0],
+            codeptr: CodePtr { code, ip: 0 },
+            sidetable_meta: vec![SidetableMeta { br_ip: 0, target_ctl_idx: 0, val_count: 0, pop_count: 0 } ],
+            types: vec![],
+            funcs: vec![],
+            tables: vec![],
+            elems: vec![],
+            refs: HashSet::new(),
+            memories: 0,
+            datas: 0,
+            globals: vec![],
+            op_ip: 0,
+            error: None,
+        }
+    }
+
+    pub fn from_module(module: &Module, func_idx: usize) -> Self {
+        let code = module.funcs[func_idx].code.clone();
+        let results = module.func_type(func_idx).results.clone();
+        let mut validate = TypedValidate::new(code, module.local_types(func_idx), results);
+        validate.types = module.types.clone();
+        validate.funcs = module.func_types();
+        validate.tables = module.table_types().iter().map(|t| t.elem_type).collect();
+        validate.elems = module.elems.iter().map(|e| e.ty).collect();
+        validate.refs = module.declared_refs();
+        validate.memories = module.memory_types().len();
+        validate.datas = module.datas.len();
+        validate.globals = module.global_types();
+        validate
+    }
+
+    fn is_loop(&self) -> bool {
+        self.ctl_entries.last().unwrap().tipe == CtlType::Loop
+    }
+
+    pub fn fail(&mut self, kind: ValidationErrorKind) {
+        let (ip, depth) = (self.op_ip, self.ctl_stack.len());
+        self.error.get_or_insert(ValidationError { kind, ip, depth });
+    }
+
+    // Unknown for a missing table, which has already failed
+    fn table_type(&mut self, table_idx: usize) -> Type {
+        match self.tables.get(table_idx) {
+            Some(&t) => t,
+            None => {
+                self.fail(ValidationErrorKind::UnknownTable(table_idx));
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_memory(&mut self) {
+        if self.memories == 0 {
+            self.fail(ValidationErrorKind::UnknownMemory(0));
+        }
+    }
+
+    fn check_data(&mut self, data_idx: usize) {
+        if data_idx >= self.datas {
+            self.fail(ValidationErrorKind::UnknownData(data_idx));
+        }
+    }
+
+    fn check_memarg(&mut self, memarg: MemArg, width: usize) {
+        self.check_memory();
+        let natural = width.trailing_zeros();
+        if memarg.align > natural {
+            self.fail(ValidationErrorKind::AlignmentTooLarge { align: memarg.align, natural });
+        }
+    }
+
+    fn label_arity(&mut self, label_idx: usize) -> Option<usize> {
+        if label_idx >= self.ctl_stack.len() {
+            self.fail(ValidationErrorKind::UnknownLabel(label_idx));
+            return None;
+        }
+        let ctl_idx = self.ctl_stack[self.ctl_stack.len() - 1 - label_idx];
+        Some(self.ctl_entries[ctl_idx].label_types().len())
+    }
+
+    // the values a block leaves at its end, or at else for the then arm
+    fn check_results(&mut self, ctl_idx: usize) {
+        let ctl = &self.ctl_entries[ctl_idx];
+        let results = ctl.results.clone();
+        let found = self.stack.len().saturating_sub(ctl.height);
+        // too few is fine if the rest come from the polymorphic stack
+        if found > results.len() || (found < results.len() && !ctl.unreachable) {
+            self.fail(ValidationErrorKind::BlockArity { expected: results.len(), found });
+        }
+        self.pop_types(&results);
+    }
+
+    fn pop_types(&mut self, types: &[Type]) {
+        for &t in types.iter().rev() {
+            let val = self.pop();
+            self.expect_type(t, val);
+        }
+    }
+
+    // params stay on the stack, but belong to the new block
+    fn push_ctl(&mut self, tipe: CtlType, cont_ip: usize, ty: BlockSig) {
+        let (params, results) = ty.params_results(&self.types).unwrap_or_else(|| {
+            let BlockSig::Index(idx) = ty else { unreachable!() };
+            self.fail(ValidationErrorKind::UnknownType(idx));
+            (vec![], vec![])
+        });
+        self.pop_types(&params);
+        self.ctl_stack.push(self.ctl_entries.len());
+        self.ctl_entries.push(CtlEntry {
+            tipe,
+            cont_ip,
+            cont_stp: self.sidetable_meta.len() - 1,
+            height: self.stack.len(),
+            unreachable: false,
+            params: params.clone(),
+            results,
+        });
+        self.stack.extend(params);
+    }
+
+    fn expect_type(&mut self, expected: Type, found: Type) -> Type {
+        if found != expected && found != Type::Unknown {
+            self.fail(ValidationErrorKind::TypeMismatch { expected, found });
+        }
+        expected
+    }
+
+    pub fn build_sidetable(&self) -> Vec<STEntry> {
+        self.sidetable_meta.iter().enumerate().map(|(stp, br_meta)| {
+            let target_ctl_idx = br_meta.target_ctl_idx;
+            let target_ctl = &self.ctl_entries[target_ctl_idx];
+            STEntry {
+                ip_delta: (target_ctl.cont_ip as isize) - (br_meta.br_ip as isize),
+                stp_delta: (target_ctl.cont_stp as isize) - (stp as isize),
+                val_count: br_meta.val_count,
+                pop_count: br_meta.pop_count,
+            }
+        }).collect()
+    }
+}
+
+pub struct TypedCompiler {
+    pub gen: String,
+    pub codeptr: CodePtr,
+    pub ic: usize,
+}
+
+impl TypedCompiler {
+    pub fn fv(&mut self) -> usize {
+        self.ic += 1;
         self.ic
     }
 }
 
 impl CBD for TypedCompiler {
     type I32Val = ();
+    type I64Val = ();
+    type F32Val = ();
+    type F64Val = ();
     type StackVal = ();
     type LocalVal = ();
     type CondVal = Idk;
@@ -310,7 +1561,7 @@ impl CBD for TypedCompiler {
 
     fn popi(&mut self) -> () {
         let i = self.fv();
-        writeln!(&mut self.gen, "let x_{i} = self.stack.pop().unwrap();").unwrap();
+        writeln!(&mut self.gen, "let x_{i} = self.popi();").unwrap();
     }
 
     fn pushi_imm(&mut self, x: i32) {
@@ -318,7 +1569,47 @@ impl CBD for TypedCompiler {
     }
     fn pushi(&mut self, _: ()) {
         let i = self.ic;
-        writeln!(&mut self.gen, "self.stack.push(x_{i});").unwrap();
+        writeln!(&mut self.gen, "self.pushi(x_{i});").unwrap();
+    }
+
+    fn popl(&mut self) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.popl();").unwrap();
+    }
+
+    fn pushl_imm(&mut self, x: i64) {
+        writeln!(&mut self.gen, "self.pushl({x});").unwrap();
+    }
+    fn pushl(&mut self, _: ()) {
+        let i = self.ic;
+        writeln!(&mut self.gen, "self.pushl(x_{i});").unwrap();
+    }
+
+    fn popf(&mut self) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.popf();").unwrap();
+    }
+
+    // from bits, since NaNs and infinities have no literal
+    fn pushf_imm(&mut self, x: f32) {
+        writeln!(&mut self.gen, "self.pushf(f32::from_bits({:#x}));", x.to_bits()).unwrap();
+    }
+    fn pushf(&mut self, _: ()) {
+        let i = self.ic;
+        writeln!(&mut self.gen, "self.pushf(x_{i});").unwrap();
+    }
+
+    fn popd(&mut self) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.popd();").unwrap();
+    }
+
+    fn pushd_imm(&mut self, x: f64) {
+        writeln!(&mut self.gen, "self.pushd(f64::from_bits({:#x}));", x.to_bits()).unwrap();
+    }
+    fn pushd(&mut self, _: ()) {
+        let i = self.ic;
+        writeln!(&mut self.gen, "self.pushd(x_{i});").unwrap();
     }
 
     fn push(&mut self, _: ()) {
@@ -340,15 +1631,27 @@ impl CBD for TypedCompiler {
         writeln!(&mut self.gen, "let x_{i} = self.locals[{idx} as usize];").unwrap();
     }
 
-    fn start_block(&mut self, _ty_index: usize) { }
-    fn start_loop(&mut self, _ty_index: usize) { }
+    fn set_global(&mut self, idx: i32, _: ()) {
+        let i = self.ic;
+        writeln!(&mut self.gen, "self.globals[{idx} as usize] = x_{i};").unwrap();
+    }
+
+    fn get_global(&mut self, idx: i32) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.globals[{idx} as usize];").unwrap();
+    }
+
+    fn start_block(&mut self, _ty: BlockSig) { }
+    fn start_loop(&mut self, _ty: BlockSig) { }
+    fn start_if(&mut self, _ty: BlockSig) { }
+    fn start_else(&mut self) { }
     fn end(&mut self) { }
 
     fn i32_add(&mut self, _: (), _: ()) -> () {
         let i1 = self.ic - 1;
         let i2 = self.ic;
         let i3 = self.fv();
-        writeln!(&mut self.gen, "let x_{i3} = x_{i1} + x_{i2};").unwrap();
+        writeln!(&mut self.gen, "let x_{i3} = self.i32_add(x_{i1}, x_{i2});").unwrap();
     }
 
     fn i32_eqz(&mut self, _: ()) -> Idk {
@@ -358,6 +1661,117 @@ impl CBD for TypedCompiler {
         Idk
     }
 
+    fn i32_binop(&mut self, op: I32Binop, _: (), _: ()) {
+        let i1 = self.ic - 1; // rhs, popped first
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.i32_binop(I32Binop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn i32_unop(&mut self, op: I32Unop, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.i32_unop(I32Unop::{op:?}, x_{i1});").unwrap();
+    }
+
+    fn i32_relop(&mut self, op: I32Relop, _: (), _: ()) {
+        let i1 = self.ic - 1;
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.i32_relop(I32Relop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn i64_add(&mut self, _: (), _: ()) {
+        let i1 = self.ic - 1;
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.i64_add(x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn i64_binop(&mut self, op: I64Binop, _: (), _: ()) {
+        let i1 = self.ic - 1; // rhs, popped first
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.i64_binop(I64Binop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn i64_unop(&mut self, op: I64Unop, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.i64_unop(I64Unop::{op:?}, x_{i1});").unwrap();
+    }
+
+    fn i64_relop(&mut self, op: I64Relop, _: (), _: ()) {
+        let i1 = self.ic - 1;
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.i64_relop(I64Relop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn i64_eqz(&mut self, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = (x_{i1} == 0) as i32;").unwrap();
+    }
+
+    fn f32_binop(&mut self, op: FBinop, _: (), _: ()) {
+        let i1 = self.ic - 1; // rhs, popped first
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.f32_binop(FBinop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn f32_unop(&mut self, op: FUnop, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.f32_unop(FUnop::{op:?}, x_{i1});").unwrap();
+    }
+
+    fn f32_relop(&mut self, op: FRelop, _: (), _: ()) {
+        let i1 = self.ic - 1;
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.f32_relop(FRelop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn f64_binop(&mut self, op: FBinop, _: (), _: ()) {
+        let i1 = self.ic - 1; // rhs, popped first
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.f64_binop(FBinop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn f64_unop(&mut self, op: FUnop, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.f64_unop(FUnop::{op:?}, x_{i1});").unwrap();
+    }
+
+    fn f64_relop(&mut self, op: FRelop, _: (), _: ()) {
+        let i1 = self.ic - 1;
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.f64_relop(FRelop::{op:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn cvtop(&mut self, op: Cvtop, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.cvtop(Cvtop::{op:?}, x_{i1});").unwrap();
+    }
+
+    fn select(&mut self, _ty: Option<Type>, _: (), _: (), _: ()) {
+        let i1 = self.ic - 2; // the condition, popped first
+        let i2 = self.ic - 1;
+        let i3 = self.ic;
+        let i4 = self.fv();
+        writeln!(&mut self.gen, "let x_{i4} = if x_{i1} != 0 {{ x_{i3} }} else {{ x_{i2} }};").unwrap();
+    }
+
+    fn unreachable(&mut self) {
+        writeln!(&mut self.gen, "self.unreachable();").unwrap();
+    }
+
     fn branch(&mut self, _label_idx: usize) {
         writeln!(&mut self.gen,
         "
@@ -367,10 +1781,140 @@ impl CBD for TypedCompiler {
             // stupid casts
             self.codeptr.ip = ((self.codeptr.ip as isize) + ste.ip_delta) as usize;
             self.stp = ((self.stp as isize) + ste.stp_delta) as usize;
+            ste.unwind(&mut self.stack);
         }}
         ").unwrap();
     }
 
+    fn branch_table(&mut self, labels: &[usize], _: ()) {
+        let i = self.ic;
+        let n = labels.len();
+        writeln!(&mut self.gen, "self.stp += table_index(x_{i}, {n});").unwrap();
+        self.branch(0);
+    }
+
+    fn call(&mut self, func_idx: usize) {
+        writeln!(&mut self.gen, "self.call({func_idx});").unwrap();
+    }
+
+    fn i32_load(&mut self, op: I32LoadOp, memarg: MemArg, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.i32_load(I32LoadOp::{op:?}, {memarg:?}, x_{i1});").unwrap();
+    }
+
+    fn i32_store(&mut self, op: I32StoreOp, memarg: MemArg, _: (), _: ()) {
+        let i1 = self.ic - 1; // the value, popped first
+        let i2 = self.ic;
+        writeln!(&mut self.gen, "self.i32_store(I32StoreOp::{op:?}, {memarg:?}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn memory_size(&mut self) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.memory_size();").unwrap();
+    }
+
+    fn memory_grow(&mut self, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.memory_grow(x_{i1});").unwrap();
+    }
+
+    fn memory_init(&mut self, data_idx: usize, _: (), _: (), _: ()) {
+        let i1 = self.ic - 2; // the length, popped first
+        let i2 = self.ic - 1;
+        let i3 = self.ic;
+        writeln!(&mut self.gen, "self.memory_init({data_idx}, x_{i3}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn data_drop(&mut self, data_idx: usize) {
+        writeln!(&mut self.gen, "self.data_drop({data_idx});").unwrap();
+    }
+
+    fn memory_copy(&mut self, _: (), _: (), _: ()) {
+        let i1 = self.ic - 2; // the length, popped first
+        let i2 = self.ic - 1;
+        let i3 = self.ic;
+        writeln!(&mut self.gen, "self.memory_copy(x_{i3}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn memory_fill(&mut self, _: (), _: (), _: ()) {
+        let i1 = self.ic - 2; // the length, popped first
+        let i2 = self.ic - 1;
+        let i3 = self.ic;
+        writeln!(&mut self.gen, "self.memory_fill(x_{i3}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn call_indirect(&mut self, type_idx: usize, table_idx: usize, _: ()) {
+        let i = self.ic;
+        writeln!(&mut self.gen, "self.call_indirect({type_idx}, {table_idx}, x_{i});").unwrap();
+    }
+
+    fn ref_null(&mut self, t: Type) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.ref_null(Type::{t:?});").unwrap();
+    }
+
+    fn ref_is_null(&mut self, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.ref_is_null(x_{i1});").unwrap();
+    }
+
+    fn ref_func(&mut self, func_idx: usize) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.ref_func({func_idx});").unwrap();
+    }
+
+    fn table_get(&mut self, table_idx: usize, _: ()) {
+        let i1 = self.ic;
+        let i2 = self.fv();
+        writeln!(&mut self.gen, "let x_{i2} = self.table_get({table_idx}, x_{i1});").unwrap();
+    }
+
+    fn table_set(&mut self, table_idx: usize, _: (), _: ()) {
+        let i1 = self.ic - 1; // the value, popped first
+        let i2 = self.ic;
+        writeln!(&mut self.gen, "self.table_set({table_idx}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn table_size(&mut self, table_idx: usize) {
+        let i = self.fv();
+        writeln!(&mut self.gen, "let x_{i} = self.table_size({table_idx});").unwrap();
+    }
+
+    fn table_grow(&mut self, table_idx: usize, _: (), _: ()) {
+        let i1 = self.ic - 1; // the delta, popped first
+        let i2 = self.ic;
+        let i3 = self.fv();
+        writeln!(&mut self.gen, "let x_{i3} = self.table_grow({table_idx}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn table_fill(&mut self, table_idx: usize, _: (), _: (), _: ()) {
+        let i1 = self.ic - 2; // the length, popped first
+        let i2 = self.ic - 1;
+        let i3 = self.ic;
+        writeln!(&mut self.gen, "self.table_fill({table_idx}, x_{i3}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn table_copy(&mut self, dst_table: usize, src_table: usize, _: (), _: (), _: ()) {
+        let i1 = self.ic - 2; // the length, popped first
+        let i2 = self.ic - 1;
+        let i3 = self.ic;
+        writeln!(&mut self.gen, "self.table_copy({dst_table}, {src_table}, x_{i3}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn table_init(&mut self, table_idx: usize, elem_idx: usize, _: (), _: (), _: ()) {
+        let i1 = self.ic - 2; // the length, popped first
+        let i2 = self.ic - 1;
+        let i3 = self.ic;
+        writeln!(&mut self.gen, "self.table_init({table_idx}, {elem_idx}, x_{i3}, x_{i2}, x_{i1});").unwrap();
+    }
+
+    fn elem_drop(&mut self, elem_idx: usize) {
+        writeln!(&mut self.gen, "self.elem_drop({elem_idx});").unwrap();
+    }
+
     fn fallthru(&mut self) {
         writeln!(&mut self.gen, "self.stp += 1;").unwrap();
     }
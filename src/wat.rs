@@ -0,0 +1,1471 @@
+use crate::{BlockSig, CodeEntry, Opcode, Type};
+use crate::module::{ConstExpr, Data, DataMode, Elem, ElemMode, Export, ExportDesc, Func, FuncType, Global, GlobalType, Import, ImportDesc, Memory, Module, Table};
+use crate::mem::{access_width, MemArg, PAGE_SIZE};
+
+use std::collections::HashMap;
+
+// Text format front-end. Parses into s-expressions first, then lowers
+// module fields and (flat or folded) instructions into CodeEntry streams,
+// resolving $names for types, functions, tables, element and data segments,
+// globals, locals and labels along the way. Inline (export ...) and (import ...) abbreviations become
+// export and import entries.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatError {
+    pub line: usize,
+    pub col: usize,
+    pub msg: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+fn err<T>(pos: Pos, msg: impl Into<String>) -> Result<T, WatError> {
+    Err(WatError { line: pos.line, col: pos.col, msg: msg.into() })
+}
+
+#[derive(Debug, Clone)]
+pub enum Sexp {
+    Atom(String, Pos),
+    Str(Vec<u8>, Pos), // names must be UTF-8, data strings needn't be
+    List(Vec<Sexp>, Pos),
+}
+
+impl Sexp {
+    pub fn pos(&self) -> Pos {
+        match self {
+            Sexp::Atom(_, pos) | Sexp::Str(_, pos) | Sexp::List(_, pos) => *pos,
+        }
+    }
+
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(a, _) => Some(a),
+            _ => None,
+        }
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.atom().filter(|a| a.starts_with('$'))
+    }
+
+    // (keyword ...) => Some((keyword, rest))
+    fn form(&self) -> Option<(&str, &[Sexp])> {
+        match self {
+            Sexp::List(items, _) => Some((items.first()?.atom()?, &items[1..])),
+            _ => None,
+        }
+    }
+
+    fn is_form(&self, keyword: &str) -> bool {
+        self.form().is_some_and(|(k, _)| k == keyword)
+    }
+}
+
+struct Lexer<'a> {
+    src: &'a [u8],
+    i: usize,
+    line: usize,
+    col: usize,
+}
+
+impl Lexer<'_> {
+    fn pos(&self) -> Pos {
+        Pos { line: self.line, col: self.col }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.i).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.i += 1;
+        if c == b'\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_ws(&mut self) -> Result<(), WatError> {
+        loop {
+            match (self.peek(), self.src.get(self.i + 1)) {
+                (Some(c), _) if c.is_ascii_whitespace() => { self.bump(); }
+                (Some(b';'), Some(b';')) => {
+                    while self.peek().is_some_and(|c| c != b'\n') {
+                        self.bump();
+                    }
+                }
+                (Some(b'('), Some(b';')) => self.block_comment()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // (; ... ;), nestable
+    fn block_comment(&mut self) -> Result<(), WatError> {
+        let start = self.pos();
+        let mut depth = 0;
+        loop {
+            match (self.peek(), self.src.get(self.i + 1)) {
+                (Some(b'('), Some(b';')) => depth += 1,
+                (Some(b';'), Some(b')')) => depth -= 1,
+                (None, _) => return err(start, "unterminated block comment"),
+                _ => {
+                    self.bump();
+                    continue;
+                }
+            }
+            self.bump();
+            self.bump();
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn sexp(&mut self) -> Result<Sexp, WatError> {
+        self.skip_ws()?;
+        let pos = self.pos();
+        match self.peek() {
+            None => err(pos, "unexpected end of input"),
+            Some(b'(') => {
+                self.bump();
+                let mut items = vec![];
+                loop {
+                    self.skip_ws()?;
+                    match self.peek() {
+                        Some(b')') => {
+                            self.bump();
+                            return Ok(Sexp::List(items, pos));
+                        }
+                        None => return err(pos, "unclosed '('"),
+                        _ => items.push(self.sexp()?),
+                    }
+                }
+            }
+            Some(b')') => err(pos, "unexpected ')'"),
+            Some(b'"') => {
+                self.bump();
+                let mut s = vec![];
+                loop {
+                    match self.bump() {
+                        None => return err(pos, "unterminated string"),
+                        Some(b'"') => break,
+                        Some(b'\\') => s.push(self.escape(pos)?),
+                        Some(c) => s.push(c),
+                    }
+                }
+                Ok(Sexp::Str(s, pos))
+            }
+            Some(_) => {
+                let start = self.i;
+                while self.peek().is_some_and(|c| !c.is_ascii_whitespace() && !b"()\";".contains(&c)) {
+                    self.bump();
+                }
+                let atom = std::str::from_utf8(&self.src[start..self.i]).unwrap();
+                Ok(Sexp::Atom(atom.to_string(), pos))
+            }
+        }
+    }
+
+    fn escape(&mut self, pos: Pos) -> Result<u8, WatError> {
+        let hex = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+        match self.bump() {
+            Some(b'n') => Ok(b'\n'),
+            Some(b't') => Ok(b'\t'),
+            Some(b'r') => Ok(b'\r'),
+            Some(c @ (b'"' | b'\'' | b'\\')) => Ok(c),
+            Some(c) => match (hex(c), self.bump().and_then(hex)) {
+                (Some(hi), Some(lo)) => Ok(hi << 4 | lo),
+                _ => err(pos, "bad string escape"),
+            },
+            None => err(pos, "unterminated string"),
+        }
+    }
+}
+
+pub fn parse_sexps(src: &str) -> Result<Vec<Sexp>, WatError> {
+    let mut lexer = Lexer { src: src.as_bytes(), i: 0, line: 1, col: 1 };
+    let mut res = vec![];
+    loop {
+        lexer.skip_ws()?;
+        if lexer.peek().is_none() {
+            return Ok(res);
+        }
+        res.push(lexer.sexp()?);
+    }
+}
+
+// Accepts either `(module ...)` or a bare sequence of module fields, so that
+// a lone `(func ...)` is a valid fixture.
+pub fn parse_module(src: &str) -> Result<Module, WatError> {
+    let sexps = parse_sexps(src)?;
+    let fields = match sexps.as_slice() {
+        [m] if m.is_form("module") => {
+            let (_, rest) = m.form().unwrap();
+            match rest.first() {
+                Some(s) if s.id().is_some() => &rest[1..],
+                _ => rest,
+            }
+        }
+        _ => &sexps[..],
+    };
+    ModuleParser::default().module(fields)
+}
+
+// sign and magnitude of a decimal or hex integer literal
+fn int_literal(s: &str) -> Option<(bool, u64)> {
+    let (neg, digits) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let digits = digits.replace('_', "");
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse::<u64>(),
+    };
+    Some((neg, magnitude.ok()?))
+}
+
+pub fn parse_int(s: &str, pos: Pos) -> Result<i32, WatError> {
+    match int_literal(s) {
+        // signed or unsigned interpretation both allowed, like the reference parser
+        Some((true, m)) if m <= 1 << 31 => Ok((m as i64).wrapping_neg() as i32),
+        Some((false, m)) if m <= u32::MAX as u64 => Ok(m as u32 as i32),
+        _ => err(pos, format!("bad i32 literal {s}")),
+    }
+}
+
+pub fn parse_i64(s: &str, pos: Pos) -> Result<i64, WatError> {
+    match int_literal(s) {
+        Some((true, m)) if m <= 1 << 63 => Ok((m as i64).wrapping_neg()),
+        Some((false, m)) => Ok(m as i64),
+        _ => err(pos, format!("bad i64 literal {s}")),
+    }
+}
+
+enum FloatLit {
+    Inf,
+    Nan(Option<u64>), // payload, None for the canonical NaN
+    Decimal(String),
+    Hex(u64, i32), // mantissa and binary exponent
+}
+
+// sign and value of a float literal: decimal, hex (0x1.8p3), inf, nan or
+// nan:0x payload
+fn float_literal(s: &str) -> Option<(bool, FloatLit)> {
+    let (neg, body) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let body = body.replace('_', "");
+    let lit = match body.as_str() {
+        "inf" => FloatLit::Inf,
+        "nan" => FloatLit::Nan(None),
+        _ if body.starts_with("nan:0x") => FloatLit::Nan(Some(u64::from_str_radix(&body[6..], 16).ok()?)),
+        _ if body.starts_with("0x") => {
+            let (digits, exp) = match body[2..].split_once(['p', 'P']) {
+                Some((digits, exp)) => (digits, exp.parse::<i32>().ok()?),
+                None => (&body[2..], 0),
+            };
+            let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+            if int.is_empty() {
+                return None;
+            }
+            // digits that don't fit in the mantissa only scale it, and
+            // leave a sticky bit so it still rounds the right way
+            let (mut m, mut e) = (0u64, exp);
+            for (i, c) in int.chars().chain(frac.chars()).enumerate() {
+                let d = c.to_digit(16)? as u64;
+                if m >> 60 == 0 {
+                    m = m << 4 | d;
+                    e -= if i >= int.len() { 4 } else { 0 };
+                } else {
+                    m |= (d != 0) as u64;
+                    e += if i < int.len() { 4 } else { 0 };
+                }
+            }
+            FloatLit::Hex(m, e)
+        }
+        _ if body.starts_with(|c: char| c.is_ascii_digit()) => FloatLit::Decimal(body),
+        _ => return None,
+    };
+    Some((neg, lit))
+}
+
+// exact up to 53 significant bits, longer mantissas round twice
+fn hex_float(m: u64, mut e: i32) -> f64 {
+    let mut x = m as f64;
+    while e != 0 {
+        let k = e.clamp(-1000, 1000);
+        x *= 2f64.powi(k);
+        e -= k;
+    }
+    x
+}
+
+pub fn parse_f32(s: &str, pos: Pos) -> Result<u32, WatError> {
+    let bits = match float_literal(s) {
+        Some((neg, lit)) => match lit {
+            FloatLit::Inf => Some(0x7f80_0000),
+            FloatLit::Nan(None) => Some(0x7fc0_0000),
+            FloatLit::Nan(Some(p)) if p != 0 && p < 1 << 23 => Some(0x7f80_0000 | p as u32),
+            FloatLit::Decimal(d) => d.parse::<f32>().ok().filter(|x| x.is_finite()).map(f32::to_bits),
+            FloatLit::Hex(m, e) => Some(hex_float(m, e) as f32).filter(|x| x.is_finite()).map(f32::to_bits),
+            FloatLit::Nan(Some(_)) => None,
+        }.map(|bits| bits | (neg as u32) << 31),
+        None => None,
+    };
+    bits.map_or_else(|| err(pos, format!("bad f32 literal {s}")), Ok)
+}
+
+pub fn parse_f64(s: &str, pos: Pos) -> Result<u64, WatError> {
+    let bits = match float_literal(s) {
+        Some((neg, lit)) => match lit {
+            FloatLit::Inf => Some(0x7ff0_0000_0000_0000),
+            FloatLit::Nan(None) => Some(0x7ff8_0000_0000_0000),
+            FloatLit::Nan(Some(p)) if p != 0 && p < 1 << 52 => Some(0x7ff0_0000_0000_0000 | p),
+            FloatLit::Decimal(d) => d.parse::<f64>().ok().filter(|x| x.is_finite()).map(f64::to_bits),
+            FloatLit::Hex(m, e) => Some(hex_float(m, e)).filter(|x| x.is_finite()).map(f64::to_bits),
+            FloatLit::Nan(Some(_)) => None,
+        }.map(|bits| bits | (neg as u64) << 63),
+        None => None,
+    };
+    bits.map_or_else(|| err(pos, format!("bad f64 literal {s}")), Ok)
+}
+
+fn parse_ref_type(s: &Sexp) -> Result<Type, WatError> {
+    match s.atom() {
+        Some("funcref" | "anyfunc") => Ok(Type::FuncRef),
+        Some("externref") => Ok(Type::ExternRef),
+        _ => err(s.pos(), "expected funcref or externref"),
+    }
+}
+
+// ref.null's immediate
+fn parse_heap_type(s: &Sexp) -> Result<Type, WatError> {
+    match s.atom() {
+        Some("func") => Ok(Type::FuncRef),
+        Some("extern") => Ok(Type::ExternRef),
+        _ => err(s.pos(), "expected func or extern"),
+    }
+}
+
+fn parse_val_type(s: &Sexp) -> Result<Type, WatError> {
+    match s.atom() {
+        Some("i32") => Ok(Type::I32),
+        Some("i64") => Ok(Type::I64),
+        Some("f32") => Ok(Type::F32),
+        Some("f64") => Ok(Type::F64),
+        Some("funcref") => Ok(Type::FuncRef),
+        Some("externref") => Ok(Type::ExternRef),
+        _ => err(s.pos(), "expected a value type"),
+    }
+}
+
+fn is_index(s: &Sexp) -> bool {
+    s.id().is_some() || s.atom().is_some_and(|a| a.parse::<u32>().is_ok())
+}
+
+fn parse_name(s: &Sexp) -> Result<String, WatError> {
+    match s {
+        Sexp::Str(name, pos) => String::from_utf8(name.clone()).or_else(|_| err(*pos, "name is not utf-8")),
+        _ => err(s.pos(), "expected a name string"),
+    }
+}
+
+// the index space a func, table, memory or global field or import belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Space {
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+impl Space {
+    fn from_keyword(keyword: &str) -> Option<Space> {
+        match keyword {
+            "func" => Some(Space::Func),
+            "table" => Some(Space::Table),
+            "memory" => Some(Space::Memory),
+            "global" => Some(Space::Global),
+            _ => None,
+        }
+    }
+
+    fn of_import(desc: &ImportDesc) -> Space {
+        match desc {
+            ImportDesc::Func(_) => Space::Func,
+            ImportDesc::Table(_) => Space::Table,
+            ImportDesc::Memory(_) => Space::Memory,
+            ImportDesc::Global(_) => Space::Global,
+        }
+    }
+
+    fn export(self, idx: usize) -> ExportDesc {
+        match self {
+            Space::Func => ExportDesc::Func(idx),
+            Space::Table => ExportDesc::Table(idx),
+            Space::Memory => ExportDesc::Memory(idx),
+            Space::Global => ExportDesc::Global(idx),
+        }
+    }
+}
+
+// an import's module and name
+type ImportNames = (String, String);
+
+// the space and $id of a field that defines or imports something
+fn field_space(field: &Sexp) -> Option<(Space, Option<&str>, bool)> {
+    let (keyword, rest) = field.form()?;
+    if keyword == "import" {
+        let (keyword, rest) = rest.get(2)?.form()?;
+        return Some((Space::from_keyword(keyword)?, rest.first().and_then(Sexp::id), true));
+    }
+    let id = rest.first().and_then(Sexp::id);
+    let rest = if id.is_some() { &rest[1..] } else { rest };
+    let imported = rest.iter().find(|s| !s.is_form("export")).is_some_and(|s| s.is_form("import"));
+    Some((Space::from_keyword(keyword)?, id, imported))
+}
+
+#[derive(Default)]
+struct ModuleParser {
+    module: Module,
+    type_names: HashMap<String, usize>,
+    func_names: HashMap<String, usize>,
+    func_count: usize,
+    table_names: HashMap<String, usize>,
+    table_count: usize,
+    memory_count: usize,
+    import_spaces: Vec<Space>, // of every import, in order
+    elem_names: HashMap<String, usize>,
+    elem_count: usize,
+    data_names: HashMap<String, usize>,
+    data_count: usize,
+    global_names: HashMap<String, usize>,
+    global_count: usize,
+}
+
+impl ModuleParser {
+    fn module(mut self, fields: &[Sexp]) -> Result<Module, WatError> {
+        // types and func, table, elem, data and global names first, funcs may
+        // refer to ones defined after them. Imports come first in their
+        // index space.
+        for field in fields {
+            match field.form() {
+                Some(("type", _)) => self.type_def(field)?,
+                // an inline (elem ...) is a segment of its own
+                Some(("table", rest)) if rest.last().is_some_and(|s| s.is_form("elem")) => self.elem_count += 1,
+                Some(("elem", rest)) => {
+                    if let Some(id) = rest.first().and_then(Sexp::id) {
+                        self.elem_names.insert(id.to_string(), self.elem_count);
+                    }
+                    self.elem_count += 1;
+                }
+                Some(("memory", rest)) if rest.last().is_some_and(|s| s.is_form("data")) => self.data_count += 1,
+                Some(("data", rest)) => {
+                    if let Some(id) = rest.first().and_then(Sexp::id) {
+                        self.data_names.insert(id.to_string(), self.data_count);
+                    }
+                    self.data_count += 1;
+                }
+                _ => {}
+            }
+        }
+        let spaces: Vec<_> = fields.iter().filter_map(field_space).collect();
+        let imports = spaces.iter().filter(|(_, _, imported)| *imported);
+        self.import_spaces = imports.clone().map(|&(space, _, _)| space).collect();
+        for &(space, id, _) in imports.chain(spaces.iter().filter(|(_, _, imported)| !imported)) {
+            let (names, count) = match space {
+                Space::Func => (&mut self.func_names, &mut self.func_count),
+                Space::Table => (&mut self.table_names, &mut self.table_count),
+                Space::Memory => {
+                    self.memory_count += 1;
+                    continue;
+                }
+                Space::Global => (&mut self.global_names, &mut self.global_count),
+            };
+            if let Some(id) = id {
+                names.insert(id.to_string(), *count);
+            }
+            *count += 1;
+        }
+        for field in fields {
+            match field.form() {
+                Some(("type", _)) => {}
+                Some(("import", _)) => self.import(field)?,
+                Some(("export", _)) => self.export(field)?,
+                Some(("start", _)) => self.start(field)?,
+                Some(("func", rest)) => self.func(rest, field.pos())?,
+                Some(("table", _)) => self.table(field)?,
+                Some(("memory", _)) => self.memory(field)?,
+                Some(("global", _)) => self.global(field)?,
+                Some(("elem", _)) => self.elem(field)?,
+                Some(("data", _)) => self.data(field)?,
+                Some((other, _)) => return err(field.pos(), format!("unsupported module field {other}")),
+                None => return err(field.pos(), "expected a module field"),
+            }
+        }
+        Ok(self.module)
+    }
+
+    // imports and definitions so far
+    fn imported(&self, space: Space) -> usize {
+        self.module.imports.iter().filter(|i| Space::of_import(&i.desc) == space).count()
+    }
+
+    fn defined(&self, space: Space) -> usize {
+        match space {
+            Space::Func => self.module.funcs.len(),
+            Space::Table => self.module.tables.len(),
+            Space::Memory => self.module.memories.len(),
+            Space::Global => self.module.globals.len(),
+        }
+    }
+
+    // a field's (export "n")* (import "m" "n")? abbreviations, after its
+    // $id, returning the import's names and what follows. Exports name the
+    // field's index in `space`: its import's, or its definition's after
+    // every import.
+    fn inline_abbrevs<'a>(&mut self, space: Space, mut rest: &'a [Sexp]) -> Result<(Option<ImportNames>, &'a [Sexp]), WatError> {
+        let mut exports = vec![];
+        while let Some(("export", names)) = rest.first().and_then(Sexp::form) {
+            match names {
+                [name] => exports.push(parse_name(name)?),
+                _ => return err(rest[0].pos(), "expected one export name"),
+            }
+            rest = &rest[1..];
+        }
+        let mut import = None;
+        if let Some(("import", names)) = rest.first().and_then(Sexp::form) {
+            match names {
+                [module, name] => import = Some((parse_name(module)?, parse_name(name)?)),
+                _ => return err(rest[0].pos(), "expected a module and a name to import"),
+            }
+            rest = &rest[1..];
+        }
+        let idx = match import {
+            Some(_) => self.imported(space),
+            None => self.import_spaces.iter().filter(|&&s| s == space).count() + self.defined(space),
+        };
+        for name in exports {
+            self.module.exports.push(Export { name, desc: space.export(idx) });
+        }
+        Ok((import, rest))
+    }
+
+    // (import "m" "n" (func $f? typeuse)), and likewise for tables,
+    // memories and globals
+    fn import(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, rest) = field.form().unwrap();
+        let [module, name, desc] = rest else {
+            return err(field.pos(), "expected a module, a name and an import description");
+        };
+        let (module, name) = (parse_name(module)?, parse_name(name)?);
+        let Some((space, mut rest)) = desc.form().and_then(|(k, rest)| Some((Space::from_keyword(k)?, rest))) else {
+            return err(desc.pos(), "expected func, table, memory or global");
+        };
+        if rest.first().and_then(Sexp::id).is_some() {
+            rest = &rest[1..];
+        }
+        let desc = self.import_desc(space, rest, desc.pos())?;
+        self.module.imports.push(Import { module, name, desc });
+        Ok(())
+    }
+
+    fn import_desc(&mut self, space: Space, rest: &[Sexp], pos: Pos) -> Result<ImportDesc, WatError> {
+        Ok(match space {
+            Space::Func => {
+                let (ty, n) = self.type_use(rest)?;
+                if let Some(s) = rest.get(n) {
+                    return err(s.pos(), "unexpected item in imported function");
+                }
+                ImportDesc::Func(ty)
+            }
+            Space::Table => ImportDesc::Table(table_type(rest, pos)?),
+            Space::Memory => ImportDesc::Memory(memory_type(rest, pos)?),
+            Space::Global => match rest {
+                [ty] => ImportDesc::Global(global_type(ty)?),
+                _ => return err(pos, "expected a global type"),
+            },
+        })
+    }
+
+    // (export "n" (func x)), and likewise for tables, memories and globals
+    fn export(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, rest) = field.form().unwrap();
+        let [name, desc] = rest else {
+            return err(field.pos(), "expected a name and an export description");
+        };
+        let name = parse_name(name)?;
+        let desc = match desc.form() {
+            Some(("func", [idx])) => ExportDesc::Func(self.func_index(idx)? as usize),
+            Some(("table", [idx])) => ExportDesc::Table(self.table_index(idx)?),
+            Some(("memory", [idx])) => ExportDesc::Memory(self.memory_index(idx)?),
+            Some(("global", [idx])) => ExportDesc::Global(self.global_index(idx)? as usize),
+            _ => return err(desc.pos(), "expected (func x), (table x), (memory x) or (global x)"),
+        };
+        self.module.exports.push(Export { name, desc });
+        Ok(())
+    }
+
+    // (start x)
+    fn start(&mut self, field: &Sexp) -> Result<(), WatError> {
+        match field.form().unwrap().1 {
+            [idx] => self.module.start = Some(self.func_index(idx)? as usize),
+            _ => return err(field.pos(), "expected one function index"),
+        }
+        Ok(())
+    }
+
+    fn type_def(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, mut rest) = field.form().unwrap();
+        if let Some(id) = rest.first().and_then(Sexp::id) {
+            self.type_names.insert(id.to_string(), self.module.types.len());
+            rest = &rest[1..];
+        }
+        let func = match rest {
+            [f] if f.is_form("func") => f.form().unwrap().1,
+            _ => return err(field.pos(), "expected (func ...) in type definition"),
+        };
+        let mut ty = FuncType { params: vec![], results: vec![] };
+        let rest = params_results(func, &mut ty, &mut vec![])?;
+        if let Some(s) = rest.first() {
+            return err(s.pos(), "unexpected item in function type");
+        }
+        self.module.types.push(ty);
+        Ok(())
+    }
+
+    fn type_index(&self, s: &Sexp) -> Result<usize, WatError> {
+        let idx = match s.id() {
+            Some(id) => self.type_names.get(id).copied(),
+            None => s.atom().and_then(|a| a.parse().ok()),
+        };
+        match idx {
+            Some(idx) if idx < self.module.types.len() => Ok(idx),
+            _ => err(s.pos(), "unknown type"),
+        }
+    }
+
+    fn func_index(&self, s: &Sexp) -> Result<i32, WatError> {
+        let idx = match s.id() {
+            Some(id) => self.func_names.get(id).copied(),
+            None => s.atom().and_then(|a| a.parse().ok()),
+        };
+        match idx {
+            Some(idx) if idx < self.func_count => Ok(idx as i32),
+            _ => err(s.pos(), format!("unknown function {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    fn table_index(&self, s: &Sexp) -> Result<usize, WatError> {
+        let idx = match s.id() {
+            Some(id) => self.table_names.get(id).copied(),
+            None => s.atom().and_then(|a| a.parse().ok()),
+        };
+        match idx {
+            Some(idx) if idx < self.table_count => Ok(idx),
+            _ => err(s.pos(), format!("unknown table {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    fn elem_index(&self, s: &Sexp) -> Result<usize, WatError> {
+        let idx = match s.id() {
+            Some(id) => self.elem_names.get(id).copied(),
+            None => s.atom().and_then(|a| a.parse().ok()),
+        };
+        match idx {
+            Some(idx) if idx < self.elem_count => Ok(idx),
+            _ => err(s.pos(), format!("unknown element segment {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    fn data_index(&self, s: &Sexp) -> Result<usize, WatError> {
+        let idx = match s.id() {
+            Some(id) => self.data_names.get(id).copied(),
+            None => s.atom().and_then(|a| a.parse().ok()),
+        };
+        match idx {
+            Some(idx) if idx < self.data_count => Ok(idx),
+            _ => err(s.pos(), format!("unknown data segment {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    // (type idx)? (param t*)* (result t*)*, as a type index, and how many
+    // items it took
+    fn type_use(&mut self, items: &[Sexp]) -> Result<(usize, usize), WatError> {
+        let mut i = 0;
+        let mut type_use = None;
+        if let Some(t) = items.first().filter(|s| s.is_form("type")) {
+            match t.form().unwrap().1 {
+                [idx] => type_use = Some(self.type_index(idx)?),
+                _ => return err(t.pos(), "expected one type index"),
+            }
+            i += 1;
+        }
+
+        let mut ty = FuncType { params: vec![], results: vec![] };
+        let rest = params_results(&items[i..], &mut ty, &mut vec![])?;
+        let inline_pos = items.get(i).map(Sexp::pos);
+        let n = items.len() - rest.len();
+
+        let idx = match type_use {
+            Some(idx) if ty.params.is_empty() && ty.results.is_empty() => idx,
+            Some(idx) if self.module.types[idx] == ty => idx,
+            Some(_) => return err(inline_pos.unwrap(), "inline type doesn't match type use"),
+            None => self.intern_type(ty),
+        };
+        Ok((idx, n))
+    }
+
+    // (table $t? min max? reftype) or (table $t? reftype (elem elemlist)),
+    // where the elem list sizes the table
+    fn table(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, mut rest) = field.form().unwrap();
+        if rest.first().and_then(Sexp::id).is_some() {
+            rest = &rest[1..];
+        }
+        let table = self.import_spaces.iter().filter(|&&s| s == Space::Table).count() + self.module.tables.len();
+        let (import, rest) = self.inline_abbrevs(Space::Table, rest)?;
+        if let Some((module, name)) = import {
+            let desc = self.import_desc(Space::Table, rest, field.pos())?;
+            self.module.imports.push(Import { module, name, desc });
+            return Ok(());
+        }
+        match rest {
+            [reftype, elem] if elem.is_form("elem") => {
+                let elem_type = parse_ref_type(reftype)?;
+                let init = self.elem_list(elem.form().unwrap().1)?;
+                let n = init.len() as u32;
+                self.module.tables.push(Table { elem_type, min: n, max: Some(n) });
+                let mode = ElemMode::Active { table, offset: ConstExpr::I32Const(0) };
+                self.module.elems.push(Elem { ty: elem_type, mode, init });
+            }
+            _ => self.module.tables.push(table_type(rest, field.pos())?),
+        }
+        Ok(())
+    }
+
+    // (memory $m? min max?) or (memory $m? (data string*)), where the data
+    // sizes the memory
+    fn memory(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, mut rest) = field.form().unwrap();
+        if rest.first().and_then(Sexp::id).is_some() {
+            rest = &rest[1..];
+        }
+        let memory = self.import_spaces.iter().filter(|&&s| s == Space::Memory).count() + self.module.memories.len();
+        let (import, rest) = self.inline_abbrevs(Space::Memory, rest)?;
+        match (import, rest) {
+            (Some((module, name)), _) => {
+                let desc = ImportDesc::Memory(memory_type(rest, field.pos())?);
+                self.module.imports.push(Import { module, name, desc });
+            }
+            (None, [data]) if data.is_form("data") => {
+                let init = data_string(data.form().unwrap().1)?;
+                let pages = init.len().div_ceil(PAGE_SIZE) as u32;
+                self.module.memories.push(Memory { min: pages, max: Some(pages) });
+                let mode = DataMode::Active { memory, offset: ConstExpr::I32Const(0) };
+                self.module.datas.push(Data { mode, init });
+            }
+            (None, _) => self.module.memories.push(memory_type(rest, field.pos())?),
+        }
+        Ok(())
+    }
+
+    // active (data $d? (memory x)? (offset expr) string*), where the offset
+    // may also be given as a bare (i32.const n) or (global.get x), or
+    // passive (data $d? string*)
+    fn data(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, mut rest) = field.form().unwrap();
+        if rest.first().and_then(Sexp::id).is_some() {
+            rest = &rest[1..];
+        }
+        let mode = match rest.first() {
+            Some(Sexp::List(..)) => {
+                let mut memory = 0;
+                if let Some(m) = rest.first().filter(|s| s.is_form("memory")) {
+                    match m.form().unwrap().1 {
+                        [idx] => memory = self.memory_index(idx)?,
+                        _ => return err(m.pos(), "expected one memory index"),
+                    }
+                    rest = &rest[1..];
+                }
+                let offset_expr = match rest.first() {
+                    Some(s) if s.is_form("offset") => match s.form().unwrap().1 {
+                        [expr] => expr,
+                        _ => return err(s.pos(), "expected one offset expression"),
+                    },
+                    Some(s) if s.is_form("i32.const") || s.is_form("global.get") => s,
+                    _ => return err(field.pos(), "expected an offset expression"),
+                };
+                let offset = self.const_expr(offset_expr)?;
+                rest = &rest[1..];
+                DataMode::Active { memory, offset }
+            }
+            _ => DataMode::Passive,
+        };
+        let init = data_string(rest)?;
+        self.module.datas.push(Data { mode, init });
+        Ok(())
+    }
+
+    // memories have no names, only indices
+    fn memory_index(&self, s: &Sexp) -> Result<usize, WatError> {
+        match s.atom().and_then(|a| a.parse().ok()) {
+            Some(idx) if idx < self.memory_count => Ok(idx),
+            _ => err(s.pos(), format!("unknown memory {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    // active (elem $e? (table x)? (offset expr) elemlist), where the offset
+    // may also be given as a bare (i32.const n) or (global.get x),
+    // declarative (elem $e? declare elemlist) or passive (elem $e? elemlist).
+    // The elem list is `func? idx*` or a reference type and expressions.
+    fn elem(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, mut rest) = field.form().unwrap();
+        if rest.first().and_then(Sexp::id).is_some() {
+            rest = &rest[1..];
+        }
+        let active = |s: &Sexp| ["table", "offset", "i32.const", "global.get"].iter().any(|k| s.is_form(k));
+        let mode = match rest.first() {
+            Some(s) if s.atom() == Some("declare") => {
+                rest = &rest[1..];
+                ElemMode::Declarative
+            }
+            Some(s) if active(s) => {
+                let mut table = 0;
+                if let Some(t) = rest.first().filter(|s| s.is_form("table")) {
+                    match t.form().unwrap().1 {
+                        [idx] => table = self.table_index(idx)?,
+                        _ => return err(t.pos(), "expected one table index"),
+                    }
+                    rest = &rest[1..];
+                }
+                let offset_expr = match rest.first() {
+                    Some(s) if s.is_form("offset") => match s.form().unwrap().1 {
+                        [expr] => expr,
+                        _ => return err(s.pos(), "expected one offset expression"),
+                    },
+                    Some(s) if s.is_form("i32.const") || s.is_form("global.get") => s,
+                    _ => return err(field.pos(), "expected an offset expression"),
+                };
+                let offset = self.const_expr(offset_expr)?;
+                rest = &rest[1..];
+                ElemMode::Active { table, offset }
+            }
+            _ => ElemMode::Passive,
+        };
+        let ty = match rest.first().and_then(Sexp::atom) {
+            Some("func") => {
+                rest = &rest[1..];
+                Type::FuncRef
+            }
+            Some("funcref" | "externref") => {
+                let ty = parse_ref_type(&rest[0])?;
+                rest = &rest[1..];
+                ty
+            }
+            _ => Type::FuncRef,
+        };
+        let init = self.elem_list(rest)?;
+        self.module.elems.push(Elem { ty, mode, init });
+        Ok(())
+    }
+
+    // function indices, or (item expr) and bare expressions. An item's
+    // expression may also be flat, (item ref.func $f).
+    fn elem_list(&self, items: &[Sexp]) -> Result<Vec<ConstExpr>, WatError> {
+        items.iter().map(|s| match s.form() {
+            Some(("item", [expr])) => self.const_expr(expr),
+            Some(("item", flat)) => self.const_expr(&Sexp::List(flat.to_vec(), s.pos())),
+            Some(_) => self.const_expr(s),
+            None => Ok(ConstExpr::RefFunc(self.func_index(s)? as usize)),
+        }).collect()
+    }
+
+    // (global $g? t expr) or (global $g? (mut t) expr)
+    fn global(&mut self, field: &Sexp) -> Result<(), WatError> {
+        let (_, mut rest) = field.form().unwrap();
+        if rest.first().and_then(Sexp::id).is_some() {
+            rest = &rest[1..];
+        }
+        let (import, rest) = self.inline_abbrevs(Space::Global, rest)?;
+        if let Some((module, name)) = import {
+            let desc = self.import_desc(Space::Global, rest, field.pos())?;
+            self.module.imports.push(Import { module, name, desc });
+            return Ok(());
+        }
+        let [ty, init] = rest else {
+            return err(field.pos(), "expected a global type and initializer");
+        };
+        let ty = global_type(ty)?;
+        let init = self.const_expr(init)?;
+        self.module.globals.push(Global { ty, init });
+        Ok(())
+    }
+
+    // a (t.const n), (ref.null t), (ref.func x) or (global.get x)
+    fn const_expr(&self, s: &Sexp) -> Result<ConstExpr, WatError> {
+        match s.form() {
+            Some(("i32.const", [n])) if n.atom().is_some() => Ok(ConstExpr::I32Const(parse_int(n.atom().unwrap(), n.pos())?)),
+            Some(("i64.const", [n])) if n.atom().is_some() => Ok(ConstExpr::I64Const(parse_i64(n.atom().unwrap(), n.pos())?)),
+            Some(("f32.const", [n])) if n.atom().is_some() => Ok(ConstExpr::F32Const(parse_f32(n.atom().unwrap(), n.pos())?)),
+            Some(("f64.const", [n])) if n.atom().is_some() => Ok(ConstExpr::F64Const(parse_f64(n.atom().unwrap(), n.pos())?)),
+            Some(("global.get", [idx])) => Ok(ConstExpr::GlobalGet(self.global_index(idx)? as usize)),
+            Some(("ref.null", [t])) => Ok(ConstExpr::RefNull(parse_heap_type(t)?)),
+            Some(("ref.func", [idx])) => Ok(ConstExpr::RefFunc(self.func_index(idx)? as usize)),
+            _ => err(s.pos(), "expected a constant expression"),
+        }
+    }
+
+    fn global_index(&self, s: &Sexp) -> Result<i32, WatError> {
+        let idx = match s.id() {
+            Some(id) => self.global_names.get(id).copied(),
+            None => s.atom().and_then(|a| a.parse().ok()),
+        };
+        match idx {
+            Some(idx) if idx < self.global_count => Ok(idx as i32),
+            _ => err(s.pos(), format!("unknown global {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    fn func(&mut self, mut rest: &[Sexp], pos: Pos) -> Result<(), WatError> {
+        if rest.first().and_then(Sexp::id).is_some() {
+            rest = &rest[1..];
+        }
+        let (import, mut rest) = self.inline_abbrevs(Space::Func, rest)?;
+        if let Some((module, name)) = import {
+            let desc = self.import_desc(Space::Func, rest, pos)?;
+            self.module.imports.push(Import { module, name, desc });
+            return Ok(());
+        }
+
+        let mut type_use = None;
+        if let Some(t) = rest.first().filter(|s| s.is_form("type")) {
+            match t.form().unwrap().1 {
+                [idx] => type_use = Some(self.type_index(idx)?),
+                _ => return err(t.pos(), "expected one type index"),
+            }
+            rest = &rest[1..];
+        }
+
+        let mut ty = FuncType { params: vec![], results: vec![] };
+        let mut local_names = vec![];
+        rest = params_results(rest, &mut ty, &mut local_names)?;
+
+        let ty = match type_use {
+            // inline params/results must agree with the referenced type if present
+            Some(idx) if ty.params.is_empty() && ty.results.is_empty() => {
+                local_names = vec![None; self.module.types[idx].params.len()];
+                idx
+            }
+            Some(idx) if self.module.types[idx] == ty => idx,
+            Some(_) => return err(rest.first().map_or(Pos { line: 0, col: 0 }, Sexp::pos), "inline type doesn't match type use"),
+            None => self.intern_type(ty),
+        };
+
+        let mut locals = vec![];
+        while let Some(("local", decl)) = rest.first().and_then(Sexp::form) {
+            match decl {
+                [id, t] if id.id().is_some() => {
+                    local_names.push(id.id().map(String::from));
+                    locals.push(parse_val_type(t)?);
+                }
+                _ => for t in decl {
+                    local_names.push(None);
+                    locals.push(parse_val_type(t)?);
+                }
+            }
+            rest = &rest[1..];
+        }
+
+        let mut lower = FuncLowering {
+            parser: self,
+            local_names,
+            labels: vec![],
+            code: vec![],
+        };
+        lower.instrs(rest)?;
+        if !lower.labels.is_empty() {
+            return err(rest.last().map_or(Pos { line: 0, col: 0 }, Sexp::pos), "unclosed block");
+        }
+        let mut code = lower.code;
+        code.push(CodeEntry::Op(Opcode::End));
+
+        self.module.funcs.push(Func { ty, locals, code });
+        Ok(())
+    }
+
+    fn intern_type(&mut self, ty: FuncType) -> usize {
+        match self.module.types.iter().position(|t| *t == ty) {
+            Some(idx) => idx,
+            None => {
+                self.module.types.push(ty);
+                self.module.types.len() - 1
+            }
+        }
+    }
+}
+
+// min max? reftype
+fn table_type(rest: &[Sexp], pos: Pos) -> Result<Table, WatError> {
+    let [limits @ .., reftype] = rest else {
+        return err(pos, "expected table limits and a reference type");
+    };
+    if limits.is_empty() || limits.len() > 2 {
+        return err(pos, "expected table limits and a reference type");
+    }
+    let elem_type = parse_ref_type(reftype)?;
+    let limit = |s: &Sexp| match s.atom().and_then(|a| a.parse::<u32>().ok()) {
+        Some(n) => Ok(n),
+        None => err(s.pos(), "expected a table limit"),
+    };
+    let min = limit(&limits[0])?;
+    let max = limits.get(1).map(limit).transpose()?;
+    Ok(Table { elem_type, min, max })
+}
+
+// min max?
+fn memory_type(rest: &[Sexp], pos: Pos) -> Result<Memory, WatError> {
+    let limit = |s: &Sexp| match s.atom().and_then(|a| a.parse::<u32>().ok()) {
+        Some(n) => Ok(n),
+        None => err(s.pos(), "expected a memory limit"),
+    };
+    match rest {
+        [min] => Ok(Memory { min: limit(min)?, max: None }),
+        [min, max] => Ok(Memory { min: limit(min)?, max: Some(limit(max)?) }),
+        _ => err(pos, "expected memory limits"),
+    }
+}
+
+// the strings' bytes, concatenated
+fn data_string(items: &[Sexp]) -> Result<Vec<u8>, WatError> {
+    let mut bytes = vec![];
+    for s in items {
+        match s {
+            Sexp::Str(b, _) => bytes.extend_from_slice(b),
+            _ => return err(s.pos(), "expected a data string"),
+        }
+    }
+    Ok(bytes)
+}
+
+// t or (mut t)
+fn global_type(s: &Sexp) -> Result<GlobalType, WatError> {
+    match s.form() {
+        Some(("mut", [t])) => Ok(GlobalType { ty: parse_val_type(t)?, mutable: true }),
+        _ => Ok(GlobalType { ty: parse_val_type(s)?, mutable: false }),
+    }
+}
+
+// parses leading (param ...) and (result ...) forms, returning what's left
+fn params_results<'a>(mut rest: &'a [Sexp], ty: &mut FuncType, names: &mut Vec<Option<String>>) -> Result<&'a [Sexp], WatError> {
+    while let Some(("param", decl)) = rest.first().and_then(Sexp::form) {
+        match decl {
+            [id, t] if id.id().is_some() => {
+                names.push(id.id().map(String::from));
+                ty.params.push(parse_val_type(t)?);
+            }
+            _ => for t in decl {
+                names.push(None);
+                ty.params.push(parse_val_type(t)?);
+            }
+        }
+        rest = &rest[1..];
+    }
+    while let Some(("result", decl)) = rest.first().and_then(Sexp::form) {
+        for t in decl {
+            ty.results.push(parse_val_type(t)?);
+        }
+        rest = &rest[1..];
+    }
+    Ok(rest)
+}
+
+// both selects share a mnemonic, a leading (result t) makes it the typed one
+fn select_type(items: &[Sexp]) -> Result<(Opcode, Option<CodeEntry>), WatError> {
+    match items.first().and_then(Sexp::form) {
+        Some(("result", [t])) => Ok((Opcode::SelectTyped, Some(CodeEntry::ValType(parse_val_type(t)?)))),
+        Some(("result", _)) => err(items[0].pos(), "select expects one result type"),
+        _ => Ok((Opcode::Select, None)),
+    }
+}
+
+struct FuncLowering<'a> {
+    parser: &'a mut ModuleParser, // block types may add to the module's types
+    local_names: Vec<Option<String>>,
+    labels: Vec<Option<String>>, // innermost last
+    code: Vec<CodeEntry>,
+}
+
+impl FuncLowering<'_> {
+    fn instrs(&mut self, items: &[Sexp]) -> Result<(), WatError> {
+        let mut i = 0;
+        while i < items.len() {
+            match &items[i] {
+                Sexp::List(..) => {
+                    self.folded(&items[i])?;
+                    i += 1;
+                }
+                Sexp::Atom(..) => i = self.plain(items, i)?,
+                Sexp::Str(_, pos) => return err(*pos, "unexpected string"),
+            }
+        }
+        Ok(())
+    }
+
+    // flat instruction at items[i], returns the index after its immediates
+    fn plain(&mut self, items: &[Sexp], mut i: usize) -> Result<usize, WatError> {
+        use Opcode::*;
+
+        let s = &items[i];
+        let name = s.atom().unwrap();
+        let op = Opcode::from_name(name).ok_or_else(|| self.unknown(s, name))?;
+        i += 1;
+        match op {
+            Block | Loop | If => {
+                self.code.push(CodeEntry::Op(op));
+                let label = items.get(i).and_then(Sexp::id).map(String::from);
+                if label.is_some() {
+                    i += 1;
+                }
+                i = self.block_type(items, i)?;
+                self.labels.push(label);
+            }
+            Else => {
+                if self.labels.is_empty() {
+                    return err(s.pos(), "else without matching if");
+                }
+                // optional repeated label
+                if items.get(i).and_then(Sexp::id).is_some() {
+                    i += 1;
+                }
+                self.code.push(CodeEntry::Op(Else));
+            }
+            End => {
+                if self.labels.pop().is_none() {
+                    return err(s.pos(), "end without matching block");
+                }
+                // optional repeated label
+                if items.get(i).and_then(Sexp::id).is_some() {
+                    i += 1;
+                }
+                self.code.push(CodeEntry::Op(End));
+            }
+            BrTable => {
+                self.code.push(CodeEntry::Op(op));
+                let (labels, n) = self.labels(&items[i..], s.pos())?;
+                self.code.push(labels);
+                i += n;
+            }
+            CallIndirect => {
+                let (imms, n) = self.call_indirect(&items[i..])?;
+                self.code.push(CodeEntry::Op(op));
+                self.code.extend(imms);
+                i += n;
+            }
+            _ if access_width(op).is_some() => {
+                let (memarg, n) = self.memarg(op, &items[i..])?;
+                self.code.push(CodeEntry::Op(op));
+                self.code.push(memarg);
+                i += n;
+            }
+            Select | SelectTyped => {
+                let (op, ty) = select_type(&items[i..])?;
+                self.code.push(CodeEntry::Op(op));
+                if let Some(ty) = ty {
+                    self.code.push(ty);
+                    i += 1;
+                }
+            }
+            TableGet | TableSet | TableSize | TableGrow | TableFill | TableCopy | TableInit => {
+                let (imms, n) = self.table_imms(op, &items[i..], s.pos())?;
+                self.code.push(CodeEntry::Op(op));
+                self.code.extend(imms);
+                i += n;
+            }
+            _ => {
+                self.code.push(CodeEntry::Op(op));
+                if let Some(imm) = self.imm(op, &items[i..], s.pos())? {
+                    self.code.push(imm);
+                    i += 1;
+                }
+            }
+        }
+        Ok(i)
+    }
+
+    // (op imm* folded*) or (block $l? bt instr*) / (loop ...) or
+    // (if $l? bt folded* (then instr*) (else instr*)?)
+    fn folded(&mut self, s: &Sexp) -> Result<(), WatError> {
+        use Opcode::*;
+
+        let Some((name, rest)) = s.form() else {
+            return err(s.pos(), "expected an instruction");
+        };
+        let op = Opcode::from_name(name).ok_or_else(|| self.unknown(s, name))?;
+        match op {
+            Block | Loop => {
+                let mut i = 0;
+                let label = rest.first().and_then(Sexp::id).map(String::from);
+                if label.is_some() {
+                    i += 1;
+                }
+                self.code.push(CodeEntry::Op(op));
+                let i = self.block_type(rest, i)?;
+                self.labels.push(label);
+                self.instrs(&rest[i..])?;
+                self.labels.pop();
+                self.code.push(CodeEntry::Op(End));
+            }
+            If => {
+                let mut i = 0;
+                let label = rest.first().and_then(Sexp::id).map(String::from);
+                if label.is_some() {
+                    i += 1;
+                }
+                // the condition is evaluated before the if
+                let i = self.block_type(rest, i)?;
+                let bt = self.code.pop().unwrap();
+                let Some(then) = rest[i..].iter().position(|s| s.is_form("then")).map(|p| i + p) else {
+                    return err(s.pos(), "if expects (then ...)");
+                };
+                for operand in &rest[i..then] {
+                    self.folded(operand)?;
+                }
+                self.code.push(CodeEntry::Op(If));
+                self.code.push(bt);
+
+                self.labels.push(label);
+                self.instrs(rest[then].form().unwrap().1)?;
+                match &rest[then + 1..] {
+                    [] => {}
+                    [els] if els.is_form("else") => {
+                        self.code.push(CodeEntry::Op(Else));
+                        self.instrs(els.form().unwrap().1)?;
+                    }
+                    [extra, ..] => return err(extra.pos(), "expected (else ...) or the end of the if"),
+                }
+                self.labels.pop();
+                self.code.push(CodeEntry::Op(End));
+            }
+            Else => return err(s.pos(), "else can't be folded"),
+            End => return err(s.pos(), "end can't be folded"),
+            BrTable => {
+                let (labels, n) = self.labels(rest, s.pos())?;
+                for operand in &rest[n..] {
+                    self.folded(operand)?;
+                }
+                self.code.push(CodeEntry::Op(op));
+                self.code.push(labels);
+            }
+            CallIndirect => {
+                let (imms, n) = self.call_indirect(rest)?;
+                for operand in &rest[n..] {
+                    self.folded(operand)?;
+                }
+                self.code.push(CodeEntry::Op(op));
+                self.code.extend(imms);
+            }
+            _ if access_width(op).is_some() => {
+                let (memarg, n) = self.memarg(op, rest)?;
+                for operand in &rest[n..] {
+                    self.folded(operand)?;
+                }
+                self.code.push(CodeEntry::Op(op));
+                self.code.push(memarg);
+            }
+            Select | SelectTyped => {
+                let (op, ty) = select_type(rest)?;
+                for operand in &rest[ty.is_some() as usize..] {
+                    self.folded(operand)?;
+                }
+                self.code.push(CodeEntry::Op(op));
+                self.code.extend(ty);
+            }
+            TableGet | TableSet | TableSize | TableGrow | TableFill | TableCopy | TableInit => {
+                let (imms, n) = self.table_imms(op, rest, s.pos())?;
+                for operand in &rest[n..] {
+                    self.folded(operand)?;
+                }
+                self.code.push(CodeEntry::Op(op));
+                self.code.extend(imms);
+            }
+            _ => {
+                let imm = self.imm(op, rest, s.pos())?;
+                for operand in &rest[imm.is_some() as usize..] {
+                    self.folded(operand)?;
+                }
+                self.code.push(CodeEntry::Op(op));
+                self.code.extend(imm);
+            }
+        }
+        Ok(())
+    }
+
+    // (type idx)? (param t*)* (result t*)*, returns the index after them
+    fn block_type(&mut self, items: &[Sexp], mut i: usize) -> Result<usize, WatError> {
+        let mut type_use = None;
+        if let Some(t) = items.get(i).filter(|s| s.is_form("type")) {
+            match t.form().unwrap().1 {
+                [idx] => type_use = Some(self.parser.type_index(idx)?),
+                _ => return err(t.pos(), "expected one type index"),
+            }
+            i += 1;
+        }
+
+        let mut ty = FuncType { params: vec![], results: vec![] };
+        let rest = params_results(&items[i..], &mut ty, &mut vec![])?;
+        let inline_pos = items.get(i).map(Sexp::pos);
+        i = items.len() - rest.len();
+
+        let sig = match (type_use, &ty.params[..], &ty.results[..]) {
+            (Some(idx), [], []) => BlockSig::Index(idx),
+            (Some(idx), _, _) if self.parser.module.types[idx] == ty => BlockSig::Index(idx),
+            (Some(_), _, _) => return err(inline_pos.unwrap(), "inline type doesn't match type use"),
+            (None, [], []) => BlockSig::Empty,
+            (None, [], &[t]) => BlockSig::Value(t),
+            (None, _, _) => BlockSig::Index(self.parser.intern_type(ty)),
+        };
+        self.code.push(CodeEntry::BlockType(sig));
+        Ok(i)
+    }
+
+    // op's immediate, taken from the front of items
+    fn imm(&self, op: Opcode, items: &[Sexp], op_pos: Pos) -> Result<Option<CodeEntry>, WatError> {
+        use Opcode::*;
+
+        let imm = match op {
+            I32Const | I64Const | F32Const | F64Const | LocalGet | LocalSet | LocalTee | GlobalGet | GlobalSet | Br | BrIf | Call | RefNull | RefFunc
+            | MemoryInit | DataDrop | ElemDrop => match items.first() {
+                Some(s) if s.atom().is_some() => s,
+                _ => return err(op_pos, format!("{} expects an immediate", op.name())),
+            },
+            _ => return Ok(None),
+        };
+        let val = match op {
+            I64Const => return Ok(Some(CodeEntry::I64Imm(parse_i64(imm.atom().unwrap(), imm.pos())?))),
+            F32Const => return Ok(Some(CodeEntry::F32Imm(parse_f32(imm.atom().unwrap(), imm.pos())?))),
+            F64Const => return Ok(Some(CodeEntry::F64Imm(parse_f64(imm.atom().unwrap(), imm.pos())?))),
+            RefNull => return Ok(Some(CodeEntry::ValType(parse_heap_type(imm)?))),
+            I32Const => parse_int(imm.atom().unwrap(), imm.pos())?,
+            LocalGet | LocalSet | LocalTee => self.local(imm)?,
+            GlobalGet | GlobalSet => self.parser.global_index(imm)?,
+            Br | BrIf => self.label(imm)?,
+            Call | RefFunc => self.parser.func_index(imm)?,
+            MemoryInit | DataDrop => self.parser.data_index(imm)? as i32,
+            ElemDrop => self.parser.elem_index(imm)? as i32,
+            _ => unreachable!(),
+        };
+        Ok(Some(CodeEntry::I32Imm(val)))
+    }
+
+    // call_indirect's type and table indices, from `$t? typeuse`, and how
+    // many items they took
+    fn call_indirect(&mut self, items: &[Sexp]) -> Result<([CodeEntry; 2], usize), WatError> {
+        let mut i = 0;
+        let mut table = 0;
+        if let Some(t) = items.first().filter(|s| s.id().is_some() || s.atom().is_some_and(|a| a.parse::<u32>().is_ok())) {
+            table = self.parser.table_index(t)?;
+            i += 1;
+        }
+        let (ty, n) = self.parser.type_use(&items[i..])?;
+        Ok(([CodeEntry::I32Imm(ty as i32), CodeEntry::I32Imm(table as i32)], i + n))
+    }
+
+    // a table op's indices, and how many items they took: `table.init
+    // table? elem`, `table.copy (dst src)?`, or an optional table for the
+    // rest, tables defaulting to 0. The binary puts init's elem index first.
+    fn table_imms(&self, op: Opcode, items: &[Sexp], op_pos: Pos) -> Result<(Vec<CodeEntry>, usize), WatError> {
+        let imm = |idx: usize| CodeEntry::I32Imm(idx as i32);
+        let n = items.iter().take_while(|s| is_index(s)).count();
+        match (op, n) {
+            (Opcode::TableInit, 0) => err(op_pos, "table.init expects an element segment"),
+            (Opcode::TableInit, 1) => Ok((vec![imm(self.parser.elem_index(&items[0])?), imm(0)], 1)),
+            (Opcode::TableInit, _) => {
+                let table = self.parser.table_index(&items[0])?;
+                Ok((vec![imm(self.parser.elem_index(&items[1])?), imm(table)], 2))
+            }
+            (Opcode::TableCopy, 0) => Ok((vec![imm(0), imm(0)], 0)),
+            (Opcode::TableCopy, 1) => err(op_pos, "table.copy expects both table indices or neither"),
+            (Opcode::TableCopy, _) => {
+                let dst = self.parser.table_index(&items[0])?;
+                Ok((vec![imm(dst), imm(self.parser.table_index(&items[1])?)], 2))
+            }
+            (_, 0) => Ok((vec![imm(0)], 0)),
+            (_, _) => Ok((vec![imm(self.parser.table_index(&items[0])?)], 1)),
+        }
+    }
+
+    // `offset=n? align=n?` for a load or store, and how many items it took.
+    // The alignment defaults to the access width.
+    fn memarg(&self, op: Opcode, items: &[Sexp]) -> Result<(CodeEntry, usize), WatError> {
+        let mut i = 0;
+        let field = |i: usize, key: &str| items.get(i).and_then(Sexp::atom).and_then(|a| a.strip_prefix(key));
+        let mut offset = 0;
+        if let Some(n) = field(i, "offset=") {
+            offset = parse_int(n, items[i].pos())? as u32;
+            i += 1;
+        }
+        let mut align = access_width(op).unwrap().trailing_zeros();
+        if let Some(n) = field(i, "align=") {
+            match parse_int(n, items[i].pos())? as u32 {
+                a if a.is_power_of_two() => align = a.trailing_zeros(),
+                _ => return err(items[i].pos(), "alignment must be a power of two"),
+            }
+            i += 1;
+        }
+        Ok((CodeEntry::MemArg(MemArg { align, offset }), i))
+    }
+
+    // br_table's labels, the last being the default, and how many items they took
+    fn labels(&self, items: &[Sexp], op_pos: Pos) -> Result<(CodeEntry, usize), WatError> {
+        // labels run until the next instruction
+        let is_label = |s: &Sexp| s.id().is_some() || s.atom().is_some_and(|a| a.parse::<u32>().is_ok());
+        let n = items.iter().take_while(|s| is_label(s)).count();
+        if n == 0 {
+            return err(op_pos, "br_table expects at least a default label");
+        }
+        let labels = items[..n].iter().map(|s| Ok(self.label(s)? as usize)).collect::<Result<_, _>>()?;
+        Ok((CodeEntry::Labels(labels), n))
+    }
+
+    fn local(&self, s: &Sexp) -> Result<i32, WatError> {
+        let idx = match s.id() {
+            Some(id) => self.local_names.iter().position(|n| n.as_deref() == Some(id)),
+            None => s.atom().and_then(|a| a.parse().ok()),
+        };
+        match idx {
+            Some(idx) if idx < self.local_names.len() => Ok(idx as i32),
+            _ => err(s.pos(), format!("unknown local {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    // label ids resolve to the innermost enclosing block with that name
+    fn label(&self, s: &Sexp) -> Result<i32, WatError> {
+        let depth = match s.id() {
+            Some(id) => self.labels.iter().rev().position(|l| l.as_deref() == Some(id)),
+            // the function body itself is the outermost label
+            None => s.atom().and_then(|a| a.parse().ok()).filter(|&d| d <= self.labels.len()),
+        };
+        match depth {
+            Some(depth) => Ok(depth as i32),
+            None => err(s.pos(), format!("unknown label {}", s.atom().unwrap_or_default())),
+        }
+    }
+
+    fn unknown(&self, s: &Sexp, name: &str) -> WatError {
+        WatError { line: s.pos().line, col: s.pos().col, msg: format!("unknown instruction {name}") }
+    }
+}